@@ -0,0 +1,15 @@
+-- Table attachments
+-- Every stored upload, used for per-device quotas
+CREATE TABLE
+	IF NOT EXISTS attachments (
+		-- path relative to the file upload directory
+		path TEXT PRIMARY KEY,
+		name TEXT NOT NULL,
+		-- file size in bytes
+		size INTEGER NOT NULL,
+		-- the device that uploaded the file
+		device_id TEXT NOT NULL,
+		created_at INTEGER NOT NULL DEFAULT (unixepoch())
+	);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_device_id ON attachments (device_id);
@@ -0,0 +1,6 @@
+-- Thumbnail / video poster of an attachment,
+-- path relative to the file upload directory
+ALTER TABLE attachments ADD COLUMN thumbnail TEXT;
+
+-- Size of the generated thumbnail, counted in the storage usage of the uploader
+ALTER TABLE attachments ADD COLUMN thumbnail_size INTEGER NOT NULL DEFAULT 0;
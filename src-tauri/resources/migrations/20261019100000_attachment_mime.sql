@@ -0,0 +1,2 @@
+-- MIME type sniffed from the content of an attachment
+ALTER TABLE attachments ADD COLUMN mime TEXT;
@@ -0,0 +1,15 @@
+-- Devices registered with a paired host, NULL for devices of this host
+ALTER TABLE devices ADD COLUMN home_host TEXT;
+
+-- Hosts trusted by their certificate fingerprint, paired once both sides trust each other
+CREATE TABLE
+	IF NOT EXISTS peer_hosts (
+		fingerprint TEXT PRIMARY KEY,
+		id TEXT UNIQUE,
+		alias TEXT NOT NULL DEFAULT '',
+		url TEXT NOT NULL,
+		-- shared by both hosts, authenticates the requests between them
+		secret TEXT NOT NULL,
+		paired_at INTEGER,
+		created_at INTEGER NOT NULL DEFAULT (unixepoch())
+	);
@@ -0,0 +1,19 @@
+-- Who registered, connected, uploaded or deleted what, and from where
+CREATE TABLE
+	IF NOT EXISTS audit_log (
+		id INTEGER PRIMARY KEY AUTOINCREMENT,
+		action TEXT NOT NULL,
+		-- NULL when the request could not be tied to a device
+		device_id TEXT,
+		ip TEXT,
+		user_agent TEXT,
+		-- success or failure
+		outcome TEXT NOT NULL,
+		-- the error of a failure, or what the action touched
+		detail TEXT,
+		created_at INTEGER NOT NULL DEFAULT (unixepoch())
+	);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_device_id ON audit_log (device_id);
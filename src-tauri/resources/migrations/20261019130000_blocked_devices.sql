@@ -0,0 +1,10 @@
+-- Devices removed for good, their id and fingerprint may not register again
+CREATE TABLE
+	IF NOT EXISTS blocked_devices (
+		id TEXT PRIMARY KEY,
+		name TEXT NOT NULL DEFAULT '',
+		fingerprint_id TEXT,
+		blocked_at INTEGER NOT NULL DEFAULT (unixepoch())
+	);
+
+CREATE INDEX IF NOT EXISTS idx_blocked_devices_fingerprint_id ON blocked_devices (fingerprint_id);
@@ -0,0 +1,9 @@
+-- Access role assigned from the host, devices without a row are members
+CREATE TABLE
+	IF NOT EXISTS device_access (
+		device_id TEXT PRIMARY KEY REFERENCES devices (id) ON DELETE CASCADE,
+		role TEXT NOT NULL DEFAULT 'member',
+		-- guests only, NULL for the other roles
+		expires_at INTEGER,
+		updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+	);
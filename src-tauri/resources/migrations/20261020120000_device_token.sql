@@ -0,0 +1,4 @@
+-- Devices authenticate with a secret issued at registration, only its SHA-256 is kept.
+-- The devices registered before have none, they get one by registering again.
+ALTER TABLE devices ADD COLUMN token_hash TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_devices_token_hash ON devices (token_hash);
@@ -1,5 +1,13 @@
 use super::CmdResult;
-use crate::{cmd::StringifyErr, feat, module::device::Device};
+use crate::{
+    cmd::StringifyErr,
+    feat,
+    module::{
+        device::{BlockedDevice, Device, RegisteredDevice},
+        device_access::DeviceAccess,
+    },
+    server::discovery::DiscoveredHost,
+};
 
 /// Get device info by id
 #[tauri::command]
@@ -18,10 +26,16 @@ pub async fn devices_discover(exclude_ids: Vec<String>) -> CmdResult<Vec<Device>
     feat::devices_discover(&exclude_ids).await.stringify_err()
 }
 
-/// Create device
+/// Other Synclan hosts found on the local network
 #[tauri::command]
-pub async fn register_device(payload: Device) -> CmdResult<Device> {
-    payload.register().await.stringify_err()
+pub async fn discovered_hosts() -> CmdResult<Vec<DiscoveredHost>> {
+    Ok(feat::discovered_hosts())
+}
+
+/// Create the device of this app, or give it a new token
+#[tauri::command]
+pub async fn register_device(payload: Device) -> CmdResult<RegisteredDevice> {
+    feat::register_device(payload).await.stringify_err()
 }
 
 /// Patch device
@@ -35,3 +49,33 @@ pub async fn patch_device(payload: Device) -> CmdResult {
 pub async fn remove_device(id: String) -> CmdResult {
     feat::remove_device(id).await.stringify_err()
 }
+
+/// Remove device and keep it from registering again
+#[tauri::command]
+pub async fn block_device(id: String) -> CmdResult {
+    feat::block_device(id).await.stringify_err()
+}
+
+/// Allow a blocked device to register again
+#[tauri::command]
+pub async fn unblock_device(id: String) -> CmdResult {
+    feat::unblock_device(id).await.stringify_err()
+}
+
+/// Query blocked devices
+#[tauri::command]
+pub async fn get_blocked_devices() -> CmdResult<Vec<BlockedDevice>> {
+    feat::get_blocked_devices().await.stringify_err()
+}
+
+/// Query the assigned access roles, devices without one are members
+#[tauri::command]
+pub async fn get_device_access() -> CmdResult<Vec<DeviceAccess>> {
+    feat::get_device_access().await.stringify_err()
+}
+
+/// Assign an access role, guests need an expiry
+#[tauri::command]
+pub async fn set_device_access(payload: DeviceAccess) -> CmdResult {
+    feat::set_device_access(payload).await.stringify_err()
+}
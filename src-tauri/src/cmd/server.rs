@@ -1,12 +1,42 @@
 use super::CmdResult;
-use crate::{cmd::StringifyErr, config::Config, feat};
+use crate::{
+    cmd::StringifyErr,
+    config::Config,
+    feat,
+    module::{
+        attachment::{Attachment, DeviceUsage},
+        audit_log::{AuditLog, AuditLogFilter},
+        peer_host::PeerHost,
+    },
+    server::pairing::PairingLink,
+    utils::tls::{self, CertInfo},
+};
 use std::fs;
 
+/// Every URL the server is reachable at
 #[tauri::command]
-pub async fn get_server_domain() -> CmdResult<String> {
+pub async fn get_server_domain() -> CmdResult<Vec<String>> {
     feat::get_server_domain().await.stringify_err()
 }
 
+/// SHA-256 fingerprint of the server certificate
+#[tauri::command]
+pub async fn get_server_fingerprint() -> CmdResult<Option<String>> {
+    feat::get_server_fingerprint().await.stringify_err()
+}
+
+/// The certificate the HTTPS server presents, `None` when it is not serving HTTPS
+#[tauri::command]
+pub async fn get_server_cert_info() -> CmdResult<Option<CertInfo>> {
+    tls::ServerTls::global().info().stringify_err()
+}
+
+/// Link for the pairing QR code, with a one-time grant to register
+#[tauri::command]
+pub async fn create_pairing_link() -> CmdResult<PairingLink> {
+    feat::create_pairing_link().await.stringify_err()
+}
+
 /// Exporting a Self-Signed Certificate
 #[tauri::command]
 pub async fn export_server_cert(app_handle: tauri::AppHandle) -> CmdResult {
@@ -23,7 +53,45 @@ pub async fn clean_upload_files() -> CmdResult {
                 let _ = fs::remove_dir_all(path);
             }
         }
+        Attachment::delete_all().await.stringify_err()?;
     }
 
     Ok(())
 }
+
+/// Storage usage per device, the largest first
+#[tauri::command]
+pub async fn get_upload_usage() -> CmdResult<Vec<DeviceUsage>> {
+    feat::get_upload_usage().await.stringify_err()
+}
+
+/// Pair with another Synclan host, identified by its certificate fingerprint
+#[tauri::command]
+pub async fn pair_host(url: String, fingerprint: String) -> CmdResult<PeerHost> {
+    feat::pair_host(&url, &fingerprint).await.stringify_err()
+}
+
+/// Paired hosts and the ones waiting for the other side to pair
+#[tauri::command]
+pub async fn get_peer_hosts() -> CmdResult<Vec<PeerHost>> {
+    feat::get_peer_hosts().await.stringify_err()
+}
+
+#[tauri::command]
+pub async fn remove_peer_host(fingerprint: String) -> CmdResult {
+    feat::remove_peer_host(&fingerprint).await.stringify_err()
+}
+
+/// Audit log entries, newest first
+#[tauri::command]
+pub async fn get_audit_logs(filter: Option<AuditLogFilter>) -> CmdResult<Vec<AuditLog>> {
+    feat::get_audit_logs(&filter.unwrap_or_default()).await.stringify_err()
+}
+
+/// Save the matching audit log entries as a CSV file
+#[tauri::command]
+pub async fn export_audit_logs(app_handle: tauri::AppHandle, filter: Option<AuditLogFilter>) -> CmdResult<bool> {
+    feat::export_audit_logs(&app_handle, &filter.unwrap_or_default())
+        .await
+        .stringify_err()
+}
@@ -1,7 +1,7 @@
 use crate::{
     config::{deserialize_encrypted, serialize_encrypted},
     logging,
-    utils::{dirs, help, i18n, logging::Type},
+    utils::{dirs, help, i18n, logging::Type, sniff},
 };
 use anyhow::Result;
 use log::LevelFilter;
@@ -59,6 +59,11 @@ pub struct ISynclan {
     /// http server port
     pub http_server_port: Option<u16>,
 
+    /// IP addresses or interface names to listen on
+    /// e.g. `0.0.0.0`, `::`, `192.168.1.2`, `eth0`
+    /// empty or unset: every IPv4 interface
+    pub listen_addresses: Option<Vec<String>>,
+
     /// File Cleanup
     /// 0: No cleaning; 1: 1 day; 2: 7 days; 3: 30 days; 4: 90 days
     pub auto_file_clean: Option<i32>,
@@ -66,10 +71,101 @@ pub struct ISynclan {
     /// File upload directory
     pub file_upload_dir: Option<String>,
 
+    /// Storage quota per device in MB
+    /// 0 or unset: no limit
+    pub upload_device_quota: Option<u64>,
+
+    /// Storage quota of all uploaded files in MB
+    /// 0 or unset: no limit
+    pub upload_total_quota: Option<u64>,
+
+    /// Free disk space in MB that uploads must leave on the upload directory's disk
+    pub upload_min_free_space: Option<u64>,
+
+    /// MIME types that may be uploaded, detected from the file content
+    /// e.g. `image/*`, `application/pdf`
+    /// empty or unset: everything that is not denied
+    pub upload_mime_allowlist: Option<Vec<String>>,
+
+    /// MIME types that are rejected, takes precedence over the allowlist
+    pub upload_mime_denylist: Option<Vec<String>>,
+
+    /// Messages a device may send per minute
+    /// 0: no limit
+    pub rate_limit_messages: Option<u32>,
+
+    /// Uploads a device may start per minute
+    /// 0: no limit
+    pub rate_limit_uploads: Option<u32>,
+
+    /// Discovery requests a device may make per minute
+    /// 0: no limit
+    pub rate_limit_discovery: Option<u32>,
+
     /// Whether to enable encryption for local https server
     pub enable_encryption: Option<bool>,
 
-    /// Self-signed certificates
+    /// Plain HTTP port served next to HTTPS, for downloading the certificate on first contact,
+    /// everything else is redirected to HTTPS
+    /// 0 or unset: off
+    pub http_redirect_port: Option<u16>,
+
+    /// Origins of web clients hosted elsewhere that may call the API, matched exactly on
+    /// scheme, host and port, e.g. `https://synclan.example.com`, `http://192.168.1.5:3000`
+    /// the server's own URLs are always allowed
+    pub cors_allowed_origins: Option<Vec<String>>,
+
+    /// Whether to serve the Swagger UI at `/api/docs`
+    /// unset: only in debug builds
+    pub enable_api_docs: Option<bool>,
+
+    /// Whether to serve the LocalSend v2 protocol, so LocalSend apps can send files to this device
+    pub enable_localsend: Option<bool>,
+
+    /// PEM certificate chain to serve instead of the local CA's, leaf first
+    /// takes precedence over `tls_cert_pem`, an empty string unsets it
+    pub tls_cert_file: Option<String>,
+
+    /// PEM private key of `tls_cert_file`
+    pub tls_key_file: Option<String>,
+
+    /// Pasted PEM certificate chain, leaf first
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub tls_cert_pem: Option<String>,
+
+    /// Pasted PEM private key of `tls_cert_pem`
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub tls_key_pem: Option<String>,
+
+    /// Local root CA, signs the server certificate
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub ca_cert_pem: Option<String>,
+
+    /// Local root CA signing key
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub ca_key_pem: Option<String>,
+
+    /// Server certificate chain signed by the local CA
     #[serde(
         serialize_with = "serialize_encrypted",
         deserialize_with = "deserialize_encrypted",
@@ -78,7 +174,7 @@ pub struct ISynclan {
     )]
     pub cert_pem: Option<String>,
 
-    /// Self-signed certificate signing key
+    /// Server certificate signing key
     #[serde(
         serialize_with = "serialize_encrypted",
         deserialize_with = "deserialize_encrypted",
@@ -86,6 +182,14 @@ pub struct ISynclan {
         default
     )]
     pub signing_key_pem: Option<String>,
+
+    /// Self-signed server certificate from before the local CA, paired hosts may still pin it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub legacy_cert_pem: Option<String>,
+
+    /// Signature of the local CA fingerprint by the key of `legacy_cert_pem`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub legacy_pin_signature: Option<String>,
 }
 
 impl ISynclan {
@@ -150,12 +254,25 @@ impl ISynclan {
             auto_log_clean: Some(2), // default to 7 day
             enable_authorized_access: Some(false),
             http_server_port: Some(53317),
+            listen_addresses: Some(vec![]),
             file_upload_dir,
             auto_file_clean: Some(3), // default to 30 day
+            upload_device_quota: Some(0),
+            upload_total_quota: Some(0),
+            upload_min_free_space: Some(1024), // default to 1 GB
+            upload_mime_allowlist: Some(vec![]),
+            upload_mime_denylist: Some(vec![]),
+            rate_limit_messages: Some(120),
+            rate_limit_uploads: Some(60),
+            rate_limit_discovery: Some(60),
             #[cfg(target_os = "windows")]
             enable_encryption: Some(false),
             #[cfg(not(target_os = "windows"))]
             enable_encryption: Some(false),
+            http_redirect_port: Some(0),
+            cors_allowed_origins: Some(vec![]),
+            enable_api_docs: Some(cfg!(debug_assertions)),
+            enable_localsend: Some(false),
             ..Self::default()
         }
     }
@@ -187,11 +304,48 @@ impl ISynclan {
         patch!(authorized_access_code);
         patch!(enable_random_port);
         patch!(http_server_port);
+        patch!(listen_addresses);
         patch!(auto_file_clean);
         patch!(file_upload_dir);
+        patch!(upload_device_quota);
+        patch!(upload_total_quota);
+        patch!(upload_min_free_space);
+        patch!(upload_mime_allowlist);
+        patch!(upload_mime_denylist);
+        patch!(rate_limit_messages);
+        patch!(rate_limit_uploads);
+        patch!(rate_limit_discovery);
         patch!(enable_encryption);
+        patch!(http_redirect_port);
+        patch!(cors_allowed_origins);
+        patch!(enable_api_docs);
+        patch!(enable_localsend);
+        patch!(tls_cert_file);
+        patch!(tls_key_file);
+        patch!(tls_cert_pem);
+        patch!(tls_key_pem);
+        patch!(ca_cert_pem);
+        patch!(ca_key_pem);
         patch!(cert_pem);
         patch!(signing_key_pem);
+        patch!(legacy_cert_pem);
+        patch!(legacy_pin_signature);
+    }
+
+    /// Whether a file of the (sniffed) MIME type may be uploaded
+    pub fn is_upload_mime_allowed(&self, mime: &str) -> bool {
+        let matches_any = |patterns: &Option<Vec<String>>| {
+            patterns
+                .iter()
+                .flatten()
+                .any(|pattern| sniff::mime_matches(pattern, mime))
+        };
+
+        if matches_any(&self.upload_mime_denylist) {
+            return false;
+        }
+        self.upload_mime_allowlist.as_ref().is_none_or(|list| list.is_empty())
+            || matches_any(&self.upload_mime_allowlist)
     }
 
     /// get app log level
@@ -4,8 +4,8 @@ use crate::{
     core::handle,
     feat, logging, logging_error,
     process::AsyncHandler,
-    singleton,
-    utils::{i18n, logging::Type, resolve, window_manager::WindowManager},
+    server, singleton,
+    utils::{i18n, logging::Type, network, resolve, window_manager::WindowManager},
 };
 use anyhow::Result;
 #[allow(unused_imports)]
@@ -145,6 +145,15 @@ async fn create_tray_menu(app_handle: &AppHandle, locale: &i18n::Locale) -> Resu
         None::<&str>,
     )?;
 
+    // where the other devices reach this one, the port may differ from the configured one
+    let synclan = Config::synclan().await.latest_arc();
+    let server_address = server::HttpServer::global()
+        .port()
+        .and_then(|port| network::server_urls(&synclan, port).ok())
+        .and_then(|urls| urls.into_iter().next())
+        .unwrap_or_else(|| i18n::tr(locale, "menu.server_not_running").to_string());
+    let server_address = &MenuItem::with_id(app_handle, "server_address", server_address, false, None::<&str>)?;
+
     // open config dir
     let open_config_dir = &MenuItem::with_id(
         app_handle,
@@ -210,7 +219,7 @@ async fn create_tray_menu(app_handle: &AppHandle, locale: &i18n::Locale) -> Resu
 
     let separator = &PredefinedMenuItem::separator(app_handle)?;
 
-    let mut menu_items: Vec<&dyn IsMenuItem<Wry>> = vec![dashboard, separator];
+    let mut menu_items: Vec<&dyn IsMenuItem<Wry>> = vec![dashboard, server_address, separator];
 
     menu_items.extend_from_slice(&[open_dir, devtools, about, separator, quit]);
 
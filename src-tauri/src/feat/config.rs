@@ -2,8 +2,8 @@
 use crate::{
     config::{Config, ISynclan},
     core::{autostart, logger::Logger, tray},
-    logging, server,
-    utils::logging::Type,
+    logging, logging_error, server,
+    utils::{logging::Type, tls},
 };
 use anyhow::Result;
 use bitflags::bitflags;
@@ -17,6 +17,8 @@ bitflags! {
         const LOCALE = 1 << 2;
         const LOG_LEVEL = 1 << 3;
         const LOG_FILE = 1 << 4;
+        const TLS_CERT = 1 << 5;
+        const CORS_ORIGINS = 1 << 6;
     }
 }
 
@@ -26,13 +28,14 @@ pub async fn patch_synclan(patch: &ISynclan, need_save_file: bool) -> Result<()>
 
     let update_flags = determine_update_flags(patch);
     logging!(debug, Type::Setup, "Determined update flags: {:?}", update_flags);
-    let process_flag_result: std::result::Result<(), anyhow::Error> = {
-        process_terminated_flags(update_flags, patch).await?;
-        Ok(())
-    };
+    let process_flag_result = process_terminated_flags(update_flags, patch).await;
 
     if let Err(err) = process_flag_result {
         Config::synclan().await.discard();
+        // a certificate swapped in before a later flag failed goes back with the config
+        if update_flags.contains(UpdateFlags::TLS_CERT) {
+            logging_error!(Type::Server, tls::ServerTls::global().reload().await);
+        }
         return Err(err);
     }
     Config::synclan().await.apply();
@@ -49,12 +52,28 @@ fn determine_update_flags(patch: &ISynclan) -> UpdateFlags {
     let locale = &patch.locale;
     let http_server_port = &patch.http_server_port;
     let enable_encryption = &patch.enable_encryption;
+    let enable_random_port = &patch.enable_random_port;
+    let listen_addresses = &patch.listen_addresses;
+    let http_redirect_port = &patch.http_redirect_port;
+    let enable_localsend = &patch.enable_localsend;
+    let enable_api_docs = &patch.enable_api_docs;
     let file_upload_dir = &patch.file_upload_dir;
     let log_level = &patch.app_log_level;
     let log_max_size = patch.app_log_max_size;
     let log_max_count = patch.app_log_max_count;
+    let tls_cert = patch.tls_cert_file.is_some()
+        || patch.tls_key_file.is_some()
+        || patch.tls_cert_pem.is_some()
+        || patch.tls_key_pem.is_some();
 
-    let restart_http_server = http_server_port.is_some() || enable_encryption.is_some() || file_upload_dir.is_some();
+    let restart_http_server = http_server_port.is_some()
+        || enable_random_port.is_some()
+        || listen_addresses.is_some()
+        || enable_encryption.is_some()
+        || http_redirect_port.is_some()
+        || enable_localsend.is_some()
+        || enable_api_docs.is_some()
+        || file_upload_dir.is_some();
 
     let mut update_flags = UpdateFlags::empty();
 
@@ -73,6 +92,12 @@ fn determine_update_flags(patch: &ISynclan) -> UpdateFlags {
     if log_max_size.is_some() || log_max_count.is_some() {
         update_flags.insert(UpdateFlags::LOG_FILE);
     }
+    if tls_cert {
+        update_flags.insert(UpdateFlags::TLS_CERT);
+    }
+    if patch.cors_allowed_origins.is_some() {
+        update_flags.insert(UpdateFlags::CORS_ORIGINS);
+    }
 
     update_flags
 }
@@ -84,10 +109,6 @@ async fn process_terminated_flags(update_flags: UpdateFlags, patch: &ISynclan) -
     if update_flags.contains(UpdateFlags::LOCALE) {
         tray::Tray::global().update_menu().await?;
     }
-    if update_flags.contains(UpdateFlags::RESTART_HTTP_SERVER) {
-        // TODO 还是重启应用比较好
-        // server::restart_http_server().await?;
-    }
     if update_flags.contains(UpdateFlags::LOG_LEVEL) {
         Logger::global().update_log_level(patch.get_log_level())?;
     }
@@ -96,6 +117,25 @@ async fn process_terminated_flags(update_flags: UpdateFlags, patch: &ISynclan) -
         let log_max_count = patch.app_log_max_count.unwrap_or(8);
         Logger::global().update_log_config(log_max_size, log_max_count).await?;
     }
+    if update_flags.contains(UpdateFlags::TLS_CERT) {
+        // an invalid certificate is rejected and the patch discarded
+        tls::ServerTls::global().reload().await?;
+    }
+    if update_flags.contains(UpdateFlags::CORS_ORIGINS) {
+        // takes effect on the next request, no restart needed
+        let synclan = Config::synclan().await.latest_arc();
+        server::cors::CorsOrigins::global().refresh(&synclan, server::listening_port(&synclan))?;
+    }
+    // last, a step failing after the restart would leave the server on a discarded config
+    if update_flags.contains(UpdateFlags::RESTART_HTTP_SERVER)
+        && let Err(err) = server::restart_http_server().await
+    {
+        // drop the draft now so the retry binds with the config the server ran with
+        Config::synclan().await.discard();
+        logging!(error, Type::Server, "Restart failed, rolling back: {err:?}");
+        logging_error!(Type::Server, server::restart_http_server().await);
+        return Err(err);
+    }
 
     Ok(())
 }
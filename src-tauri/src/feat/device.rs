@@ -1,4 +1,13 @@
-use crate::module::device::Device;
+use crate::{
+    module::{
+        device::{BlockedDevice, Device, RegisteredDevice},
+        device_access::DeviceAccess,
+    },
+    server::{
+        self,
+        discovery::{DiscoveredHost, Discovery},
+    },
+};
 use anyhow::Result;
 
 pub async fn get_device_by_id(id: String) -> Result<Option<Device>> {
@@ -13,6 +22,49 @@ pub async fn devices_discover(exclude_ids: &[String]) -> Result<Vec<Device>> {
     Device::get_not_in(exclude_ids).await
 }
 
+pub fn discovered_hosts() -> Vec<DiscoveredHost> {
+    Discovery::global().hosts()
+}
+
+/// Registers the device of this app, or gives it a new token when its webview lost the previous one.
+/// Unlike a device registering over HTTP, the app is trusted with an id that is taken already.
+pub async fn register_device(device: Device) -> Result<RegisteredDevice> {
+    let device = match Device::get_by_id(&device.id).await? {
+        Some(device) => device,
+        None => device.register().await?,
+    };
+    let token = Device::issue_token(&device.id).await?;
+    Ok(RegisteredDevice { device, token })
+}
+
+/// Removes the device and cuts off its live sessions, it may register again
 pub async fn remove_device(id: String) -> Result<()> {
-    Device::remove(&id).await
+    Device::remove(&id).await?;
+    server::revoke_device(&id).await
+}
+
+/// Removes the device and keeps it from registering again with the same id or fingerprint
+pub async fn block_device(id: String) -> Result<()> {
+    BlockedDevice::block(&id).await?;
+    server::revoke_device(&id).await
+}
+
+pub async fn unblock_device(id: String) -> Result<()> {
+    BlockedDevice::unblock(&id).await
+}
+
+pub async fn get_blocked_devices() -> Result<Vec<BlockedDevice>> {
+    BlockedDevice::get_all().await
+}
+
+pub async fn get_device_access() -> Result<Vec<DeviceAccess>> {
+    DeviceAccess::get_all().await
+}
+
+/// Applies from the next request or event of the device. Its sockets are disconnected,
+/// they reconnect with the new role or, for an expired guest, not at all.
+pub async fn set_device_access(access: DeviceAccess) -> Result<()> {
+    access.assign().await?;
+    server::disconnect_device(&access.device_id);
+    Ok(())
 }
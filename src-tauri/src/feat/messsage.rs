@@ -1,4 +1,7 @@
-use crate::module::message::{CursorPaginatedMessages, Message, OfflineMessagesInfoMap};
+use crate::{
+    module::message::{CursorPaginatedMessages, Message, OfflineMessagesInfoMap},
+    server::signed_url::UrlSigner,
+};
 use anyhow::Result;
 
 pub async fn get_messages(
@@ -7,15 +10,25 @@ pub async fn get_messages(
     last_id: Option<i32>,
     page_size: u32,
 ) -> Result<CursorPaginatedMessages> {
-    Message::get_messages(&self_id, &target_id, last_id, page_size).await
+    let mut data = Message::get_messages(&self_id, &target_id, last_id, page_size).await?;
+    UrlSigner::global().sign_messages(&mut data.messages).await?;
+    Ok(data)
 }
 
 pub async fn get_offline_messages(receiver: &str) -> Result<Vec<Message>> {
-    Message::get_offline_messages(receiver).await
+    let mut messages = Message::get_offline_messages(receiver).await?;
+    UrlSigner::global().sign_messages(&mut messages).await?;
+    Ok(messages)
 }
 
 pub async fn get_offline_msgs_summary(receiver: &str) -> Result<Option<OfflineMessagesInfoMap>> {
-    Message::get_offline_msgs_summary(receiver).await
+    let mut summary = Message::get_offline_msgs_summary(receiver).await?;
+    if let Some(summary) = summary.as_mut() {
+        UrlSigner::global()
+            .sign_messages(summary.values_mut().map(|group| &mut group.last_msg))
+            .await?;
+    }
+    Ok(summary)
 }
 
 pub async fn delete_conversation_messages(self_id: String, target_id: String) -> Result<()> {
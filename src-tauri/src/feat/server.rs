@@ -1,73 +1,78 @@
 use crate::{
-    config::{Config, ISynclan},
-    logging,
-    utils::{logging::Type, tls},
+    config::Config,
+    logging, logging_error,
+    module::{
+        attachment::{Attachment, DeviceUsage},
+        audit_log::{AuditLog, AuditLogFilter},
+        peer_host::PeerHost,
+    },
+    server::{
+        self, federation,
+        pairing::{PairingGrants, PairingLink},
+    },
+    utils::{logging::Type, network, tls},
 };
 use anyhow::{Result, anyhow};
 use chrono::{Local, NaiveDate};
-use local_ip_address::local_ip;
-use std::{net::IpAddr, path::Path};
+use std::path::Path;
 use tauri_plugin_dialog::{DialogExt, FilePath};
 use tokio::fs;
 
-/// Returns the full server domain (including protocol, IP address, and port).
-///
-/// This function determines whether to use `http` or `https` based on the
-/// `enable_encryption` configuration flag. It also retrieves the configured
-/// server port, defaulting to `53317` if none is specified.
+/// Every URL other devices can reach the server at (protocol, IP address and port),
+/// the one on the interface with the default route first.
 ///
 /// # Example
 /// - `http://192.168.1.10:53317`
-/// - `https://192.168.1.10:53317`
-///
-/// # Returns
-/// A `Result` containing the constructed domain string on success,
-/// or an error message on failure.
+/// - `https://[fd00::10]:53317`
+pub async fn get_server_domain() -> Result<Vec<String>> {
+    let synclan = Config::synclan().await.latest_arc();
+    network::server_urls(&synclan, server::listening_port(&synclan))
+}
+
+/// SHA-256 fingerprint of the server certificate, `None` when serving plain HTTP.
 ///
-/// # Errors
-/// Returns an error if the local IP address cannot be determined.
-pub async fn get_server_domain() -> Result<String> {
+/// Shown next to the address so that a client can tell this host from an impostor.
+pub async fn get_server_fingerprint() -> Result<Option<String>> {
     let synclan = Config::synclan().await.latest_arc();
-    let ip = local_ip()?;
-    let port = synclan.http_server_port.unwrap_or(53317);
-    let protocol = if synclan.enable_encryption.unwrap_or(false) {
-        "https"
-    } else {
-        "http"
-    };
+    if !synclan.enable_encryption.unwrap_or(false) {
+        return Ok(None);
+    }
+    Ok(tls::server_fingerprint(&synclan))
+}
+
+/// Link for the pairing QR code: the server address and a one-time grant,
+/// a phone scanning it registers without the access code.
+///
+/// The fingerprint is only shown next to the QR code. The page can't see the certificate
+/// it was served with, the user compares it with the one the browser shows.
+pub async fn create_pairing_link() -> Result<PairingLink> {
+    let domain = get_server_domain()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("The server is not reachable from the network"))?;
+    let fingerprint = get_server_fingerprint().await?;
+    let (grant, expires_at) = PairingGrants::global().issue()?;
 
-    Ok(format!("{}://{}:{}", protocol, ip, port))
+    let url = format!("{domain}/?grant={grant}");
+
+    Ok(PairingLink {
+        url,
+        fingerprint,
+        grant,
+        expires_at,
+    })
 }
 
-/// Exporting a Self-Signed Certificate
+/// Exporting the local CA certificate, trusting it covers every certificate it signs
 pub async fn export_server_cert(app_handle: &tauri::AppHandle) -> Result<()> {
     let Some(FilePath::Path(folder_path)) = app_handle.dialog().file().blocking_pick_folder() else {
         return Ok(());
     };
-    // certificate content
-    let cert_pem = Config::synclan().await.data_arc().cert_pem.clone();
-    let cert_pem = match cert_pem {
-        Some(cert) => cert,
-        None => {
-            let ip: IpAddr = "0.0.0.0".parse()?;
-            let (cert_pem, signing_key_pem) = tls::generate_cert(&ip)?;
-
-            // save to config
-            let patch = ISynclan {
-                cert_pem: Some(cert_pem.clone()),
-                signing_key_pem: Some(signing_key_pem.clone()),
-                ..ISynclan::default()
-            };
-            Config::synclan().await.edit_draft(|s| s.patch_config(&patch));
-            Config::synclan().await.apply();
-            Config::synclan().await.data_arc().save_config().await?;
-
-            cert_pem
-        },
-    };
-    let cert_path = folder_path.join("synclan.crt");
+    let (ca_cert_pem, _) = tls::ensure_ca().await?;
+    let cert_path = folder_path.join("synclan-ca.crt");
     // writing to a file
-    tokio::fs::write(&cert_path, cert_pem).await?;
+    tokio::fs::write(&cert_path, ca_cert_pem).await?;
 
     Ok(())
 }
@@ -119,9 +124,50 @@ pub async fn uploaded_files_auto_cleanup() -> Result<()> {
         if age > day {
             let folder_path = entry.path();
             let _ = fs::remove_dir_all(folder_path).await;
+            logging_error!(Type::Server, Attachment::delete_by_dir(folder_name).await);
             logging!(info, Type::Server, "Delete uploaded files: {folder_name}");
         }
     }
 
     Ok(())
 }
+
+/// Storage usage of every device, the largest first
+pub async fn get_upload_usage() -> Result<Vec<DeviceUsage>> {
+    Attachment::get_usage_summary().await
+}
+
+/// Trusts the host with `fingerprint` and pairs with it once it trusts this host too
+pub async fn pair_host(url: &str, fingerprint: &str) -> Result<PeerHost> {
+    federation::pair(url, fingerprint).await
+}
+
+pub async fn get_peer_hosts() -> Result<Vec<PeerHost>> {
+    PeerHost::get_all().await
+}
+
+/// Unpairs the host, its devices disappear from this host
+pub async fn remove_peer_host(fingerprint: &str) -> Result<()> {
+    PeerHost::remove(fingerprint).await
+}
+
+pub async fn get_audit_logs(filter: &AuditLogFilter) -> Result<Vec<AuditLog>> {
+    AuditLog::query(filter).await
+}
+
+/// Saves the matching entries as CSV, `false` when the dialog was cancelled
+pub async fn export_audit_logs(app_handle: &tauri::AppHandle, filter: &AuditLogFilter) -> Result<bool> {
+    let Some(FilePath::Path(path)) = app_handle
+        .dialog()
+        .file()
+        .set_file_name("synclan-audit-log.csv")
+        .add_filter("CSV", &["csv"])
+        .blocking_save_file()
+    else {
+        return Ok(false);
+    };
+    let logs = AuditLog::query(filter).await?;
+    fs::write(&path, AuditLog::to_csv(&logs)).await?;
+
+    Ok(true)
+}
@@ -86,15 +86,30 @@ pub fn run() {
             cmd::is_admin,
             // server
             cmd::get_server_domain,
+            cmd::get_server_fingerprint,
+            cmd::get_server_cert_info,
+            cmd::create_pairing_link,
             cmd::clean_upload_files,
             cmd::export_server_cert,
+            cmd::get_upload_usage,
+            cmd::pair_host,
+            cmd::get_peer_hosts,
+            cmd::remove_peer_host,
+            cmd::get_audit_logs,
+            cmd::export_audit_logs,
             // device
             cmd::get_device_by_id,
             cmd::get_devices,
             cmd::devices_discover,
+            cmd::discovered_hosts,
             cmd::register_device,
             cmd::patch_device,
             cmd::remove_device,
+            cmd::block_device,
+            cmd::unblock_device,
+            cmd::get_blocked_devices,
+            cmd::get_device_access,
+            cmd::set_device_access,
             // message
             cmd::get_messages,
             cmd::get_offline_messages,
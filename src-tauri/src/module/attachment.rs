@@ -0,0 +1,172 @@
+use crate::utils::db;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    /// Path relative to the file upload directory, e.g. `2025-06-04/video.mp4`
+    pub path: String,
+    pub name: String,
+    /// File size in bytes
+    pub size: i64,
+    pub device_id: String,
+    /// Scaled down preview of an image or video, e.g. `2025-06-04/.thumbnails/video.mp4.jpg`
+    pub thumbnail: Option<String>,
+    /// Size of the thumbnail in bytes, counted in the usage of the device
+    #[serde(skip)]
+    pub thumbnail_size: i64,
+    /// MIME type detected from the content at upload
+    pub mime: Option<String>,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub created_at: Option<i64>,
+}
+
+impl Attachment {
+    pub async fn create(&self) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO attachments (path, name, size, device_id, mime)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&self.path)
+        .bind(&self.name)
+        .bind(self.size)
+        .bind(&self.device_id)
+        .bind(&self.mime)
+        .execute(&db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_by_path(path: &str) -> Result<Option<Attachment>> {
+        let db_pool = db::get_db_pool()?;
+        let attachment = sqlx::query_as::<_, Attachment>(
+            "SELECT path, name, size, device_id, thumbnail, thumbnail_size, mime, created_at FROM attachments WHERE path = $1",
+        )
+        .bind(path)
+        .fetch_optional(&db_pool)
+        .await?;
+
+        Ok(attachment)
+    }
+
+    pub async fn set_thumbnail(path: &str, thumbnail: &str, size: u64) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+
+        sqlx::query("UPDATE attachments SET thumbnail = $1, thumbnail_size = $2 WHERE path = $3")
+            .bind(thumbnail)
+            .bind(size as i64)
+            .bind(path)
+            .execute(&db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Thumbnails of the given attachments, `path -> thumbnail`
+    pub async fn get_thumbnails(paths: &[String]) -> Result<HashMap<String, String>> {
+        if paths.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let db_pool = db::get_db_pool()?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new(
+            "SELECT path, thumbnail FROM attachments WHERE thumbnail IS NOT NULL AND path IN (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for path in paths {
+            separated.push_bind(path);
+        }
+        separated.push_unseparated(")");
+
+        let thumbnails = query_builder
+            .build_query_as::<(String, String)>()
+            .fetch_all(&db_pool)
+            .await?;
+
+        Ok(thumbnails.into_iter().collect())
+    }
+
+    /// Total size in bytes of the files uploaded by the device, with their thumbnails
+    pub async fn device_usage(device_id: &str) -> Result<u64> {
+        let db_pool = db::get_db_pool()?;
+        let usage: i64 =
+            sqlx::query_scalar("SELECT COALESCE(SUM(size + thumbnail_size), 0) FROM attachments WHERE device_id = $1")
+                .bind(device_id)
+                .fetch_one(&db_pool)
+                .await?;
+
+        Ok(usage.max(0) as u64)
+    }
+
+    /// Total size in bytes of all uploaded files, with their thumbnails
+    pub async fn total_usage() -> Result<u64> {
+        let db_pool = db::get_db_pool()?;
+        let usage: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(size + thumbnail_size), 0) FROM attachments")
+            .fetch_one(&db_pool)
+            .await?;
+
+        Ok(usage.max(0) as u64)
+    }
+
+    /// Storage usage of every device, the largest first
+    pub async fn get_usage_summary() -> Result<Vec<DeviceUsage>> {
+        let db_pool = db::get_db_pool()?;
+        let usage = sqlx::query_as::<_, DeviceUsage>(
+            r#"
+            SELECT
+                a.device_id,
+                d.name AS device_name,
+                SUM(a.size + a.thumbnail_size) AS total_size,
+                COUNT(*) AS file_count
+            FROM attachments a
+            LEFT JOIN devices d ON d.id = a.device_id
+            GROUP BY a.device_id
+            ORDER BY total_size DESC
+            "#,
+        )
+        .fetch_all(&db_pool)
+        .await?;
+
+        Ok(usage)
+    }
+
+    /// Remove the records of a cleaned up upload folder, e.g. `2025-06-04`
+    pub async fn delete_by_dir(dir: &str) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+
+        sqlx::query("DELETE FROM attachments WHERE path LIKE $1 || '/%'")
+            .bind(dir)
+            .execute(&db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_all() -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+
+        sqlx::query("DELETE FROM attachments").execute(&db_pool).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceUsage {
+    pub device_id: String,
+    /// `None` if the device has been removed
+    pub device_name: Option<String>,
+    /// Total size in bytes
+    pub total_size: i64,
+    pub file_count: i64,
+}
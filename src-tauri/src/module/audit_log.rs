@@ -0,0 +1,211 @@
+use crate::utils::db;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Register,
+    Connect,
+    Upload,
+    DeleteMessage,
+    DeleteConversation,
+    VerifyAccessCode,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLog {
+    #[serde(default)]
+    pub id: i64,
+    pub action: AuditAction,
+    pub device_id: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    pub detail: Option<String>,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub created_at: Option<i64>,
+}
+
+/// Every field narrows the result, timestamps are in milliseconds like the ones returned
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogFilter {
+    pub device_id: Option<String>,
+    pub action: Option<AuditAction>,
+    pub outcome: Option<AuditOutcome>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// Newest first, `None` for all of them
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl AuditAction {
+    /// The actions a client without credentials attempts
+    pub fn is_authentication(self) -> bool {
+        matches!(
+            self,
+            AuditAction::Register | AuditAction::Connect | AuditAction::VerifyAccessCode
+        )
+    }
+}
+
+impl AuditLog {
+    pub async fn create(&self) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (action, device_id, ip, user_agent, outcome, detail)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(self.action)
+        .bind(&self.device_id)
+        .bind(&self.ip)
+        .bind(&self.user_agent)
+        .bind(self.outcome)
+        .bind(&self.detail)
+        .execute(&db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn query(filter: &AuditLogFilter) -> Result<Vec<AuditLog>> {
+        let db_pool = db::get_db_pool()?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new("SELECT * FROM audit_log WHERE 1 = 1");
+        if let Some(device_id) = &filter.device_id {
+            query_builder.push(" AND device_id = ").push_bind(device_id);
+        }
+        if let Some(action) = filter.action {
+            query_builder.push(" AND action = ").push_bind(action);
+        }
+        if let Some(outcome) = filter.outcome {
+            query_builder.push(" AND outcome = ").push_bind(outcome);
+        }
+        if let Some(since) = filter.since {
+            query_builder.push(" AND created_at >= ").push_bind(since / 1000);
+        }
+        if let Some(until) = filter.until {
+            query_builder.push(" AND created_at <= ").push_bind(until / 1000);
+        }
+        query_builder.push(" ORDER BY id DESC");
+        if let Some(limit) = filter.limit {
+            query_builder.push(" LIMIT ").push_bind(limit);
+            query_builder.push(" OFFSET ").push_bind(filter.offset.unwrap_or(0));
+        }
+
+        let logs = query_builder.build_query_as::<AuditLog>().fetch_all(&db_pool).await?;
+
+        Ok(logs)
+    }
+
+    /// Deletes the entries older than `max_age_days`, then all but the newest `max_rows`.
+    /// Returns how many were deleted.
+    pub async fn prune(max_age_days: Option<i64>, max_rows: i64) -> Result<u64> {
+        let db_pool = db::get_db_pool()?;
+        let mut deleted = 0;
+        if let Some(days) = max_age_days {
+            deleted += sqlx::query("DELETE FROM audit_log WHERE created_at < unixepoch() - $1 * 86400")
+                .bind(days)
+                .execute(&db_pool)
+                .await?
+                .rows_affected();
+        }
+        deleted += sqlx::query(
+            "DELETE FROM audit_log WHERE id <= (SELECT id FROM audit_log ORDER BY id DESC LIMIT 1 OFFSET $1)",
+        )
+        .bind(max_rows)
+        .execute(&db_pool)
+        .await?
+        .rows_affected();
+
+        Ok(deleted)
+    }
+
+    /// CSV with a header row, times in local time
+    pub fn to_csv(logs: &[AuditLog]) -> String {
+        let mut csv = String::from("time,action,outcome,device_id,ip,user_agent,detail\n");
+        for log in logs {
+            let time = log
+                .created_at
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .map(|time| time.with_timezone(&Local).to_rfc3339())
+                .unwrap_or_default();
+            let fields = [
+                time,
+                serde_plain(&log.action),
+                serde_plain(&log.outcome),
+                log.device_id.clone().unwrap_or_default(),
+                log.ip.clone().unwrap_or_default(),
+                log.user_agent.clone().unwrap_or_default(),
+                log.detail.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// The serde name of a unit variant, e.g. `delete_message`
+fn serde_plain<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(ToOwned::to_owned))
+        .unwrap_or_default()
+}
+
+/// Quoted when it contains a separator, a quote or a line break. A leading `=`, `+`, `-`
+/// or `@` is prefixed with `'` so that spreadsheets don't run it as a formula.
+fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{field}")
+    } else {
+        field.to_owned()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv() {
+        let log = AuditLog {
+            id: 1,
+            action: AuditAction::DeleteMessage,
+            device_id: Some("device".into()),
+            ip: Some("192.168.1.2".into()),
+            user_agent: Some("Mozilla/5.0 (X11, Linux)".into()),
+            outcome: AuditOutcome::Failure,
+            detail: Some("=HYPERLINK(\"x\")".into()),
+            created_at: None,
+        };
+
+        assert_eq!(
+            AuditLog::to_csv(&[log]),
+            "time,action,outcome,device_id,ip,user_agent,detail\n\
+             ,delete_message,failure,device,192.168.1.2,\"Mozilla/5.0 (X11, Linux)\",\"'=HYPERLINK(\"\"x\"\")\"\n"
+        );
+    }
+}
@@ -1,16 +1,19 @@
 use crate::utils::db;
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use sqlx::{QueryBuilder, Sqlite};
 use utoipa::ToSchema;
 
-#[derive(Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, sqlx::Type)]
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, ToSchema, PartialEq, sqlx::Type)]
 #[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum DeviceRole {
     Host,
     #[default]
     Client,
+    /// A LocalSend app, it only sends files over the LocalSend protocol
+    LocalSend,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
@@ -23,13 +26,33 @@ pub struct Device {
     pub role: DeviceRole,
     pub platform: Option<String>,
     pub browser: Option<String>,
+    /// Id of the paired host the device is registered with, `None` for devices of this host
+    #[serde(default)]
+    pub home_host: Option<String>,
     #[serde(default, with = "super::unix_timestamp_ms")]
     pub created_at: Option<i64>,
     #[serde(default, with = "super::unix_timestamp_ms")]
     pub updated_at: Option<i64>,
 }
 
+/// A device that just registered, with the bearer token it authenticates with.
+/// The token is only returned here, the host keeps its SHA-256.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredDevice {
+    #[serde(flatten)]
+    pub device: Device,
+    pub token: String,
+}
+
 impl Device {
+    /// Whether the device may authenticate over HTTP or the socket
+    pub fn can_authenticate(&self) -> bool {
+        // a LocalSend app only announced its fingerprint, anyone on the network could have,
+        // the devices of a paired host authenticate with that host
+        self.role != DeviceRole::LocalSend && self.home_host.is_none()
+    }
+
     pub async fn touch(id: &str) -> Result<()> {
         let db_pool = db::get_db_pool()?;
 
@@ -51,7 +74,7 @@ impl Device {
     pub async fn get_by_id(id: &str) -> Result<Option<Device>> {
         let db_pool = db::get_db_pool()?;
         let device = sqlx::query_as::<_, Device>(
-            "SELECT id, name, avatar, fingerprint_id, role, platform, browser, created_at, updated_at FROM devices WHERE id = $1",
+            "SELECT id, name, avatar, fingerprint_id, role, platform, browser, home_host, created_at, updated_at FROM devices WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&db_pool)
@@ -60,10 +83,37 @@ impl Device {
         Ok(device)
     }
 
+    /// The device a bearer token was issued to
+    pub async fn get_by_token(token: &str) -> Result<Option<Device>> {
+        let db_pool = db::get_db_pool()?;
+        let device = sqlx::query_as::<_, Device>(
+            "SELECT id, name, avatar, fingerprint_id, role, platform, browser, home_host, created_at, updated_at FROM devices WHERE token_hash = $1",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(&db_pool)
+        .await?;
+
+        Ok(device)
+    }
+
+    /// Whether `path` is the current avatar of a device of this host,
+    /// those of a paired host are stored over there
+    pub async fn is_avatar(path: &str) -> Result<bool> {
+        let db_pool = db::get_db_pool()?;
+        let is_avatar = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM devices WHERE avatar = $1 AND home_host IS NULL)",
+        )
+        .bind(path)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(is_avatar)
+    }
+
     pub async fn get_host_device(id: &str) -> Result<Option<Device>> {
         let db_pool = db::get_db_pool()?;
         let device = sqlx::query_as::<_, Device>(
-          "SELECT id, name, avatar, fingerprint_id, role, platform, browser, created_at, updated_at FROM devices WHERE id = $1",
+          "SELECT id, name, avatar, fingerprint_id, role, platform, browser, home_host, created_at, updated_at FROM devices WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&db_pool)
@@ -82,9 +132,10 @@ impl Device {
                         role,
                         platform,
                         browser,
+                        home_host,
                         created_at,
                         updated_at
-                    FROM devices WHERE role = 'host' LIMIT 1
+                    FROM devices WHERE role = 'host' AND home_host IS NULL LIMIT 1
                     "#,
             )
             .fetch_optional(&db_pool)
@@ -108,6 +159,7 @@ impl Device {
                     role,
                     platform,
                     browser,
+                    home_host,
                     created_at,
                     updated_at
                 FROM devices WHERE id != $1
@@ -129,6 +181,7 @@ impl Device {
                     role,
                     platform,
                     browser,
+                    home_host,
                     created_at,
                     updated_at
                 FROM devices
@@ -159,6 +212,7 @@ impl Device {
                 role,
                 platform,
                 browser,
+                home_host,
                 created_at,
                 updated_at
             FROM devices WHERE id NOT IN (
@@ -197,6 +251,83 @@ impl Device {
         Ok(device)
     }
 
+    /// Registers the device with a new token, `None` if the id is taken. An id alone never
+    /// gets a token, a client registered before tokens existed pairs again as a new device.
+    pub async fn register_with_token(&self) -> Result<Option<RegisteredDevice>> {
+        let token = new_token()?;
+        let db_pool = db::get_db_pool()?;
+        let device = sqlx::query_as::<_, Device>(
+            r#"
+            INSERT INTO devices (id, name, avatar, role, platform, browser, token_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (id) DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(&self.id)
+        .bind(&self.name)
+        .bind(&self.avatar)
+        .bind(&self.role)
+        .bind(&self.platform)
+        .bind(&self.browser)
+        .bind(hash_token(&token))
+        .fetch_optional(&db_pool)
+        .await?;
+
+        Ok(device.map(|device| RegisteredDevice { device, token }))
+    }
+
+    /// Replaces the token of a registered device, the previous one stops working
+    pub async fn issue_token(id: &str) -> Result<String> {
+        let token = new_token()?;
+        let db_pool = db::get_db_pool()?;
+        let updated = sqlx::query("UPDATE devices SET token_hash = $1 WHERE id = $2")
+            .bind(hash_token(&token))
+            .bind(id)
+            .execute(&db_pool)
+            .await?
+            .rows_affected();
+        if updated == 0 {
+            bail!("Device {id} is not registered");
+        }
+
+        Ok(token)
+    }
+
+    /// Registers a device that is not registered with this host (e.g. a LocalSend app
+    /// or a device of a paired host), or refreshes it if it is already known.
+    pub async fn upsert(&self) -> Result<Device> {
+        let db_pool = db::get_db_pool()?;
+        let device = sqlx::query_as::<_, Device>(
+            r#"
+            INSERT INTO devices (id, name, avatar, fingerprint_id, role, platform, browser, home_host)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE SET
+                name = excluded.name,
+                avatar = excluded.avatar,
+                fingerprint_id = excluded.fingerprint_id,
+                role = excluded.role,
+                platform = excluded.platform,
+                browser = excluded.browser,
+                home_host = excluded.home_host,
+                updated_at = unixepoch()
+            RETURNING *
+            "#,
+        )
+        .bind(&self.id)
+        .bind(&self.name)
+        .bind(&self.avatar)
+        .bind(&self.fingerprint_id)
+        .bind(&self.role)
+        .bind(&self.platform)
+        .bind(&self.browser)
+        .bind(&self.home_host)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(device)
+    }
+
     pub async fn patch(&self) -> Result<()> {
         let db_pool = db::get_db_pool()?;
         let mut query_builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new("UPDATE devices SET ");
@@ -228,6 +359,51 @@ impl Device {
         Ok(())
     }
 
+    /// Devices registered with this host, what a paired host gets to see
+    pub async fn get_local() -> Result<Vec<Device>> {
+        let db_pool = db::get_db_pool()?;
+        let devices = sqlx::query_as::<_, Device>(
+            r#"
+            SELECT
+                id,
+                name,
+                avatar,
+                fingerprint_id,
+                role,
+                platform,
+                browser,
+                home_host,
+                created_at,
+                updated_at
+            FROM devices WHERE home_host IS NULL
+            ORDER BY updated_at DESC
+            "#,
+        )
+        .fetch_all(&db_pool)
+        .await?;
+
+        Ok(devices)
+    }
+
+    /// Forgets the devices of a peer host that are gone there
+    pub async fn remove_remote_not_in(home_host: &str, ids: &[String]) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new("DELETE FROM devices WHERE home_host = ");
+        query_builder.push_bind(home_host);
+        if !ids.is_empty() {
+            query_builder.push(" AND id NOT IN (");
+            let mut separated = query_builder.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+            separated.push_unseparated(")");
+        }
+
+        query_builder.build().execute(&db_pool).await?;
+
+        Ok(())
+    }
+
     pub async fn remove(id: &str) -> Result<()> {
         let db_pool = db::get_db_pool()?;
 
@@ -240,6 +416,100 @@ impl Device {
     }
 }
 
+fn new_token() -> Result<String> {
+    let mut token = [0u8; 32];
+    getrandom::fill(&mut token).map_err(|err| anyhow!("{err}"))?;
+    Ok(token.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Tokens are random, a plain hash is enough to look them up without keeping them
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token))
+}
+
+/// A device that was removed for good, neither its id nor its fingerprint may register again
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockedDevice {
+    pub id: String,
+    pub name: String,
+    pub fingerprint_id: Option<String>,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub blocked_at: Option<i64>,
+}
+
+impl BlockedDevice {
+    /// Removes the device and remembers its id and fingerprint
+    pub async fn block(id: &str) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+        let mut tx = db_pool.begin().await?;
+
+        let device =
+            sqlx::query_as::<_, (String, Option<String>)>("SELECT name, fingerprint_id FROM devices WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let (name, fingerprint_id) = device.unwrap_or_default();
+        sqlx::query(
+            r#"
+            INSERT INTO blocked_devices (id, name, fingerprint_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(fingerprint_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM devices WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn unblock(id: &str) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+        sqlx::query("DELETE FROM blocked_devices WHERE id = $1")
+            .bind(id)
+            .execute(&db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_all() -> Result<Vec<BlockedDevice>> {
+        let db_pool = db::get_db_pool()?;
+        let devices = sqlx::query_as::<_, BlockedDevice>("SELECT * FROM blocked_devices ORDER BY blocked_at DESC")
+            .fetch_all(&db_pool)
+            .await?;
+
+        Ok(devices)
+    }
+
+    /// Whether the id or the fingerprint belongs to a blocked device
+    pub async fn is_blocked(id: &str, fingerprint_id: Option<&str>) -> Result<bool> {
+        let db_pool = db::get_db_pool()?;
+        let blocked: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM blocked_devices
+                WHERE id = $1 OR (fingerprint_id IS NOT NULL AND fingerprint_id = $2)
+            )
+            "#,
+        )
+        .bind(id)
+        .bind(fingerprint_id)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(blocked)
+    }
+}
+
 #[derive(Debug, Default, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DevicePatch {
@@ -286,3 +556,58 @@ impl DevicePatch {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::DBManager;
+
+    fn device(id: &str, role: DeviceRole) -> Device {
+        Device {
+            id: id.to_string(),
+            name: id.to_string(),
+            role,
+            ..Device::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_with_token() {
+        DBManager::global().init_in_memory().await.unwrap();
+
+        let registered = device("device-new", DeviceRole::Client)
+            .register_with_token()
+            .await
+            .unwrap()
+            .unwrap();
+        let authenticated = Device::get_by_token(&registered.token).await.unwrap().unwrap();
+        assert_eq!(authenticated.id, "device-new");
+
+        // a taken id is never registered again, a client from before tokens included
+        device("device-legacy", DeviceRole::Client).register().await.unwrap();
+        device("device-host", DeviceRole::Host).register().await.unwrap();
+        for id in ["device-new", "device-legacy", "device-host"] {
+            let mallory = device(id, DeviceRole::Client);
+            assert!(mallory.register_with_token().await.unwrap().is_none(), "{id}");
+        }
+        assert!(Device::get_by_token(&registered.token).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_issue_token() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let registered = device("device-reissued", DeviceRole::Client)
+            .register_with_token()
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the previous token stops working
+        let token = Device::issue_token("device-reissued").await.unwrap();
+        assert!(Device::get_by_token(&registered.token).await.unwrap().is_none());
+        let authenticated = Device::get_by_token(&token).await.unwrap().unwrap();
+        assert_eq!(authenticated.id, "device-reissued");
+
+        assert!(Device::issue_token("device-unknown").await.is_err());
+    }
+}
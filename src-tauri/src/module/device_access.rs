@@ -0,0 +1,228 @@
+use super::device::{Device, DeviceRole};
+use crate::utils::db;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// What a device may do besides reading its own conversations
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Send,
+    Upload,
+    /// Delete messages and conversations
+    Delete,
+    /// See the other devices, needed to start a conversation
+    ListDevices,
+    /// Reach every device at once
+    Broadcast,
+}
+
+/// Assigned from the host's device manager, devices that never got one are members.
+/// The host device itself is always an admin.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AccessRole {
+    Admin,
+    #[default]
+    Member,
+    /// Like a member until `expires_at`, without deleting
+    Guest,
+    ReadOnly,
+}
+
+impl AccessRole {
+    pub fn capabilities(self) -> &'static [Capability] {
+        use Capability::*;
+        match self {
+            AccessRole::Admin => &[Send, Upload, Delete, ListDevices, Broadcast],
+            AccessRole::Member => &[Send, Upload, Delete, ListDevices],
+            AccessRole::Guest => &[Send, Upload, ListDevices],
+            // reads the conversations it has, it can't start one
+            AccessRole::ReadOnly => &[],
+        }
+    }
+
+    pub fn can(self, capability: Capability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAccess {
+    pub device_id: String,
+    pub role: AccessRole,
+    /// Guests only, the guest loses every access afterwards
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub expires_at: Option<i64>,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub updated_at: Option<i64>,
+}
+
+impl DeviceAccess {
+    pub async fn get(device_id: &str) -> Result<Option<DeviceAccess>> {
+        let db_pool = db::get_db_pool()?;
+        let access = sqlx::query_as::<_, DeviceAccess>("SELECT * FROM device_access WHERE device_id = $1")
+            .bind(device_id)
+            .fetch_optional(&db_pool)
+            .await?;
+
+        Ok(access)
+    }
+
+    pub async fn get_all() -> Result<Vec<DeviceAccess>> {
+        let db_pool = db::get_db_pool()?;
+        let access = sqlx::query_as::<_, DeviceAccess>("SELECT * FROM device_access")
+            .fetch_all(&db_pool)
+            .await?;
+
+        Ok(access)
+    }
+
+    /// Replaces the role of the device, a guest needs an expiry and the other roles drop it
+    pub async fn assign(&self) -> Result<()> {
+        let expires_at = match (self.role, self.expires_at) {
+            (AccessRole::Guest, None) => bail!("Guest access needs an expiry"),
+            (AccessRole::Guest, expires_at) => expires_at,
+            _ => None,
+        };
+
+        let db_pool = db::get_db_pool()?;
+        sqlx::query(
+            r#"
+            INSERT INTO device_access (device_id, role, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (device_id) DO UPDATE SET
+                role = excluded.role,
+                expires_at = excluded.expires_at,
+                updated_at = unixepoch()
+            "#,
+        )
+        .bind(&self.device_id)
+        .bind(self.role)
+        .bind(expires_at)
+        .execute(&db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Guests whose time is up, their live sockets are still to be disconnected
+    pub async fn expired_guests() -> Result<Vec<String>> {
+        let db_pool = db::get_db_pool()?;
+        let ids = sqlx::query_scalar::<_, String>(
+            "SELECT device_id FROM device_access WHERE role = 'guest' AND (expires_at IS NULL OR expires_at <= unixepoch())",
+        )
+        .fetch_all(&db_pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// The role `device` acts with, `None` once a guest's time is up
+    pub async fn effective_role(device: &Device) -> Result<Option<AccessRole>> {
+        if device.role == DeviceRole::Host {
+            return Ok(Some(AccessRole::Admin));
+        }
+        let access = Self::get(&device.id).await?;
+        Ok(match access {
+            Some(access) if access.is_expired(chrono::Utc::now().timestamp()) => None,
+            Some(access) => Some(access.role),
+            None => Some(AccessRole::default()),
+        })
+    }
+
+    /// `now` in seconds, like `expires_at`
+    fn is_expired(&self, now: i64) -> bool {
+        self.role == AccessRole::Guest && self.expires_at.is_none_or(|expires_at| expires_at <= now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::DBManager;
+
+    #[test]
+    fn test_capabilities() {
+        assert!(AccessRole::Admin.can(Capability::Broadcast));
+        assert!(!AccessRole::Member.can(Capability::Broadcast));
+        assert!(AccessRole::Member.can(Capability::Delete));
+        assert!(!AccessRole::Guest.can(Capability::Delete));
+        assert!(AccessRole::Guest.can(Capability::Send));
+        assert!(!AccessRole::ReadOnly.can(Capability::Send));
+        assert!(!AccessRole::ReadOnly.can(Capability::Upload));
+        assert!(!AccessRole::ReadOnly.can(Capability::ListDevices));
+        assert!(AccessRole::Guest.can(Capability::ListDevices));
+    }
+
+    #[test]
+    fn test_guest_expiry() {
+        let access = |role, expires_at| DeviceAccess {
+            device_id: "device".into(),
+            role,
+            expires_at,
+            updated_at: None,
+        };
+
+        assert!(!access(AccessRole::Guest, Some(100)).is_expired(99));
+        assert!(access(AccessRole::Guest, Some(100)).is_expired(100));
+        assert!(access(AccessRole::Guest, None).is_expired(0));
+        assert!(!access(AccessRole::Member, Some(100)).is_expired(200));
+    }
+
+    #[tokio::test]
+    async fn test_effective_role() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let register = |id: &str, role| {
+            let device = Device {
+                id: id.to_string(),
+                name: id.to_string(),
+                role,
+                ..Device::default()
+            };
+            async move { device.register().await.unwrap() }
+        };
+        let access = |role, expires_at| DeviceAccess {
+            device_id: "access-client".into(),
+            role,
+            expires_at,
+            updated_at: None,
+        };
+        let host = register("access-host", DeviceRole::Host).await;
+        let client = register("access-client", DeviceRole::Client).await;
+
+        // the host is an admin, new devices are members
+        assert_eq!(
+            DeviceAccess::effective_role(&host).await.unwrap(),
+            Some(AccessRole::Admin)
+        );
+        assert_eq!(
+            DeviceAccess::effective_role(&client).await.unwrap(),
+            Some(AccessRole::Member)
+        );
+
+        access(AccessRole::ReadOnly, None).assign().await.unwrap();
+        assert_eq!(
+            DeviceAccess::effective_role(&client).await.unwrap(),
+            Some(AccessRole::ReadOnly)
+        );
+
+        // a guest needs an expiry and has no access at all afterwards
+        let now = chrono::Utc::now().timestamp();
+        assert!(access(AccessRole::Guest, None).assign().await.is_err());
+        access(AccessRole::Guest, Some(now + 3600)).assign().await.unwrap();
+        assert_eq!(
+            DeviceAccess::effective_role(&client).await.unwrap(),
+            Some(AccessRole::Guest)
+        );
+        access(AccessRole::Guest, Some(now - 1)).assign().await.unwrap();
+        assert_eq!(DeviceAccess::effective_role(&client).await.unwrap(), None);
+        assert!(DeviceAccess::expired_guests().await.unwrap().contains(&client.id));
+
+        // the role goes with the device, registering again starts over as a member
+        Device::remove(&client.id).await.unwrap();
+        assert!(DeviceAccess::get(&client.id).await.unwrap().is_none());
+    }
+}
@@ -26,9 +26,60 @@ pub struct Message {
     #[serde(with = "chrono::naive::serde::ts_milliseconds_option")]
     #[schema(value_type = i64)]
     pub updated_at: Option<NaiveDateTime>,
+
+    /// Signed urls of the referenced attachments, `path -> path?expires=..&signature=..`
+    /// Only set when the message is served, never stored.
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_urls: Option<HashMap<String, String>>,
+
+    /// Signed thumbnail urls of the referenced images and videos, keyed by attachment path
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_urls: Option<HashMap<String, String>>,
 }
 
 impl Message {
+    /// Paths of the uploaded files referenced by the message.
+    ///
+    /// Media and file messages store the path as content,
+    /// text messages embed uploaded images as editor nodes with `isFromSynclan`.
+    pub fn attachment_paths(&self) -> Vec<String> {
+        let Some(content) = self.content.as_deref().filter(|c| !c.is_empty()) else {
+            return vec![];
+        };
+
+        match self.r#type {
+            MessageType::Image | MessageType::Video | MessageType::File | MessageType::Folder => {
+                if content.starts_with("blob:") || content.contains("://") {
+                    vec![]
+                } else {
+                    vec![content.to_string()]
+                }
+            },
+            MessageType::Text => {
+                fn walk(node: &serde_json::Value, paths: &mut Vec<String>) {
+                    if node["isFromSynclan"].as_bool() == Some(true)
+                        && let Some(src) = node["src"].as_str()
+                    {
+                        paths.push(src.to_string());
+                    }
+                    if let Some(children) = node["children"].as_array() {
+                        for child in children {
+                            walk(child, paths);
+                        }
+                    }
+                }
+
+                let mut paths = vec![];
+                if let Ok(state) = serde_json::from_str::<serde_json::Value>(content) {
+                    walk(&state["root"], &mut paths);
+                }
+                paths
+            },
+        }
+    }
+
     pub async fn create(&self) -> Result<Message> {
         let db_pool = db::get_db_pool()?;
         let message = sqlx::query_as::<_, Message>(
@@ -51,6 +102,17 @@ impl Message {
         Ok(message)
     }
 
+    /// Whether a message with the uuid is stored already, e.g. one forwarded twice by a paired host
+    pub async fn exists(uuid: &str) -> Result<bool> {
+        let db_pool = db::get_db_pool()?;
+        let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM messages WHERE uuid = $1)")
+            .bind(uuid)
+            .fetch_one(&db_pool)
+            .await?;
+
+        Ok(exists)
+    }
+
     /// Get messages in pages
     pub async fn get_messages(
         self_id: &str,
@@ -166,6 +228,8 @@ impl Message {
                 extra: row.extra,
                 created_at: row.created_at,
                 updated_at: row.updated_at,
+                attachment_urls: None,
+                thumbnail_urls: None,
             };
 
             summary.insert(
@@ -326,6 +390,8 @@ pub enum MessageType {
     Image,
     Video,
     File,
+    /// A folder uploaded as a bundle, the content is the folder path
+    Folder,
 }
 
 #[test]
@@ -340,3 +406,37 @@ fn test_deserialize() {
     let msg: MessageType = serde_json::from_str("\"video\"").unwrap();
     assert!(matches!(msg, MessageType::Video));
 }
+
+#[test]
+fn test_attachment_paths() {
+    let mut msg: Message = serde_json::from_value(serde_json::json!({
+        "uuid": "uuid",
+        "sender": "a",
+        "receiver": "b",
+        "type": "image",
+        "content": "2025-06-04/a.png",
+    }))
+    .unwrap();
+    assert_eq!(msg.attachment_paths(), vec!["2025-06-04/a.png"]);
+
+    msg.content = Some("blob:http://localhost/uuid".into());
+    assert!(msg.attachment_paths().is_empty());
+
+    msg.r#type = MessageType::Text;
+    msg.content = Some(
+        serde_json::json!({
+            "root": {
+                "type": "root",
+                "children": [{
+                    "type": "paragraph",
+                    "children": [
+                        { "type": "image", "src": "2025-06-04/b.png", "isFromSynclan": true },
+                        { "type": "image", "src": "https://example.com/c.png" }
+                    ]
+                }]
+            }
+        })
+        .to_string(),
+    );
+    assert_eq!(msg.attachment_paths(), vec!["2025-06-04/b.png"]);
+}
@@ -1,5 +1,9 @@
+pub mod attachment;
+pub mod audit_log;
 pub mod device;
+pub mod device_access;
 pub mod message;
+pub mod peer_host;
 
 mod unix_timestamp_ms {
     use serde::{Deserialize, Deserializer, Serializer};
@@ -0,0 +1,147 @@
+use crate::utils::db;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Another Synclan host, trusted by the fingerprint of its certificate
+#[derive(Debug, Default, Clone, Deserialize, Serialize, sqlx::FromRow, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerHost {
+    /// Uppercase hex SHA-256 of the peer's certificate
+    pub fingerprint: String,
+    /// Host device id of the peer, known once paired
+    pub id: Option<String>,
+    pub alias: String,
+    /// e.g. `https://192.168.1.2:53317`
+    pub url: String,
+    /// Shared by both hosts, authenticates the requests between them
+    #[serde(skip_serializing)]
+    pub secret: String,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub paired_at: Option<i64>,
+    #[serde(default, with = "super::unix_timestamp_ms")]
+    pub created_at: Option<i64>,
+}
+
+impl PeerHost {
+    pub fn is_paired(&self) -> bool {
+        self.paired_at.is_some()
+    }
+
+    /// Trusts the fingerprint, any previous pairing with it starts over
+    pub async fn trust(&self) -> Result<PeerHost> {
+        let db_pool = db::get_db_pool()?;
+        let peer = sqlx::query_as::<_, PeerHost>(
+            r#"
+            INSERT INTO peer_hosts (fingerprint, url, secret)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (fingerprint) DO UPDATE SET
+                url = excluded.url,
+                secret = excluded.secret,
+                paired_at = NULL
+            RETURNING *
+            "#,
+        )
+        .bind(&self.fingerprint)
+        .bind(&self.url)
+        .bind(&self.secret)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(peer)
+    }
+
+    /// Both hosts trust each other now
+    pub async fn paired(&self) -> Result<PeerHost> {
+        let db_pool = db::get_db_pool()?;
+        let peer = sqlx::query_as::<_, PeerHost>(
+            r#"
+            UPDATE peer_hosts
+            SET id = $1, alias = $2, url = $3, secret = $4, paired_at = unixepoch()
+            WHERE fingerprint = $5
+            RETURNING *
+            "#,
+        )
+        .bind(&self.id)
+        .bind(&self.alias)
+        .bind(&self.url)
+        .bind(&self.secret)
+        .bind(&self.fingerprint)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(peer)
+    }
+
+    /// Pins the host to another certificate, see [`crate::utils::tls::PinMigration`]
+    pub async fn repin(&self, fingerprint: &str) -> Result<PeerHost> {
+        let db_pool = db::get_db_pool()?;
+        let peer = sqlx::query_as::<_, PeerHost>(
+            r#"
+            UPDATE peer_hosts
+            SET fingerprint = $1
+            WHERE fingerprint = $2
+            RETURNING *
+            "#,
+        )
+        .bind(fingerprint.to_uppercase())
+        .bind(&self.fingerprint)
+        .fetch_one(&db_pool)
+        .await?;
+
+        Ok(peer)
+    }
+
+    pub async fn get_by_fingerprint(fingerprint: &str) -> Result<Option<PeerHost>> {
+        let db_pool = db::get_db_pool()?;
+        let peer = sqlx::query_as::<_, PeerHost>("SELECT * FROM peer_hosts WHERE fingerprint = $1")
+            .bind(fingerprint.to_uppercase())
+            .fetch_optional(&db_pool)
+            .await?;
+
+        Ok(peer)
+    }
+
+    /// A paired host by its host device id
+    pub async fn get_by_id(id: &str) -> Result<Option<PeerHost>> {
+        let db_pool = db::get_db_pool()?;
+        let peer = sqlx::query_as::<_, PeerHost>("SELECT * FROM peer_hosts WHERE id = $1 AND paired_at IS NOT NULL")
+            .bind(id)
+            .fetch_optional(&db_pool)
+            .await?;
+
+        Ok(peer)
+    }
+
+    pub async fn get_all() -> Result<Vec<PeerHost>> {
+        let db_pool = db::get_db_pool()?;
+        let peers = sqlx::query_as::<_, PeerHost>("SELECT * FROM peer_hosts ORDER BY created_at DESC")
+            .fetch_all(&db_pool)
+            .await?;
+
+        Ok(peers)
+    }
+
+    /// Unpairs the host and forgets its devices
+    pub async fn remove(fingerprint: &str) -> Result<()> {
+        let db_pool = db::get_db_pool()?;
+        let mut tx = db_pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM devices
+            WHERE home_host = (SELECT id FROM peer_hosts WHERE fingerprint = $1)
+            "#,
+        )
+        .bind(fingerprint)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM peer_hosts WHERE fingerprint = $1")
+            .bind(fingerprint)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
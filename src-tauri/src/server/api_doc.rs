@@ -8,6 +8,8 @@ pub const SYNCLAN_TAG: &str = "Synclan";
 pub const DEVICE_TAG: &str = "Device";
 pub const UPLOAD_TAG: &str = "Upload";
 pub const MESSAGE_TAG: &str = "Message";
+pub const TRANSFER_TAG: &str = "Transfer";
+pub const FEDERATION_TAG: &str = "Federation";
 
 #[derive(OpenApi)]
 #[openapi(
@@ -26,7 +28,9 @@ pub const MESSAGE_TAG: &str = "Message";
     (name = SYNCLAN_TAG, description = "Synclan application API endpoints"),
     (name = DEVICE_TAG, description = "Device API endpoints"),
     (name = UPLOAD_TAG, description = "Upload API endpoints"),
-    (name = MESSAGE_TAG, description = "Message API endpoints")
+    (name = MESSAGE_TAG, description = "Message API endpoints"),
+    (name = TRANSFER_TAG, description = "Direct transfer API endpoints"),
+    (name = FEDERATION_TAG, description = "Host-to-host API endpoints")
   )
 )]
 pub struct ApiDoc;
@@ -0,0 +1,60 @@
+use crate::{
+    logging_error,
+    module::audit_log::{AuditAction, AuditLog, AuditOutcome},
+    server::{
+        exception::HttpException,
+        extractors::ClientInfo,
+        rate_limit::{Limit, RateLimited, RateLimiter},
+    },
+    utils::logging::Type,
+};
+
+/// Refuses a client whose IP failed to authenticate too often lately,
+/// checked before the attempt so a guess can't succeed once limited
+pub async fn check_failures(client: &ClientInfo) -> Result<(), RateLimited> {
+    let ip = client.ip.as_deref().unwrap_or_default();
+    RateLimiter::global().check(ip, Limit::AuthFailure).await
+}
+
+/// Writes an audit log entry, the request goes on even if it can't be written.
+/// A failed authentication also counts against the client's IP, see [`check_failures`].
+pub async fn record(
+    action: AuditAction,
+    device_id: Option<&str>,
+    client: &ClientInfo,
+    outcome: AuditOutcome,
+    detail: Option<String>,
+) {
+    if outcome == AuditOutcome::Failure && action.is_authentication() {
+        let ip = client.ip.as_deref().unwrap_or_default();
+        // the bucket may be empty already, the next check refuses the client then
+        let _ = RateLimiter::global().acquire(ip, Limit::AuthFailure).await;
+    }
+
+    let entry = AuditLog {
+        id: 0,
+        action,
+        device_id: device_id.map(ToOwned::to_owned),
+        ip: client.ip.clone(),
+        user_agent: client.user_agent.clone(),
+        outcome,
+        detail,
+        created_at: None,
+    };
+    logging_error!(Type::Server, entry.create().await);
+}
+
+/// Records the result of a handler, `detail` on success and the error message on failure
+pub async fn record_result<T>(
+    action: AuditAction,
+    device_id: Option<&str>,
+    client: &ClientInfo,
+    result: &Result<T, HttpException>,
+    detail: Option<String>,
+) {
+    let (outcome, detail) = match result {
+        Ok(_) => (AuditOutcome::Success, detail),
+        Err(err) => (AuditOutcome::Failure, Some(err.message())),
+    };
+    record(action, device_id, client, outcome, detail).await;
+}
@@ -0,0 +1,133 @@
+use crate::{
+    config::ISynclan,
+    logging, singleton,
+    utils::{logging::Type, network},
+};
+use anyhow::{Context, Result, bail};
+use axum::http::{HeaderValue, Uri};
+use parking_lot::RwLock;
+use std::collections::HashSet;
+
+/// Origin of the web UI dev server (`pnpm web:dev`)
+#[cfg(debug_assertions)]
+const DEV_ORIGINS: &[&str] = &["http://localhost:1422"];
+
+/// Origins allowed to make cross-origin requests, the `Origin` header has to match one
+/// of them exactly. Rebuilt whenever the server starts listening or the config changes.
+pub struct CorsOrigins {
+    origins: RwLock<HashSet<String>>,
+}
+
+singleton!(CorsOrigins, CORS_ORIGINS);
+
+impl CorsOrigins {
+    fn new() -> Self {
+        Self {
+            origins: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// `cors_allowed_origins` plus the URLs the server is reachable at on `port`
+    pub fn refresh(&self, synclan: &ISynclan, port: u16) -> Result<()> {
+        let mut origins = allowed_origins(synclan)?;
+        for url in network::server_urls(synclan, port)? {
+            origins.insert(normalize_origin(&url)?);
+        }
+        #[cfg(debug_assertions)]
+        origins.extend(DEV_ORIGINS.iter().map(ToString::to_string));
+
+        logging!(debug, Type::Server, "CORS allowed origins: {origins:?}");
+        *self.origins.write() = origins;
+        Ok(())
+    }
+
+    pub fn is_allowed(&self, origin: &HeaderValue) -> bool {
+        origin.to_str().is_ok_and(|origin| self.origins.read().contains(origin))
+    }
+}
+
+/// The configured `cors_allowed_origins`, normalized, an invalid entry is an error
+pub fn allowed_origins(synclan: &ISynclan) -> Result<HashSet<String>> {
+    synclan
+        .cors_allowed_origins
+        .iter()
+        .flatten()
+        .map(|origin| origin.trim())
+        .filter(|origin| !origin.is_empty())
+        .map(normalize_origin)
+        .collect()
+}
+
+/// `scheme://host[:port]` the way browsers serialize it in the `Origin` header:
+/// lowercase, without the default port of the scheme and without a path.
+///
+/// # Example
+/// - `HTTPS://Example.com:443/` -> `https://example.com`
+/// - `http://[FD00::2]:8080` -> `http://[fd00::2]:8080`
+pub fn normalize_origin(origin: &str) -> Result<String> {
+    let uri: Uri = origin.parse().with_context(|| format!("Invalid origin: {origin}"))?;
+    let scheme = uri
+        .scheme_str()
+        .map(str::to_ascii_lowercase)
+        .with_context(|| format!("Origin without scheme: {origin}"))?;
+    let default_port = match scheme.as_str() {
+        "http" => 80,
+        "https" => 443,
+        _ => bail!("Origin must be http or https: {origin}"),
+    };
+    let Some(authority) = uri.authority() else {
+        bail!("Origin without host: {origin}");
+    };
+    if authority.as_str().contains('@') {
+        bail!("Origin must not contain credentials: {origin}");
+    }
+    if !matches!(uri.path(), "" | "/") || uri.query().is_some() {
+        bail!("Origin must not contain a path: {origin}");
+    }
+
+    let host = authority.host().to_ascii_lowercase();
+    Ok(match authority.port_u16() {
+        Some(port) if port != default_port => format!("{scheme}://{host}:{port}"),
+        _ => format!("{scheme}://{host}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_origin() {
+        assert_eq!(
+            normalize_origin("HTTPS://Example.com:443/").unwrap(),
+            "https://example.com"
+        );
+        assert_eq!(
+            normalize_origin("http://192.168.1.2:53317").unwrap(),
+            "http://192.168.1.2:53317"
+        );
+        assert_eq!(normalize_origin("http://[FD00::2]:80").unwrap(), "http://[fd00::2]");
+        assert!(normalize_origin("localhost:3000").is_err());
+        assert!(normalize_origin("ftp://example.com").is_err());
+        assert!(normalize_origin("https://example.com/app").is_err());
+        assert!(normalize_origin("https://user@example.com").is_err());
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let cors = CorsOrigins::new();
+        let synclan = ISynclan {
+            cors_allowed_origins: Some(vec!["http://localhost:3000".into()]),
+            enable_encryption: Some(false),
+            ..ISynclan::default()
+        };
+        cors.refresh(&synclan, 53317).unwrap();
+
+        let allowed = |origin: &'static str| cors.is_allowed(&HeaderValue::from_static(origin));
+        assert!(allowed("http://localhost:3000"));
+        assert!(!allowed("http://localhost"));
+        assert!(!allowed("http://localhost:3000.evil.com"));
+        assert!(!allowed("http://localhost.evil.com"));
+        assert!(!allowed("https://localhost:3000"));
+    }
+}
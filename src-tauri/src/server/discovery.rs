@@ -0,0 +1,294 @@
+use crate::{
+    config::Config,
+    logging,
+    module::device::Device,
+    server, singleton,
+    utils::{logging::Type, tls},
+};
+use anyhow::Result;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type as SocketType};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+/// Multicast group Synclan hosts announce themselves to
+pub const DISCOVERY_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 53, 17);
+
+pub const DISCOVERY_PORT: u16 = 53318;
+
+/// How often a host announces itself
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A host that missed this many announcements is gone
+const PEER_TTL: Duration = Duration::from_secs(3 * 30 + 5);
+
+/// Datagram a host multicasts about itself
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    /// Host device id
+    pub id: String,
+    pub alias: String,
+    pub port: u16,
+    /// `http` or `https`
+    pub protocol: String,
+    /// SHA-256 of the certificate, only with `https`
+    pub fingerprint: Option<String>,
+    pub version: String,
+    /// Asks the hosts that hear it to announce themselves right away
+    #[serde(default)]
+    pub announce: bool,
+}
+
+impl Announcement {
+    /// This host, as announced with the current config
+    pub async fn host() -> Result<Self> {
+        // a restarting server runs with the draft
+        let synclan = Config::synclan().await.latest_arc();
+        let host = Device::get_host_device("").await?;
+        let (id, alias) = match host {
+            Some(host) => (host.id, host.name),
+            None => (String::new(), "Synclan".to_string()),
+        };
+        let encrypted = synclan.enable_encryption.unwrap_or(false);
+
+        Ok(Self {
+            id,
+            alias,
+            port: server::listening_port(&synclan),
+            protocol: if encrypted { "https" } else { "http" }.to_string(),
+            fingerprint: encrypted.then(|| tls::server_fingerprint(&synclan)).flatten(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            announce: true,
+        })
+    }
+}
+
+/// Another Synclan host found on the network
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredHost {
+    pub id: String,
+    pub alias: String,
+    #[schema(value_type = String)]
+    pub address: IpAddr,
+    pub port: u16,
+    pub protocol: String,
+    pub fingerprint: Option<String>,
+    pub version: String,
+    /// e.g. `https://192.168.1.2:53317`
+    pub url: String,
+}
+
+impl DiscoveredHost {
+    fn new(announcement: Announcement, address: IpAddr) -> Self {
+        let url = format!(
+            "{}://{}",
+            announcement.protocol,
+            SocketAddr::new(address, announcement.port)
+        );
+        Self {
+            id: announcement.id,
+            alias: announcement.alias,
+            address,
+            port: announcement.port,
+            protocol: announcement.protocol,
+            fingerprint: announcement.fingerprint,
+            version: announcement.version,
+            url,
+        }
+    }
+}
+
+/// Announces this host over UDP multicast and keeps track of the hosts that announce back
+pub struct Discovery {
+    peers: Arc<DashMap<String, (DiscoveredHost, Instant)>>,
+    cancel: Mutex<Option<CancellationToken>>,
+}
+
+singleton!(Discovery, DISCOVERY);
+
+impl Discovery {
+    fn new() -> Self {
+        Self {
+            peers: Arc::new(DashMap::new()),
+            cancel: Mutex::new(None),
+        }
+    }
+
+    /// Hosts heard from recently, sorted by alias
+    pub fn hosts(&self) -> Vec<DiscoveredHost> {
+        self.peers.retain(|_, (_, last_seen)| last_seen.elapsed() < PEER_TTL);
+        let mut hosts: Vec<_> = self.peers.iter().map(|r| r.value().0.clone()).collect();
+        hosts.sort_by(|a, b| a.alias.cmp(&b.alias).then_with(|| a.id.cmp(&b.id)));
+        hosts
+    }
+
+    /// Runs until [`Discovery::shutdown`]. Discovery is best effort,
+    /// a network without multicast only logs an error.
+    pub async fn run(&self) -> Result<()> {
+        let announcement = Announcement::host().await?;
+        let group = SocketAddrV4::new(DISCOVERY_GROUP, DISCOVERY_PORT);
+        let socket = match bind_multicast(group, Ipv4Addr::UNSPECIFIED) {
+            Ok(socket) => socket,
+            Err(err) => {
+                logging!(warn, Type::Server, "LAN discovery is unavailable: {err:?}");
+                return Ok(());
+            },
+        };
+
+        logging!(info, Type::Server, "LAN discovery on {group}");
+        self.serve(socket, group, announcement).await
+    }
+
+    pub fn shutdown(&self) {
+        if let Some(cancel) = self.cancel.lock().take() {
+            cancel.cancel();
+        }
+        self.peers.clear();
+    }
+
+    async fn serve(&self, socket: UdpSocket, group: SocketAddrV4, mut announcement: Announcement) -> Result<()> {
+        let cancel = CancellationToken::new();
+        if let Some(previous) = self.cancel.lock().replace(cancel.clone()) {
+            previous.cancel();
+        }
+
+        let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+        let mut buf = [0u8; 2048];
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = interval.tick() => {
+                    // the first one asks everyone to answer, the rest just keep us alive
+                    send(&socket, group, &announcement).await;
+                    announcement.announce = false;
+                },
+                received = socket.recv_from(&mut buf) => {
+                    let (len, from) = match received {
+                        Ok(received) => received,
+                        Err(err) => {
+                            logging!(debug, Type::Server, "LAN discovery receive failed: {err}");
+                            continue;
+                        },
+                    };
+                    let Some(peer) = decode(&buf[..len], &announcement.id) else {
+                        continue;
+                    };
+
+                    if peer.announce {
+                        send(&socket, group, &announcement).await;
+                    }
+                    let host = DiscoveredHost::new(peer, from.ip());
+                    self.peers.insert(host.id.clone(), (host, Instant::now()));
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The announcement of another host, `None` for our own echo and anything else on the group
+fn decode(datagram: &[u8], own_id: &str) -> Option<Announcement> {
+    serde_json::from_slice::<Announcement>(datagram)
+        .ok()
+        .filter(|peer| !peer.id.is_empty() && peer.id != own_id)
+}
+
+async fn send(socket: &UdpSocket, group: SocketAddrV4, announcement: &Announcement) {
+    let Ok(datagram) = serde_json::to_vec(announcement) else {
+        return;
+    };
+    if let Err(err) = socket.send_to(&datagram, group).await {
+        logging!(debug, Type::Server, "LAN discovery announce failed: {err}");
+    }
+}
+
+/// Binds the group's port, shared with other processes, and joins the group on `interface`
+fn bind_multicast(group: SocketAddrV4, interface: Ipv4Addr) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, SocketType::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())?;
+    socket.join_multicast_v4(group.ip(), &interface)?;
+    socket.set_multicast_if_v4(&interface)?;
+    socket.set_multicast_loop_v4(true)?;
+
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(id: &str) -> Announcement {
+        Announcement {
+            id: id.to_string(),
+            alias: id.to_string(),
+            port: 53317,
+            protocol: "https".to_string(),
+            fingerprint: Some("AB".to_string()),
+            version: "test".to_string(),
+            announce: true,
+        }
+    }
+
+    #[test]
+    fn test_decode_announcement() {
+        let datagram = serde_json::to_vec(&announcement("b")).unwrap();
+        let peer = decode(&datagram, "a").unwrap();
+        assert_eq!(peer, announcement("b"));
+
+        // our own echo and garbage are dropped
+        assert!(decode(&datagram, "b").is_none());
+        assert!(decode(&serde_json::to_vec(&announcement("")).unwrap(), "a").is_none());
+        assert!(decode(b"M-SEARCH * HTTP/1.1", "a").is_none());
+
+        let host = DiscoveredHost::new(peer, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(host.id, "b");
+        assert_eq!(host.fingerprint.as_deref(), Some("AB"));
+        assert_eq!(host.url, "https://192.168.1.20:53317");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs multicast on the loopback interface"]
+    async fn test_discover_over_loopback() {
+        let group = SocketAddrV4::new(DISCOVERY_GROUP, 53399);
+        let a = Arc::new(Discovery::new());
+        let b = Arc::new(Discovery::new());
+
+        for (discovery, id) in [(a.clone(), "a"), (b.clone(), "b")] {
+            let socket = bind_multicast(group, Ipv4Addr::LOCALHOST).unwrap();
+            tokio::spawn(async move { discovery.serve(socket, group, announcement(id)).await });
+        }
+
+        // whichever starts first is answered by the other one
+        let found = tokio::time::timeout(Duration::from_secs(5), async {
+            while a.hosts().is_empty() || b.hosts().is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        assert!(found.is_ok());
+
+        let hosts = a.hosts();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].id, "b");
+        assert_eq!(hosts[0].fingerprint.as_deref(), Some("AB"));
+        assert_eq!(hosts[0].url, format!("https://{}:53317", hosts[0].address));
+
+        a.shutdown();
+        b.shutdown();
+    }
+}
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub(crate) struct DownloadDto {
+    /// Force the browser to save the file instead of displaying it
+    #[serde(default)]
+    pub download: bool,
+}
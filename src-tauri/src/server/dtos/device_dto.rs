@@ -1,4 +1,3 @@
-use crate::module::device::DeviceRole;
 use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
@@ -14,10 +13,15 @@ pub(crate) struct RegistorDeviceDto {
     pub avatar: Option<String>,
     pub fingerprint_id: Option<String>,
 
-    pub role: DeviceRole,
-
     pub platform: Option<String>,
     pub browser: Option<String>,
+
+    /// One-time grant from the pairing QR code
+    pub grant: Option<String>,
+
+    /// Required when authorized access is enabled, unless a grant is given
+    #[serde(alias = "accessCode")]
+    pub access_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, IntoParams, Validate, ToSchema)]
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use validator::Validate;
+
+/// Device info a LocalSend app sends with `/register` and `/prepare-upload`
+#[derive(Debug, Clone, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendDeviceDto {
+    #[validate(length(min = 1, max = 64, message = "Invalid alias"))]
+    pub alias: String,
+
+    pub version: Option<String>,
+
+    pub device_model: Option<String>,
+
+    pub device_type: Option<String>,
+
+    #[validate(length(min = 1, max = 128, message = "Invalid fingerprint"))]
+    pub fingerprint: String,
+
+    pub port: Option<u16>,
+
+    pub protocol: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendPrepareUploadDto {
+    #[validate(nested)]
+    pub info: LocalSendDeviceDto,
+
+    /// `file_id -> file`
+    pub files: HashMap<String, LocalSendFileDto>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendFileDto {
+    pub id: String,
+
+    pub file_name: String,
+
+    pub size: u64,
+
+    pub file_type: Option<String>,
+
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendUploadDto {
+    pub session_id: String,
+
+    pub file_id: String,
+
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendSessionDto {
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LocalSendPinDto {
+    /// The access code, required when authorized access is on
+    pub pin: Option<String>,
+}
@@ -5,8 +5,7 @@ use validator::{Validate, ValidationError};
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct CursorPagination {
-    #[validate(length(min = 1, message = "Invalid device id"))]
-    pub self_id: String,
+    /// The conversation partner, the other side is the authenticated device
     #[validate(length(min = 1, message = "Invalid device id"))]
     pub target_id: String,
 
@@ -21,7 +20,7 @@ pub struct CursorPagination {
 #[derive(Debug, Deserialize, IntoParams, ToSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateAckDto {
-    pub receiver: String,
+    /// Last message received by the authenticated device
     pub last_ack: i32,
 }
 
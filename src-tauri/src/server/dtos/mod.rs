@@ -2,7 +2,9 @@ use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
 use validator::{Validate, ValidationError};
 
+pub mod attachment_dto;
 pub mod device_dto;
+pub mod localsend_dto;
 pub mod message_dto;
 pub mod synclan_dto;
 pub mod upload_dto;
@@ -26,3 +26,39 @@ pub(crate) struct UploadCompleteDto {
     /// Upload id returned by /upload/chunk/init
     pub upload_id: String,
 }
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleInitDto {
+    /// Folder name
+    #[validate(length(min = 1, message = "Invalid name"))]
+    #[schema(example = "project")]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "Empty bundle"), nested)]
+    pub files: Vec<BundleFileDto>,
+
+    /// Preferred chunk size in bytes
+    #[serde(default)]
+    #[schema(example = 5242880)]
+    pub chunk_size: u64,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleFileDto {
+    /// Path relative to the folder, `/` separated
+    #[validate(length(min = 1, message = "Invalid path"))]
+    #[schema(example = "src/main.rs")]
+    pub path: String,
+
+    /// File size in bytes, may be 0
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleCompleteDto {
+    /// Bundle id returned by /upload/bundle/init
+    pub bundle_id: String,
+}
@@ -4,31 +4,100 @@ use super::{
 };
 use crate::{
     module::{
+        audit_log::{AuditAction, AuditOutcome},
         device::{self, Device},
+        device_access::{Capability, DeviceAccess},
         message::Message,
     },
-    server::AppState,
+    server::{
+        AppState, audit,
+        extractors::ClientInfo,
+        rate_limit::{Limit, RateLimiter},
+        signed_url::UrlSigner,
+        transfer::{TransferAnswer, TransferCancel, TransferOffer},
+    },
 };
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use apalis::prelude::TaskSink as _;
 use axum::http::StatusCode;
 use serde::Deserialize;
-use socketioxide::extract::{AckSender, Data, Extension, SocketRef, State};
+use socketioxide::{
+    SocketIo,
+    extract::{AckSender, Data, Extension, SocketRef, State},
+};
 use std::sync::Arc;
 
-async fn message_handler(app_state: &Arc<AppState>, payload: &Message) -> Result<Message> {
-    let message = payload.create().await?;
+async fn message_handler(app_state: &Arc<AppState>, client: &Client, payload: Message) -> Result<Message> {
+    let mut message = sent_by(client, payload).create().await?;
     let mut storage = app_state.message_storage.clone();
     storage.push(message.clone()).await?;
 
+    // the sender renders the saved message, it needs urls it can load as well
+    UrlSigner::global().sign_messages([&mut message]).await?;
     Ok(message)
 }
 
+/// The sender is the device of the socket, whatever the payload claims
+fn sent_by(client: &Client, mut payload: Message) -> Message {
+    payload.sender = client.client_id.clone();
+    payload
+}
+
+/// Read again for every event, a changed role or an expired guest applies without reconnecting
+async fn permitted(client: &Client, capability: Capability) -> bool {
+    let role = match Device::get_by_id(&client.client_id).await {
+        Ok(Some(device)) => DeviceAccess::effective_role(&device).await.ok().flatten(),
+        _ => None,
+    };
+    role.is_some_and(|role| role.can(capability))
+}
+
+fn forbidden<T>() -> AckResponse<T> {
+    AckResponse {
+        status_code: StatusCode::FORBIDDEN,
+        message: Some("Forbidden".to_string()),
+        data: None,
+    }
+}
+
+/// Maps a handler result to the ack, errors are the client's fault
+fn ack_result<T>(result: Result<T>) -> AckResponse<T> {
+    match result {
+        Ok(data) => AckResponse {
+            status_code: StatusCode::OK,
+            message: None,
+            data: Some(data),
+        },
+        Err(err) => AckResponse {
+            status_code: StatusCode::BAD_REQUEST,
+            message: Some(err.to_string()),
+            data: None,
+        },
+    }
+}
+
 pub async fn on_connection(socket: SocketRef) {
     socket.on(
         "synclan://message",
-        async |Data(payload): Data<Message>, State::<Arc<AppState>>(app_state), ack: AckSender| {
-            let resp = match message_handler(&app_state, &payload).await {
+        async |Data(payload): Data<Message>,
+               Extension::<Arc<Client>>(client),
+               State::<Arc<AppState>>(app_state),
+               ack: AckSender| {
+            if !permitted(&client, Capability::Send).await {
+                ack.send(&forbidden::<Message>()).ok();
+                return;
+            }
+            if let Err(err) = RateLimiter::global().acquire(&client.client_id, Limit::Message).await {
+                let resp = AckResponse::<Message> {
+                    status_code: StatusCode::TOO_MANY_REQUESTS,
+                    message: Some(err.to_string()),
+                    data: None,
+                };
+                ack.send(&resp).ok();
+                return;
+            }
+
+            let resp = match message_handler(&app_state, &client, payload).await {
                 Ok(saved_msg) => AckResponse {
                     status_code: StatusCode::OK,
                     message: None,
@@ -45,10 +114,67 @@ pub async fn on_connection(socket: SocketRef) {
         },
     );
 
+    socket.on(
+        "synclan://transfer:offer",
+        async |Data(offer): Data<TransferOffer>,
+               Extension::<Arc<Client>>(client),
+               State::<Arc<AppState>>(app_state),
+               ack: AckSender| {
+            if !permitted(&client, Capability::Upload).await {
+                ack.send(&forbidden::<TransferOffer>()).ok();
+                return;
+            }
+            let resp = ack_result(app_state.transfers.offer(&client.client_id, offer));
+            ack.send(&resp).ok();
+        },
+    );
+
+    socket.on(
+        "synclan://transfer:answer",
+        async |Data(answer): Data<TransferAnswer>,
+               Extension::<Arc<Client>>(client),
+               State::<Arc<AppState>>(app_state),
+               ack: AckSender| {
+            let resp = ack_result(app_state.transfers.answer(&client.client_id, &answer));
+            ack.send(&resp).ok();
+        },
+    );
+
+    socket.on(
+        "synclan://transfer:cancel",
+        async |Data(cancel): Data<TransferCancel>,
+               Extension::<Arc<Client>>(client),
+               State::<Arc<AppState>>(app_state),
+               ack: AckSender| {
+            let resp = ack_result(app_state.transfers.cancel(&client.client_id, &cancel.transfer_id));
+            ack.send(&resp).ok();
+        },
+    );
+
     socket.on_disconnect(
-        async |_s: SocketRef, Extension::<Arc<Client>>(client), State::<Clients>(clients)| {
-            // remove client from clients
-            clients.remove(&client.client_id);
+        async |s: SocketRef,
+               io: SocketIo,
+               Extension::<Arc<Client>>(client),
+               State::<Clients>(clients),
+               State::<Arc<AppState>>(app_state)| {
+            // the device may still be connected from another tab
+            let remaining = io.of("/socket").and_then(|ns| {
+                ns.sockets().into_iter().find_map(|other| {
+                    other
+                        .extensions
+                        .get::<Arc<Client>>()
+                        .filter(|other_client| other.id != s.id && other_client.client_id == client.client_id)
+                })
+            });
+            match remaining {
+                Some(other_client) => clients.add(other_client),
+                None => {
+                    // remove client from clients
+                    clients.remove(&client.client_id);
+                    // the other end would wait forever
+                    app_state.transfers.cancel_device(&client.client_id);
+                },
+            }
         },
     );
 }
@@ -56,7 +182,8 @@ pub async fn on_connection(socket: SocketRef) {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Auth {
-    pub device_id: Option<String>,
+    /// Issued when the device registered, see [`Device::get_by_token`]
+    pub token: Option<String>,
 }
 
 /// Handles the connection of a new user.
@@ -66,10 +193,28 @@ pub async fn authenticate_middleware(
     Data(auth): Data<Auth>,
     State(clients): State<Clients>,
 ) -> Result<()> {
-    let device_id = auth.device_id.ok_or_else(|| anyhow!("Unauthorized"))?;
-    let device = device::Device::get_by_id(&device_id)
+    let client_info = ClientInfo::from_parts(socket.req_parts());
+    audit::check_failures(&client_info).await?;
+    let result = authenticate(&socket, auth, &clients).await;
+
+    let (device_id, outcome, detail) = match &result {
+        Ok(device_id) => (Some(device_id.as_str()), AuditOutcome::Success, None),
+        Err(err) => (None, AuditOutcome::Failure, Some(err.to_string())),
+    };
+    audit::record(AuditAction::Connect, device_id, &client_info, outcome, detail).await;
+    result.map(|_| ())
+}
+
+/// Returns the id of the device the token belongs to
+async fn authenticate(socket: &SocketRef, auth: Auth, clients: &Clients) -> Result<String> {
+    let token = auth.token.ok_or_else(|| anyhow!("Unauthorized"))?;
+    let device = device::Device::get_by_token(&token)
         .await?
+        .filter(Device::can_authenticate)
         .ok_or_else(|| anyhow!("Unauthorized"))?;
+    if DeviceAccess::effective_role(&device).await?.is_none() {
+        bail!("Guest access expired");
+    }
 
     Device::touch(&device.id).await?;
 
@@ -80,5 +225,35 @@ pub async fn authenticate_middleware(
 
     socket.extensions.insert(client);
 
-    Ok(())
+    Ok(device.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::message::MessageType;
+    use socketioxide::socket::Sid;
+
+    #[test]
+    fn test_sent_by() {
+        let client = Client::new(Sid::new(), "alice".into());
+        let payload = Message {
+            id: None,
+            uuid: "spoofed".into(),
+            sender: "bob".into(),
+            receiver: "carol".into(),
+            r#type: MessageType::Text,
+            content: None,
+            plain_content: Some("hello".into()),
+            extra: None,
+            created_at: None,
+            updated_at: None,
+            attachment_urls: None,
+            thumbnail_urls: None,
+        };
+
+        let message = sent_by(&client, payload);
+        assert_eq!(message.sender, "alice");
+        assert_eq!(message.receiver, "carol");
+    }
 }
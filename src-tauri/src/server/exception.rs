@@ -1,4 +1,4 @@
-use super::{Type, logging_error};
+use super::{Type, logging_error, rate_limit::RateLimited};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -52,6 +52,9 @@ pub enum HttpException {
     /// 422
     #[error("Unprocessable Entity")]
     UnprocessableEntityException(Option<String>),
+    /// 429
+    #[error("Too Many Requests")]
+    TooManyRequestsException(Option<String>),
     /// 500
     #[error("Internal Server Error")]
     InternalServerErrorException(Option<String>),
@@ -93,6 +96,7 @@ impl HttpException {
             HttpException::UnprocessableEntityException(_) => {
                 (StatusCode::UNPROCESSABLE_ENTITY, "Unprocessable Entity")
             },
+            HttpException::TooManyRequestsException(_) => (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests"),
             HttpException::InternalServerErrorException(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
             },
@@ -105,14 +109,12 @@ impl HttpException {
             },
         }
     }
-}
 
-impl IntoResponse for HttpException {
-    fn into_response(self) -> Response {
-        let (status, default_message) = self.status_and_default_message();
+    /// The custom message if any, otherwise the default one of the status
+    pub fn message(&self) -> String {
+        let (_, default_message) = self.status_and_default_message();
 
-        // Use custom message if any, otherwise use default message
-        let message = match self {
+        match self {
             HttpException::BadRequestException(Some(msg))
             | HttpException::UnauthorizedException(Some(msg))
             | HttpException::ForbiddenException(Some(msg))
@@ -127,18 +129,24 @@ impl IntoResponse for HttpException {
             | HttpException::UnsupportedMediaTypeException(Some(msg))
             | HttpException::ImATeapotException(Some(msg))
             | HttpException::UnprocessableEntityException(Some(msg))
+            | HttpException::TooManyRequestsException(Some(msg))
             | HttpException::InternalServerErrorException(Some(msg))
             | HttpException::NotImplementedException(Some(msg))
             | HttpException::BadGatewayException(Some(msg))
             | HttpException::ServiceUnavailableException(Some(msg))
             | HttpException::GatewayTimeoutException(Some(msg))
-            | HttpException::HttpVersionNotSupportedException(Some(msg)) => msg,
+            | HttpException::HttpVersionNotSupportedException(Some(msg)) => msg.clone(),
             _ => default_message.to_string(),
-        };
+        }
+    }
+}
 
+impl IntoResponse for HttpException {
+    fn into_response(self) -> Response {
+        let (status, _) = self.status_and_default_message();
         let body = axum::Json(ExceptionResponse {
             status_code: status.as_u16(),
-            message,
+            message: self.message(),
         });
 
         (status, body).into_response()
@@ -159,6 +167,12 @@ impl From<anyhow::Error> for HttpException {
     }
 }
 
+impl From<RateLimited> for HttpException {
+    fn from(err: RateLimited) -> Self {
+        Self::TooManyRequestsException(Some(err.to_string()))
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ExceptionResponse {
@@ -1,8 +1,10 @@
 mod body_extractor;
+mod client_extractor;
 mod param_extractor;
 mod query_extractor;
 
 pub use body_extractor::*;
+pub use client_extractor::*;
 pub use param_extractor::*;
 pub use query_extractor::*;
 
@@ -0,0 +1,457 @@
+use crate::{
+    config::Config,
+    logging, logging_error,
+    module::{
+        device::{Device, DeviceRole},
+        message::Message,
+        peer_host::PeerHost,
+    },
+    server,
+    utils::{
+        logging::Type,
+        network,
+        tls::{self, PinMigration},
+    },
+};
+use anyhow::{Context, Result, anyhow, bail};
+use rustls::{
+    DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// Header naming the host a federation request comes from, next to its `Bearer` secret
+pub const HOST_HEADER: &str = "x-synclan-host";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sent by the host that pairs to the host it pairs with
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct PairRequest {
+    /// Host device id of the sender
+    #[validate(length(min = 1, message = "Invalid id"))]
+    pub id: String,
+    pub alias: String,
+    /// Where the sender can be reached
+    #[validate(url(message = "Invalid url"))]
+    pub url: String,
+    #[validate(length(equal = 64, message = "Invalid fingerprint"))]
+    pub fingerprint: String,
+    #[validate(length(min = 32, message = "Invalid secret"))]
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PairResponse {
+    pub id: String,
+    pub alias: String,
+}
+
+/// The host that receives a pairing asks the sender's certificate holder to confirm the secret
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmRequest {
+    pub secret: String,
+}
+
+/// A message for a device of the receiving host, with its sender
+#[derive(Debug, Deserialize, Serialize, ToSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardedMessage {
+    pub sender: Device,
+    pub message: Message,
+}
+
+/// `JsonResponse` of the other host
+#[derive(Deserialize)]
+struct Payload<T> {
+    payload: T,
+}
+
+/// This host as a paired host sees it
+struct LocalHost {
+    id: String,
+    alias: String,
+    url: String,
+    fingerprint: String,
+}
+
+impl LocalHost {
+    /// Federation pins certificates, so it needs HTTPS
+    async fn get() -> Result<Self> {
+        let synclan = Config::synclan().await.data_arc();
+        if !synclan.enable_encryption.unwrap_or(false) {
+            bail!("Pairing hosts requires HTTPS");
+        }
+        let fingerprint =
+            tls::server_fingerprint(&synclan).ok_or_else(|| anyhow!("The server certificate is not ready"))?;
+        let host = Device::get_host_device("")
+            .await?
+            .ok_or_else(|| anyhow!("Host device not found"))?;
+        let port = server::listening_port(&synclan);
+        let ip = network::reachable_ips(&synclan)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("The server is not reachable from the network"))?;
+
+        Ok(Self {
+            id: host.id,
+            alias: host.name,
+            url: format!("https://{}", SocketAddr::new(ip, port)),
+            fingerprint,
+        })
+    }
+}
+
+/// Starts pairing with the host at `url`, which must present the certificate with `fingerprint`.
+///
+/// Pairing completes once the other host trusts this one as well, until then
+/// the peer stays pending and the other host's pairing completes it.
+pub async fn pair(url: &str, fingerprint: &str) -> Result<PeerHost> {
+    let local = LocalHost::get().await?;
+    let url = url.trim_end_matches('/').to_string();
+    let fingerprint = fingerprint.replace(':', "").to_uppercase();
+    if fingerprint == local.fingerprint {
+        bail!("Cannot pair with this host");
+    }
+
+    let peer = PeerHost {
+        fingerprint,
+        url,
+        secret: new_secret()?,
+        ..PeerHost::default()
+    }
+    .trust()
+    .await?;
+
+    let request = PairRequest {
+        id: local.id,
+        alias: local.alias,
+        url: local.url,
+        fingerprint: local.fingerprint,
+        secret: peer.secret.clone(),
+    };
+    let response = client(Some(&peer.fingerprint))?
+        .post(format!("{}/api/v1/federation/pair", peer.url))
+        .json(&request)
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::FORBIDDEN {
+        logging!(info, Type::Server, "Waiting for {} to trust this host", peer.url);
+        return Ok(peer);
+    }
+    let response = response
+        .error_for_status()?
+        .json::<Payload<PairResponse>>()
+        .await?
+        .payload;
+
+    let peer = PeerHost {
+        id: Some(response.id),
+        alias: response.alias,
+        ..peer
+    }
+    .paired()
+    .await?;
+    logging_error!(Type::Server, sync_devices(&peer).await);
+
+    Ok(peer)
+}
+
+/// Completes a pairing started by another host, if this host trusts its fingerprint.
+///
+/// The claimed fingerprint is proven by calling the sender back on a connection
+/// pinned to it, only the holder of that certificate knows the secret.
+pub async fn accept_pairing(request: PairRequest) -> Result<Option<PairResponse>> {
+    let local = LocalHost::get().await?;
+    let fingerprint = request.fingerprint.to_uppercase();
+    let Some(peer) = PeerHost::get_by_fingerprint(&fingerprint).await? else {
+        return Ok(None);
+    };
+
+    client(Some(&fingerprint))?
+        .post(format!(
+            "{}/api/v1/federation/confirm",
+            request.url.trim_end_matches('/')
+        ))
+        .json(&ConfirmRequest {
+            secret: request.secret.clone(),
+        })
+        .send()
+        .await?
+        .error_for_status()
+        .context("The host did not confirm the pairing")?;
+
+    let peer = PeerHost {
+        id: Some(request.id),
+        alias: request.alias,
+        url: request.url.trim_end_matches('/').to_string(),
+        secret: request.secret,
+        ..peer
+    }
+    .paired()
+    .await?;
+    logging!(info, Type::Server, "Paired with {} ({})", peer.alias, peer.url);
+
+    // the sender only learns about this host once it answers
+    let synced = peer.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        logging_error!(Type::Server, sync_devices(&synced).await);
+    });
+
+    Ok(Some(PairResponse {
+        id: local.id,
+        alias: local.alias,
+    }))
+}
+
+/// Whether a pairing this host started is waiting for `secret`
+pub async fn confirm_pairing(secret: &str) -> Result<bool> {
+    let peers = PeerHost::get_all().await?;
+    Ok(peers
+        .iter()
+        .any(|peer| !peer.is_paired() && constant_time_eq(&peer.secret, secret)))
+}
+
+/// The paired host a federation request comes from
+pub async fn authenticate(host_id: &str, secret: &str) -> Result<Option<PeerHost>> {
+    let peer = PeerHost::get_by_id(host_id).await?;
+    Ok(peer.filter(|peer| constant_time_eq(&peer.secret, secret)))
+}
+
+/// Copies the devices of a paired host, they can be messaged like local ones
+pub async fn sync_devices(peer: &PeerHost) -> Result<()> {
+    let peer_id = peer.id.as_deref().ok_or_else(|| anyhow!("The host is not paired"))?;
+    let devices = request(peer, reqwest::Method::GET, "devices")
+        .await?
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Payload<Vec<Device>>>()
+        .await?
+        .payload;
+
+    let mut ids = Vec::with_capacity(devices.len());
+    for device in devices {
+        if let Some(device) = upsert_remote(peer_id, device).await? {
+            ids.push(device.id);
+        }
+    }
+    Device::remove_remote_not_in(peer_id, &ids).await?;
+
+    logging!(info, Type::Server, "Synced {} devices of {}", ids.len(), peer.alias);
+    Ok(())
+}
+
+/// Records a device of a paired host, unless it is registered with this host as well
+pub async fn upsert_remote(peer_id: &str, device: Device) -> Result<Option<Device>> {
+    if let Some(existing) = Device::get_by_id(&device.id).await?
+        && existing.home_host.as_deref() != Some(peer_id)
+    {
+        return Ok(None);
+    }
+
+    // whatever the peer says, its devices never act as the host of this one
+    let device = Device {
+        role: DeviceRole::Client,
+        home_host: Some(peer_id.to_string()),
+        ..device
+    }
+    .upsert()
+    .await?;
+    Ok(Some(device))
+}
+
+/// Refreshes the devices of every paired host, unreachable hosts are skipped
+pub async fn sync_all() -> Result<()> {
+    for peer in PeerHost::get_all().await?.iter().filter(|peer| peer.is_paired()) {
+        let Err(err) = sync_devices(peer).await else {
+            continue;
+        };
+        // a host paired before its local CA presents another certificate now
+        match migrate_pin(peer).await {
+            Ok(Some(peer)) => logging_error!(Type::Server, sync_devices(&peer).await),
+            Ok(None) => logging!(warn, Type::Server, "Failed to sync {}: {err}", peer.url),
+            Err(migrate_err) => logging!(
+                warn,
+                Type::Server,
+                "Failed to sync {}: {err}, no pin migration: {migrate_err}",
+                peer.url
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Moves the pin of `peer` to its local CA, if the certificate it pinned vouches for it
+async fn migrate_pin(peer: &PeerHost) -> Result<Option<PeerHost>> {
+    // the proof is signed, it doesn't matter who serves it
+    let migration = client(None)?
+        .get(format!("{}/api/v1/federation/pin", peer.url))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Payload<PinMigration>>()
+        .await?
+        .payload;
+    if migration.fingerprint == peer.fingerprint || !migration.verify(&peer.fingerprint) {
+        return Ok(None);
+    }
+
+    let peer = peer.repin(&migration.fingerprint).await?;
+    logging!(info, Type::Server, "Moved the pin of {} to its local CA", peer.url);
+    Ok(Some(peer))
+}
+
+/// Hands a message to the host its receiver is registered with
+pub async fn forward(home_host: &str, message: &Message) -> Result<()> {
+    let peer = PeerHost::get_by_id(home_host)
+        .await?
+        .ok_or_else(|| anyhow!("Host {home_host} is not paired"))?;
+    let sender = Device::get_by_id(&message.sender)
+        .await?
+        .ok_or_else(|| anyhow!("Device {} not found", message.sender))?;
+
+    let forwarded = ForwardedMessage {
+        sender,
+        message: Message {
+            attachment_urls: None,
+            thumbnail_urls: None,
+            ..message.clone()
+        },
+    };
+    request(&peer, reqwest::Method::POST, "messages")
+        .await?
+        .json(&forwarded)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Authenticated request to a paired host
+async fn request(peer: &PeerHost, method: reqwest::Method, path: &str) -> Result<reqwest::RequestBuilder> {
+    let host_id = Device::get_host_device("")
+        .await?
+        .map(|host| host.id)
+        .unwrap_or_default();
+
+    Ok(client(Some(&peer.fingerprint))?
+        .request(method, format!("{}/api/v1/federation/{path}", peer.url))
+        .bearer_auth(&peer.secret)
+        .header(HOST_HEADER, host_id))
+}
+
+/// HTTP client that only talks to the certificate with `fingerprint`,
+/// to anyone without one, only for responses that prove themselves
+fn client(fingerprint: Option<&str>) -> Result<reqwest::Client> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(FingerprintVerifier {
+            fingerprint: fingerprint.map(str::to_uppercase),
+            provider,
+        }))
+        .with_no_client_auth();
+
+    Ok(reqwest::Client::builder()
+        .use_preconfigured_tls(config)
+        .timeout(REQUEST_TIMEOUT)
+        .build()?)
+}
+
+fn new_secret() -> Result<String> {
+    let mut secret = [0u8; 32];
+    getrandom::fill(&mut secret).map_err(|err| anyhow!("{err}"))?;
+    Ok(secret.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Hosts sign their leaves with a local CA, the pinned fingerprint is the trust anchor.
+/// It matches either the leaf itself or a CA in the chain the leaf is verified against.
+#[derive(Debug)]
+struct FingerprintVerifier {
+    /// `None` accepts any certificate
+    fingerprint: Option<String>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let Some(fingerprint) = &self.fingerprint else {
+            return Ok(ServerCertVerified::assertion());
+        };
+        let matches = |cert: &CertificateDer<'_>| &format!("{:X}", Sha256::digest(cert.as_ref())) == fingerprint;
+        if matches(end_entity) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        let Some(ca) = intermediates.iter().find(|cert| matches(cert)) else {
+            return Err(rustls::Error::General("Certificate fingerprint mismatch".into()));
+        };
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.clone().into_owned())?;
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), self.provider.clone())
+            .build()
+            .map_err(|err| rustls::Error::General(err.to_string()))?
+            .verify_server_cert(end_entity, &[], server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd"));
+        assert!(!constant_time_eq("abc", "abcd"));
+    }
+}
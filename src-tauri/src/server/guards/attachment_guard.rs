@@ -0,0 +1,54 @@
+use super::AuthGuard;
+use crate::{module::device::Device, server::signed_url::UrlSigner};
+use axum::{
+    RequestPartsExt,
+    extract::{FromRequestParts, Path, Query},
+    http::{StatusCode, request::Parts},
+};
+use serde::Deserialize;
+
+/// Directory of the permanent uploads, those in use as a device avatar are readable without credentials
+const PUBLIC_DIR: &str = "assets/";
+
+#[derive(Deserialize)]
+struct Signature {
+    expires: Option<i64>,
+    signature: Option<String>,
+}
+
+/// Guards `/attachments/{*path}`.
+///
+/// Device avatars are public, any other file is granted with a valid `expires` + `signature` query (see [`UrlSigner`]),
+/// otherwise the request needs a bearer token like every other route.
+pub struct AttachmentGuard;
+
+impl<S> FromRequestParts<S> for AttachmentGuard
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(path) = parts
+            .extract::<Path<String>>()
+            .await
+            .map_err(|_| (StatusCode::NOT_FOUND, "Not Found"))?;
+
+        // any device can upload to the public dir, only what it set as avatar is public
+        if path.starts_with(PUBLIC_DIR) && Device::is_avatar(&path).await.unwrap_or(false) {
+            return Ok(Self);
+        }
+
+        if let Ok(Query(Signature {
+            expires: Some(expires),
+            signature: Some(signature),
+        })) = parts.extract::<Query<Signature>>().await
+            && UrlSigner::global().verify(&path, expires, &signature)
+        {
+            return Ok(Self);
+        }
+
+        AuthGuard::from_request_parts(parts, state).await?;
+        Ok(Self)
+    }
+}
@@ -1,5 +1,5 @@
 use super::Claims;
-use crate::module::device::Device;
+use crate::module::{device::Device, device_access::DeviceAccess};
 use axum::{
     RequestPartsExt,
     extract::FromRequestParts,
@@ -24,12 +24,102 @@ where
             .await
             .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?;
 
-        let device = Device::get_by_id(bearer.token())
+        // the token issued at registration, a device id is no secret
+        let device = Device::get_by_token(bearer.token())
             .await
             .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?
+            .filter(Device::can_authenticate)
             .ok_or((StatusCode::UNAUTHORIZED, "Unauthorized"))?;
-        parts.extensions.insert(Claims::new(device.id));
+        let access = DeviceAccess::effective_role(&device)
+            .await
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?
+            .ok_or((StatusCode::FORBIDDEN, "Guest access expired"))?;
+        parts.extensions.insert(Claims::new(device.id, device.role, access));
 
         Ok(Self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        module::{
+            device::DeviceRole,
+            device_access::{AccessRole, DeviceAccess},
+        },
+        server::federation,
+        utils::db::DBManager,
+    };
+    use axum::http::Request;
+
+    /// Runs the guard on a request with `token` as bearer
+    async fn authorize(token: &str) -> Result<Claims, StatusCode> {
+        let request = Request::builder()
+            .header("Authorization", format!("Bearer {token}"))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        AuthGuard::from_request_parts(&mut parts, &())
+            .await
+            .map_err(|(status, _)| status)?;
+        Ok(parts.extensions.remove::<Claims>().unwrap())
+    }
+
+    async fn register(id: &str, role: DeviceRole) -> String {
+        let device = Device {
+            id: id.to_string(),
+            name: id.to_string(),
+            role,
+            ..Device::default()
+        };
+        device.upsert().await.unwrap();
+        Device::issue_token(id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_token_auth() {
+        DBManager::global().init_in_memory().await.unwrap();
+
+        let token = register("auth-client", DeviceRole::Client).await;
+        let claims = authorize(&token).await.unwrap();
+        assert_eq!(claims.device_id, "auth-client");
+        assert_eq!(claims.access, AccessRole::Member);
+
+        // the id of a device is no credential
+        assert_eq!(authorize("auth-client").await.unwrap_err(), StatusCode::UNAUTHORIZED);
+        let host_token = register("auth-host", DeviceRole::Host).await;
+        assert_eq!(authorize(&host_token).await.unwrap().access, AccessRole::Admin);
+        assert_eq!(authorize("auth-host").await.unwrap_err(), StatusCode::UNAUTHORIZED);
+
+        // a LocalSend app only announced its fingerprint, it never authenticates
+        let token = register("localsend-auth", DeviceRole::LocalSend).await;
+        assert_eq!(authorize(&token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+
+        // the host of a paired host is only a client here, and none of its devices authenticates here
+        let remote_host = Device {
+            id: "auth-remote-host".to_string(),
+            name: "Remote".to_string(),
+            role: DeviceRole::Host,
+            ..Device::default()
+        };
+        let remote_host = federation::upsert_remote("auth-peer", remote_host)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(remote_host.role, DeviceRole::Client);
+        let token = Device::issue_token(&remote_host.id).await.unwrap();
+        assert_eq!(authorize(&token).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+
+        // an expired guest is known, but has no access any more
+        let token = register("auth-guest", DeviceRole::Client).await;
+        let guest = DeviceAccess {
+            device_id: "auth-guest".to_string(),
+            role: AccessRole::Guest,
+            expires_at: Some(chrono::Utc::now().timestamp() - 1),
+            updated_at: None,
+        };
+        guest.assign().await.unwrap();
+        assert_eq!(authorize(&token).await.unwrap_err(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,46 @@
+use super::Claims;
+use crate::module::device_access::Capability;
+use axum::{
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+};
+
+/// Guards that let a request through when the role of the device has the capability.
+/// They read the [`Claims`], so they run behind [`super::AuthGuard`], either as a
+/// `route_layer` of a whole router or as an argument of a single handler.
+macro_rules! capability_guard {
+    ($($(#[$meta:meta])* $name:ident => $capability:expr;)+) => {
+        $(
+            $(#[$meta])*
+            pub struct $name;
+
+            impl<S> FromRequestParts<S> for $name
+            where
+                S: Send + Sync,
+            {
+                type Rejection = (StatusCode, &'static str);
+
+                async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+                    let claims = parts
+                        .extensions
+                        .get::<Claims>()
+                        .ok_or((StatusCode::UNAUTHORIZED, "Unauthorized"))?;
+                    if !claims.can($capability) {
+                        return Err((StatusCode::FORBIDDEN, "Forbidden"));
+                    }
+
+                    Ok(Self)
+                }
+            }
+        )+
+    };
+}
+
+capability_guard! {
+    /// Uploads of files and folders
+    CanUpload => Capability::Upload;
+    /// Deleting messages and conversations
+    CanDelete => Capability::Delete;
+    /// Listing and discovering devices and hosts
+    CanListDevices => Capability::ListDevices;
+}
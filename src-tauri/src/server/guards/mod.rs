@@ -1,21 +1,52 @@
+use crate::module::{
+    device::DeviceRole,
+    device_access::{AccessRole, Capability},
+};
 use axum::{
     extract::FromRequestParts,
     http::{StatusCode, request::Parts},
 };
 use serde::{Deserialize, Serialize};
 
+pub mod attachment_guard;
 pub mod auth_guard;
+pub mod capability_guard;
+pub mod peer_guard;
 
+pub use attachment_guard::*;
 pub use auth_guard::*;
+pub use capability_guard::*;
+pub use peer_guard::*;
 
+/// The device a request is authenticated as, handlers take the caller's identity from
+/// here and never from the path, query or body
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub device_id: String,
+    pub role: DeviceRole,
+    pub access: AccessRole,
 }
 
 impl Claims {
-    pub fn new(device_id: String) -> Self {
-        Self { device_id }
+    pub fn new(device_id: String, role: DeviceRole, access: AccessRole) -> Self {
+        Self {
+            device_id,
+            role,
+            access,
+        }
+    }
+
+    pub fn can(&self, capability: Capability) -> bool {
+        self.access.can(capability)
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.role == DeviceRole::Host
+    }
+
+    /// A device may manage itself, the host every device
+    pub fn can_manage(&self, device_id: &str) -> bool {
+        self.device_id == device_id || self.is_host()
     }
 }
 
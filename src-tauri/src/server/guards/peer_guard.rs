@@ -0,0 +1,42 @@
+use crate::{
+    module::peer_host::PeerHost,
+    server::federation::{self, HOST_HEADER},
+};
+use axum::{
+    RequestPartsExt,
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+
+/// A request from a paired host, authenticated by the secret shared at pairing
+pub struct PeerGuard(pub PeerHost);
+
+impl<S> FromRequestParts<S> for PeerGuard
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?;
+        let host_id = parts
+            .headers
+            .get(HOST_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or((StatusCode::UNAUTHORIZED, "Unauthorized"))?;
+
+        let peer = federation::authenticate(host_id, bearer.token())
+            .await
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?
+            .ok_or((StatusCode::UNAUTHORIZED, "Unauthorized"))?;
+
+        Ok(Self(peer))
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{config::Config, module::device::Device, utils::tls};
+use anyhow::{Result, bail};
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// LocalSend protocol version we speak
+pub const PROTOCOL_VERSION: &str = "2.1";
+
+/// A session nobody uploads to for this long no longer blocks new ones
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Answer of `/info` and `/register`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSendInfo {
+    pub alias: String,
+    pub version: String,
+    pub device_model: Option<String>,
+    pub device_type: String,
+    pub fingerprint: String,
+    pub download: bool,
+}
+
+impl LocalSendInfo {
+    /// This device as seen by LocalSend apps
+    pub async fn host() -> Result<Self> {
+        let alias = match Device::get_host_device("").await? {
+            Some(host) => host.name,
+            None => "Synclan".to_string(),
+        };
+
+        Ok(Self {
+            alias,
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: Some(std::env::consts::OS.to_string()),
+            device_type: "desktop".to_string(),
+            fingerprint: fingerprint().await,
+            download: false,
+        })
+    }
+}
+
+/// LocalSend identifies HTTPS peers by the SHA-256 of their certificate,
+/// plain HTTP peers by any stable random string.
+///
+/// Apps that knew this host before the local CA keep seeing the certificate it replaced.
+async fn fingerprint() -> String {
+    let synclan = Config::synclan().await.data_arc();
+    let cert_fingerprint = synclan
+        .enable_encryption
+        .unwrap_or(false)
+        .then(|| {
+            synclan
+                .legacy_cert_pem
+                .as_deref()
+                .and_then(tls::cert_fingerprint)
+                .or_else(|| tls::server_fingerprint(&synclan))
+        })
+        .flatten();
+    if let Some(fingerprint) = cert_fingerprint {
+        return fingerprint;
+    }
+
+    let host_id = Device::get_host_device("").await.ok().flatten().map(|host| host.id);
+    format!("{:X}", Sha256::digest(host_id.unwrap_or_default()))
+}
+
+/// Id of the synthetic device that stands for a LocalSend app
+pub fn device_id(fingerprint: &str) -> String {
+    format!("localsend-{fingerprint}")
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalSendFile {
+    pub file_name: String,
+    pub size: u64,
+    pub token: String,
+    received: bool,
+}
+
+#[derive(Debug)]
+struct LocalSendSession {
+    session_id: String,
+    /// Synthetic device of the sender
+    sender: String,
+    files: HashMap<String, LocalSendFile>,
+    last_active: Instant,
+}
+
+/// LocalSend allows a single upload session at a time, a second sender gets `409 Conflict`.
+#[derive(Clone, Default)]
+pub struct LocalSendSessions {
+    active: Arc<Mutex<Option<LocalSendSession>>>,
+}
+
+impl LocalSendSessions {
+    /// Starts a session for `files` (`file_id -> (file_name, size)`), returns the session id
+    /// and the upload token of every file.
+    pub fn start(
+        &self,
+        sender: &str,
+        files: impl IntoIterator<Item = (String, String, u64)>,
+    ) -> Result<(String, HashMap<String, String>)> {
+        let mut active = self.active.lock();
+        if active
+            .as_ref()
+            .is_some_and(|session| session.last_active.elapsed() < SESSION_IDLE_TIMEOUT)
+        {
+            bail!("Blocked by another session");
+        }
+
+        let files: HashMap<String, LocalSendFile> = files
+            .into_iter()
+            .map(|(file_id, file_name, size)| {
+                let file = LocalSendFile {
+                    file_name,
+                    size,
+                    token: uuid::Uuid::new_v4().simple().to_string(),
+                    received: false,
+                };
+                (file_id, file)
+            })
+            .collect();
+        let tokens = files
+            .iter()
+            .map(|(file_id, file)| (file_id.clone(), file.token.clone()))
+            .collect();
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        *active = Some(LocalSendSession {
+            session_id: session_id.clone(),
+            sender: sender.to_string(),
+            files,
+            last_active: Instant::now(),
+        });
+
+        Ok((session_id, tokens))
+    }
+
+    /// The sender and the file, if the token is valid and the file has not been received yet
+    pub fn claim(&self, session_id: &str, file_id: &str, token: &str) -> Option<(String, LocalSendFile)> {
+        let mut active = self.active.lock();
+        let session = active.as_mut().filter(|session| session.session_id == session_id)?;
+        let file = session
+            .files
+            .get(file_id)
+            .filter(|file| file.token == token && !file.received)?
+            .clone();
+        session.last_active = Instant::now();
+
+        Some((session.sender.clone(), file))
+    }
+
+    /// Marks the file as received, the session ends with its last file
+    pub fn complete(&self, session_id: &str, file_id: &str) {
+        let mut active = self.active.lock();
+        let Some(session) = active.as_mut().filter(|session| session.session_id == session_id) else {
+            return;
+        };
+        if let Some(file) = session.files.get_mut(file_id) {
+            file.received = true;
+        }
+        session.last_active = Instant::now();
+
+        if session.files.values().all(|file| file.received) {
+            *active = None;
+        }
+    }
+
+    pub fn cancel(&self, session_id: &str) -> bool {
+        let mut active = self.active.lock();
+        if active.as_ref().is_some_and(|session| session.session_id == session_id) {
+            *active = None;
+            return true;
+        }
+        false
+    }
+
+    /// Whether the session is still running, uploads stop once it is cancelled
+    pub fn is_active(&self, session_id: &str) -> bool {
+        self.active
+            .lock()
+            .as_ref()
+            .is_some_and(|session| session.session_id == session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions() {
+        let sessions = LocalSendSessions::default();
+        let files = vec![
+            ("a".to_string(), "a.txt".to_string(), 1),
+            ("b".to_string(), "b.txt".to_string(), 2),
+        ];
+        let (session_id, tokens) = sessions.start("localsend-x", files).unwrap();
+
+        // a second sender is blocked while the session runs
+        assert!(sessions.start("localsend-y", vec![]).is_err());
+
+        assert!(sessions.claim(&session_id, "a", "wrong").is_none());
+        let (sender, file) = sessions.claim(&session_id, "a", &tokens["a"]).unwrap();
+        assert_eq!(sender, "localsend-x");
+        assert_eq!(file.file_name, "a.txt");
+
+        sessions.complete(&session_id, "a");
+        assert!(sessions.claim(&session_id, "a", &tokens["a"]).is_none());
+        assert!(sessions.is_active(&session_id));
+
+        sessions.complete(&session_id, "b");
+        assert!(!sessions.is_active(&session_id));
+        assert!(sessions.start("localsend-y", vec![]).is_ok());
+    }
+}
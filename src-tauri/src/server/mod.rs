@@ -1,34 +1,39 @@
 #![allow(unused)]
 
 use crate::{
-    config::Config,
+    config::{Config, ISynclan},
+    core::tray,
     feat, logging, logging_error,
+    module::device_access::DeviceAccess,
     process::AsyncHandler,
     server::{
         events::{handlers, store},
         workers::WorkerMonitor,
     },
     singleton,
-    utils::{db, dirs, logging::Type, tls},
+    utils::{db, dirs, logging::Type, network, tls},
 };
 use anyhow::{Context, Result, anyhow};
 use apalis::prelude::{BackoffConfig, IntervalStrategy, StrategyBuilder};
 use apalis_sqlite::SqliteStorage;
 use api_doc::ApiDoc;
 use axum::{
+    Router,
     extract::DefaultBodyLimit,
-    handler::HandlerWithoutStateExt,
-    http::{Method, StatusCode, header},
+    http::{Method, header},
 };
-use axum_server::Handle;
+use axum_server::{Handle, tls_rustls::RustlsConfig};
 use parking_lot::Mutex;
+use serde::Serialize;
 use socketioxide::{SocketIo, handler::ConnectHandler, layer::SocketIoLayer};
 use sqlx::{Pool, Sqlite};
 use std::{
-    net::{IpAddr, SocketAddr},
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::Arc,
     time::Duration,
 };
+use tokio::sync::{oneshot, watch};
 use tower_http::{
     cors::{AllowOrigin, CorsLayer},
     services::{ServeDir, ServeFile},
@@ -38,24 +43,48 @@ use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
 
 mod api_doc;
+mod audit;
+pub mod cors;
+pub mod discovery;
 mod dtos;
 pub mod events;
 mod exception;
 mod extractors;
+pub mod federation;
 mod guards;
+pub mod localsend;
+pub mod pairing;
+pub mod rate_limit;
 mod routes;
+mod security_headers;
+pub mod signed_url;
 mod status_code_serde;
+pub mod transfer;
 mod workers;
 
+/// How often the sockets of expired guests are looked for
+const GUEST_EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Pool<Sqlite>,
     pub message_storage: workers::MessageBackend,
+    pub upload_storage: workers::UploadBackend,
+    pub upload_sessions: workers::UploadSessions,
+    pub thumbnail_storage: workers::ThumbnailBackend,
+    pub transfers: transfer::Transfers,
+    pub localsend: localsend::LocalSendSessions,
 }
 
 pub struct HttpServer {
     handle: Arc<Mutex<Option<Handle<std::net::SocketAddr>>>>,
     runtime_handle: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    /// Port actually bound, `None` while not listening
+    port: watch::Sender<Option<u16>>,
+    /// Socket.IO of the running server, to reach the connected clients
+    io: Arc<Mutex<Option<SocketIo>>>,
+    /// Chunk uploads of the running server, a revoked device's are dropped
+    upload_sessions: Arc<Mutex<Option<workers::UploadSessions>>>,
 }
 
 impl Default for HttpServer {
@@ -63,10 +92,29 @@ impl Default for HttpServer {
         Self {
             handle: Arc::new(Mutex::new(None)),
             runtime_handle: Arc::new(Mutex::new(None)),
+            port: watch::Sender::new(None),
+            io: Arc::new(Mutex::new(None)),
+            upload_sessions: Arc::new(Mutex::new(None)),
         }
     }
 }
 
+/// Sent to the connected clients before the server restarts
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerRestart {
+    /// Where the server comes back, empty when it may come back on another port:
+    /// a random one, or a free one when the configured port is taken
+    pub urls: Vec<String>,
+}
+
+/// Port the server listens on, the configured one until it is bound
+pub fn listening_port(synclan: &ISynclan) -> u16 {
+    HttpServer::global()
+        .port()
+        .unwrap_or_else(|| synclan.http_server_port.unwrap_or(53317))
+}
+
 singleton!(HttpServer, HTTPSERVER);
 
 impl HttpServer {
@@ -74,6 +122,18 @@ impl HttpServer {
         Self::default()
     }
 
+    /// Port actually bound, which differs from the configured one in random port mode
+    /// or when the configured one was taken
+    pub fn port(&self) -> Option<u16> {
+        *self.port.borrow()
+    }
+
+    /// Waits until the server listens, returns its port
+    pub async fn listening(&self) -> Option<u16> {
+        let mut port = self.port.subscribe();
+        port.wait_for(Option::is_some).await.ok().and_then(|port| *port)
+    }
+
     /// start http server
     pub async fn start(&self, db_pool: &Pool<Sqlite>) -> Result<()> {
         // stop the current server first (if any)
@@ -89,12 +149,85 @@ impl HttpServer {
         feat::uploaded_files_auto_cleanup().await?;
 
         let db_pool_clone = db_pool.clone();
-        let runtime_handle = AsyncHandler::spawn(|| async {
-            logging_error!(Type::Server, Self::run(db_pool_clone, cloned_handle).await);
+        let (stopped_tx, stopped_rx) = oneshot::channel();
+        let runtime_handle = AsyncHandler::spawn(move || async move {
+            let result = Self::run(db_pool_clone, cloned_handle).await;
+            let _ = stopped_tx.send(result.as_ref().map_err(|err| format!("{err:#}")).copied());
+            logging_error!(Type::Server, result);
         });
         *self.runtime_handle.lock() = Some(runtime_handle);
 
-        Ok(())
+        // it is up once it listens, failing to bind stops it before that
+        tokio::select! {
+            _ = self.listening() => Ok(()),
+            stopped = stopped_rx => match stopped {
+                Ok(Err(err)) => Err(anyhow!(err)),
+                _ => Err(anyhow!("The HTTP server stopped before listening")),
+            },
+        }
+    }
+
+    /// Restarts with the latest config: the clients are told to reconnect, the connections
+    /// drain and the in-flight jobs finish before the server binds again.
+    /// Fails if the new server can't listen, the old one is gone by then.
+    pub async fn restart(&self, db_pool: &Pool<Sqlite>) -> Result<()> {
+        self.notify_restart().await;
+        self.start(db_pool).await
+    }
+
+    async fn notify_restart(&self) {
+        let Some(io) = self.io.lock().clone() else {
+            return;
+        };
+        let synclan = Config::synclan().await.latest_arc();
+        // the port this server holds is freed for the restart, another one may be taken
+        let port = synclan.http_server_port.unwrap_or(53317);
+        let urls = if synclan.enable_random_port.unwrap_or(false) || self.port() != Some(port) {
+            vec![]
+        } else {
+            network::server_urls(&synclan, port).unwrap_or_default()
+        };
+
+        let restart = ServerRestart { urls };
+        if let Some(ns) = io.of("/socket") {
+            for socket in ns.sockets() {
+                socket.emit("synclan://server:restart", &restart).ok();
+            }
+        }
+    }
+
+    /// Disconnects every socket of the device, the disconnect handler drops it from `Clients`
+    fn disconnect_device(&self, device_id: &str) -> usize {
+        let Some(ns) = self.io.lock().clone().and_then(|io| io.of("/socket")) else {
+            return 0;
+        };
+        let sockets: Vec<_> = ns
+            .sockets()
+            .into_iter()
+            .filter(|socket| {
+                socket
+                    .extensions
+                    .get::<Arc<store::Client>>()
+                    .is_some_and(|client| client.client_id == device_id)
+            })
+            .collect();
+        let count = sockets.len();
+        for socket in sockets {
+            socket.disconnect().ok();
+        }
+        count
+    }
+
+    /// Requests of an expired guest are refused on their own, its open sockets are not
+    async fn disconnect_expired_guests() {
+        let mut interval = tokio::time::interval(GUEST_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            match DeviceAccess::expired_guests().await {
+                Ok(ids) => ids.iter().for_each(|id| disconnect_device(id)),
+                Err(err) => logging!(error, Type::Server, "Failed to look for expired guests: {err}"),
+            }
+        }
     }
 
     pub async fn run(
@@ -102,50 +235,119 @@ impl HttpServer {
         db_pool: Pool<Sqlite>,
         handle: Handle<std::net::SocketAddr>,
     ) -> Result<()> {
-        let config = &apalis_sqlite::Config::default()
-            .with_poll_interval(
-                StrategyBuilder::new()
-                    .apply(
-                        IntervalStrategy::new(Duration::from_millis(100)).with_backoff(
-                            BackoffConfig::new(Duration::from_secs(2))
-                                .with_multiplier(1.5)
-                                .with_jitter(0.1),
-                        ),
-                    )
-                    .build(),
-            )
-            .set_buffer_size(50);
-        let mut message_backend = SqliteStorage::new_with_config(&db_pool, config);
+        let mut message_backend = SqliteStorage::new_with_config(&db_pool, &Self::backend_config(None));
+        // merge jobs live in their own queue, so the message dispatcher never fetches them
+        let upload_backend =
+            SqliteStorage::new_with_config(&db_pool, &Self::backend_config(Some("synclan-upload-merge")));
+        let upload_sessions = workers::UploadSessions::default();
+        let thumbnail_backend =
+            SqliteStorage::new_with_config(&db_pool, &Self::backend_config(Some("synclan-thumbnail")));
+        let clients = store::Clients::default();
+        let transfers = transfer::Transfers::new(clients.clone());
         let app_state = Arc::new(AppState {
             db_pool,
             message_storage: message_backend.clone(),
+            upload_storage: upload_backend.clone(),
+            upload_sessions: upload_sessions.clone(),
+            thumbnail_storage: thumbnail_backend.clone(),
+            transfers: transfers.clone(),
+            localsend: localsend::LocalSendSessions::default(),
         });
 
-        let clients = store::Clients::default();
         let (layer, io) = SocketIo::builder()
             .with_state(app_state.clone())
             .with_state(clients.clone())
             .build_layer();
+        transfers.bind(io.clone());
+        *HttpServer::global().io.lock() = Some(io.clone());
+        *HttpServer::global().upload_sessions.lock() = Some(upload_sessions.clone());
         io.ns(
             "/socket",
             handlers::on_connection.with(handlers::authenticate_middleware),
         );
 
-        tokio::try_join!(
+        // abandoned chunk uploads give their reservation back
+        let sweeper = tokio::spawn(workers::upload::sweep_stale_sessions(upload_sessions.clone()));
+        let guest_expiry = tokio::spawn(Self::disconnect_expired_guests());
+        let served = tokio::try_join!(
             Self::run_http_server(handle, app_state, layer),
-            Self::run_backend_server(message_backend, io, clients),
-        )?;
+            Self::run_backend_server(
+                message_backend,
+                upload_backend,
+                thumbnail_backend,
+                io,
+                clients,
+                upload_sessions
+            ),
+            Self::run_discovery(),
+            Self::run_federation(),
+        );
+        sweeper.abort();
+        guest_expiry.abort();
+        served?;
 
         Ok(())
     }
 
+    /// apalis sqlite storage config, `queue` defaults to the job type name
+    fn backend_config(queue: Option<&str>) -> apalis_sqlite::Config {
+        let config = match queue {
+            Some(queue) => apalis_sqlite::Config::new(queue),
+            None => apalis_sqlite::Config::default(),
+        };
+        config
+            .with_poll_interval(
+                StrategyBuilder::new()
+                    .apply(
+                        IntervalStrategy::new(Duration::from_millis(100)).with_backoff(
+                            BackoffConfig::new(Duration::from_secs(2))
+                                .with_multiplier(1.5)
+                                .with_jitter(0.1),
+                        ),
+                    )
+                    .build(),
+            )
+            .set_buffer_size(50)
+    }
+
     async fn run_backend_server(
         // &self,
         message_backend: workers::MessageBackend,
+        upload_backend: workers::UploadBackend,
+        thumbnail_backend: workers::ThumbnailBackend,
         io: SocketIo,
         clients: store::Clients,
+        upload_sessions: workers::UploadSessions,
     ) -> Result<()> {
-        WorkerMonitor::global().run(message_backend, io, clients).await
+        WorkerMonitor::global()
+            .run(
+                message_backend,
+                upload_backend,
+                thumbnail_backend,
+                io,
+                clients,
+                upload_sessions,
+            )
+            .await
+    }
+
+    /// LAN discovery must not take the server down with it
+    async fn run_discovery() -> Result<()> {
+        // announcements carry the bound port, a server that never binds has nothing to announce
+        if tokio::time::timeout(Duration::from_secs(10), HttpServer::global().listening())
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+        logging_error!(Type::Server, discovery::Discovery::global().run().await);
+        Ok(())
+    }
+
+    /// Paired hosts may have gained or lost devices while this one was down
+    async fn run_federation() -> Result<()> {
+        logging_error!(Type::Server, federation::sync_all().await);
+        Ok(())
     }
 
     /// the entry to start http server
@@ -167,22 +369,25 @@ impl HttpServer {
         let static_server =
             ServeDir::new(&web_static_dir).not_found_service(ServeFile::new(web_static_dir.join("index.html")));
 
-        let mut app = router
-            // swagger ui
-            .merge(SwaggerUi::new("/api/docs").url("/api/docs/openapi.json", api));
-
-        if let Some(file_upload_dir) = &synclan.file_upload_dir {
-            // uploads files static server
-            app = app.nest_service(
-                "/attachments",
-                ServeDir::new(file_upload_dir)
-                    .not_found_service((async || (StatusCode::NOT_FOUND, "Not found")).into_service()),
-            );
+        let mut app = router;
+
+        // swagger ui
+        if synclan.enable_api_docs.unwrap_or(cfg!(debug_assertions)) {
+            app = app.merge(SwaggerUi::new("/api/docs").url("/api/docs/openapi.json", api));
+        }
+
+        // uploaded files
+        app = app.nest("/attachments", routes::attachment_router());
+
+        // LocalSend apps talk to us as a LocalSend peer
+        if synclan.enable_localsend.unwrap_or(false) {
+            app = app.nest("/api/localsend/v2", routes::localsend_router());
         }
 
         app = app
             // web static server
             .fallback_service(static_server);
+        app = security_headers::layer(app, synclan.enable_encryption != Some(false));
 
         let app = app
             .layer(layer)
@@ -196,6 +401,16 @@ impl HttpServer {
                         header::AUTHORIZATION,
                         header::CONTENT_LANGUAGE,
                         header::CONTENT_TYPE,
+                        header::IF_NONE_MATCH,
+                        header::IF_RANGE,
+                        header::RANGE,
+                    ])
+                    .expose_headers([
+                        header::ACCEPT_RANGES,
+                        header::CONTENT_DISPOSITION,
+                        header::CONTENT_LENGTH,
+                        header::CONTENT_RANGE,
+                        header::ETAG,
                     ])
                     .allow_methods([
                         Method::GET,
@@ -207,7 +422,7 @@ impl HttpServer {
                         Method::OPTIONS,
                     ])
                     .allow_origin(AllowOrigin::predicate(|origin, _request_parts| {
-                        origin.as_bytes().starts_with(b"http://localhost")
+                        cors::CorsOrigins::global().is_allowed(origin)
                     }))
                     .max_age(Duration::from_secs(3600)),
             )
@@ -215,29 +430,157 @@ impl HttpServer {
 
         logging!(info, Type::Server, "Starting HTTP server...");
 
-        let ip: IpAddr = "0.0.0.0".parse()?;
-        let port = synclan.http_server_port.unwrap_or(53317);
-        let addr = SocketAddr::from((ip, port));
+        let ips = network::listen_ips(&synclan)?;
+        let configured_port = synclan.http_server_port.unwrap_or(53317);
+        let random = synclan.enable_random_port.unwrap_or(false);
+        let port = if random { 0 } else { configured_port };
+        let on_listening = |addr: SocketAddr| {
+            HttpServer::global().port.send_replace(Some(addr.port()));
+            logging_error!(Type::Server, cors::CorsOrigins::global().refresh(&synclan, addr.port()));
+            AsyncHandler::spawn(|| async {
+                logging_error!(Type::Tray, tray::Tray::global().update_menu().await);
+            });
+        };
 
-        match synclan.enable_encryption {
+        let served = match synclan.enable_encryption {
             Some(true) | None => {
-                let config = tls::build_rustls_config_with_ip(&ip).await?;
-                logging!(info, Type::Server, "HTTP server listening on https://{}", addr);
+                let config = tls::ServerTls::global().build_config().await?;
+                let watcher = tokio::spawn(tls::ServerTls::global().watch());
+                let redirect_handle = Handle::new();
+                if let Some(redirect_port) = synclan.http_redirect_port.filter(|p| *p != 0 && *p != configured_port) {
+                    tokio::spawn(Self::run_http_redirect(
+                        ips.clone(),
+                        redirect_port,
+                        redirect_handle.clone(),
+                    ));
+                }
+
+                let served = Self::serve_all(&ips, port, !random, Some(config), handle, app, on_listening).await;
+                redirect_handle.graceful_shutdown(Some(Duration::from_secs(1)));
+                watcher.abort();
+                tls::ServerTls::global().clear();
+                served
+            },
+            Some(false) => Self::serve_all(&ips, port, !random, None, handle, app, on_listening).await,
+        };
+        HttpServer::global().port.send_replace(None);
+
+        served
+    }
+
+    /// Serves `app` on every IP until `handle` shuts them all down, over HTTPS with `tls`.
+    /// With port `0` the first IP picks a free port, which the others share.
+    /// With `fallback` the first IP picks a free port as well when `port` is taken.
+    async fn serve_all(
+        ips: &[IpAddr],
+        port: u16,
+        fallback: bool,
+        tls: Option<RustlsConfig>,
+        handle: Handle<std::net::SocketAddr>,
+        app: Router,
+        on_listening: impl FnOnce(SocketAddr),
+    ) -> Result<()> {
+        let dual_stack = ips.contains(&IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        let (first, rest) = ips.split_first().context("No address to listen on")?;
+
+        let serve_first = |port| {
+            tokio::spawn(Self::serve_one(
+                SocketAddr::new(*first, port),
+                tls.clone(),
+                handle.clone(),
+                app.clone(),
+                dual_stack,
+            ))
+        };
+        let mut first = serve_first(port);
+        let bound = match handle.listening().await {
+            Some(bound) => bound,
+            // the bind failed, the task has the error
+            None => {
+                let err = match first.await? {
+                    Err(err) if fallback && port != 0 && Self::is_addr_in_use(&err) => err,
+                    served => return served,
+                };
+                logging!(
+                    warn,
+                    Type::Server,
+                    "Port {port} is in use, listening on a free port instead: {err:#}"
+                );
+                first = serve_first(0);
+                let Some(bound) = handle.listening().await else {
+                    return first.await?;
+                };
+                bound
+            },
+        };
+        on_listening(bound);
+
+        let abort_first = first.abort_handle();
+        let rest = rest.iter().map(|ip| {
+            let addr = SocketAddr::new(*ip, bound.port());
+            Self::serve_one(addr, tls.clone(), handle.clone(), app.clone(), dual_stack)
+        });
+        let served = tokio::try_join!(async { first.await? }, futures::future::try_join_all(rest));
+        if served.is_err() {
+            abort_first.abort();
+        }
+        served.map(|_| ())
+    }
+
+    fn is_addr_in_use(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::AddrInUse)
+    }
+
+    /// `0.0.0.0` next to a dual-stack `::` can't bind and is not needed, that is not an error
+    async fn serve_one(
+        addr: SocketAddr,
+        tls: Option<RustlsConfig>,
+        handle: Handle<std::net::SocketAddr>,
+        app: Router,
+        dual_stack: bool,
+    ) -> Result<()> {
+        let protocol = if tls.is_some() { "https" } else { "http" };
+        let server = match tls {
+            Some(config) => {
                 axum_server::bind_rustls(addr, config)
                     .handle(handle)
-                    .serve(app.into_make_service())
-                    .await?;
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
             },
-            Some(false) => {
-                logging!(info, Type::Server, "HTTP server listening on http://{}", addr);
+            None => {
                 axum_server::bind(addr)
                     .handle(handle)
-                    .serve(app.into_make_service())
-                    .await?;
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
             },
         };
+        match server {
+            Err(err)
+                if err.kind() == io::ErrorKind::AddrInUse
+                    && dual_stack
+                    && addr.ip() == IpAddr::V4(Ipv4Addr::UNSPECIFIED) =>
+            {
+                logging!(debug, Type::Server, "{addr} is covered by the dual-stack IPv6 socket");
+                Ok(())
+            },
+            served => served.with_context(|| format!("Failed to serve on {protocol}://{addr}")),
+        }
+    }
 
-        Ok(())
+    /// Plain HTTP next to HTTPS, see [`routes::http_redirect_router`].
+    /// It is only a convenience, failing to bind it does not stop the server.
+    async fn run_http_redirect(ips: Vec<IpAddr>, port: u16, handle: Handle<std::net::SocketAddr>) {
+        let listening = tokio::time::timeout(Duration::from_secs(10), HttpServer::global().listening());
+        let Ok(Some(https_port)) = listening.await else {
+            return;
+        };
+        let app = routes::http_redirect_router(https_port);
+        let on_listening =
+            |addr: SocketAddr| logging!(info, Type::Server, "HTTP redirect listening on port {}", addr.port());
+        if let Err(err) = Self::serve_all(&ips, port, false, None, handle, app, on_listening).await {
+            logging!(error, Type::Server, "HTTP redirect stopped: {err:?}");
+        }
     }
 
     /// gracefully shutdown http server & worker
@@ -251,22 +594,31 @@ impl HttpServer {
         logging!(info, Type::Server, "Shutting down HTTP server");
 
         WorkerMonitor::global().shutdown();
+        discovery::Discovery::global().shutdown();
+        self.io.lock().take();
+        self.upload_sessions.lock().take();
 
         if let Some(handle) = self.handle.lock().take() {
             handle.graceful_shutdown(Some(Duration::from_secs(5)));
         }
 
         let runtime_handle = self.runtime_handle.lock().take();
-        if let Some(handle) = runtime_handle {
-            // Wait for the runtime to finish, with a timeout to prevent hanging indefinitely.
-            if tokio::time::timeout(Duration::from_millis(5500), handle).await.is_err() {
+        if let Some(mut handle) = runtime_handle {
+            // Wait for the runtime to finish, the workers get 5s to finish their jobs.
+            // With a timeout to prevent hanging indefinitely.
+            if tokio::time::timeout(Duration::from_secs(10), &mut handle)
+                .await
+                .is_err()
+            {
                 logging!(
                     warn,
                     Type::Server,
                     "Graceful shutdown timed out! Aborting backend tasks."
                 );
+                handle.abort();
             }
         }
+        self.port.send_replace(None);
 
         logging!(info, Type::Server, "HTTP server shutdown successfully");
 
@@ -291,11 +643,48 @@ pub async fn restart_http_server() -> Result<()> {
     let db_pool = db::DBManager::global()
         .db_pool()
         .with_context(|| anyhow!("Failed to restart: SQLite connection pool has not been initialized"))?;
-    HttpServer::global().start(db_pool).await?;
+    HttpServer::global().restart(db_pool).await?;
     logging!(info, Type::Server, "HTTP server restarted successfully");
     Ok(())
 }
 
+/// Cuts off a device that was removed or blocked: its live sockets are disconnected and
+/// the jobs it would receive or its uploads are waiting for are dropped, with the chunks
+/// of those uploads. Requests it makes afterwards are rejected because its row is gone.
+pub async fn revoke_device(device_id: &str) -> Result<()> {
+    let disconnected = HttpServer::global().disconnect_device(device_id);
+    rate_limit::RateLimiter::global().remove_device(device_id);
+    let purged = workers::purge_device_jobs(&db::get_db_pool()?, device_id).await?;
+
+    // without a running server there is no reservation, only the chunks on disk
+    let sessions = HttpServer::global().upload_sessions.lock().clone().unwrap_or_default();
+    let synclan = Config::synclan().await.data_arc();
+    if let Some(upload_dir) = synclan.file_upload_dir.as_deref() {
+        for upload_id in &purged.upload_ids {
+            logging_error!(Type::Server, sessions.discard(upload_dir, upload_id).await);
+        }
+    }
+    logging!(
+        info,
+        Type::Server,
+        "Revoked device {device_id}: {disconnected} sockets disconnected, {} jobs dropped",
+        purged.count
+    );
+    Ok(())
+}
+
+/// Disconnects the live sockets of a device whose access changed, see [`HttpServer::disconnect_device`]
+pub fn disconnect_device(device_id: &str) {
+    let disconnected = HttpServer::global().disconnect_device(device_id);
+    if disconnected > 0 {
+        logging!(
+            info,
+            Type::Server,
+            "Disconnected {disconnected} sockets of device {device_id}"
+        );
+    }
+}
+
 /// Stop the local http server
 pub async fn stop_http_server() -> Result<()> {
     logging!(info, Type::Server, "Stopping local HTTP server...");
@@ -0,0 +1,71 @@
+use crate::singleton;
+use anyhow::{Result, anyhow};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// How long a pairing QR code can be scanned
+const GRANT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// What the pairing QR code encodes
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingLink {
+    /// e.g. `https://192.168.1.2:53317/?grant=..`
+    pub url: String,
+    /// SHA-256 of the server certificate to compare by hand, `None` over plain HTTP
+    pub fingerprint: Option<String>,
+    pub grant: String,
+    /// Unix timestamp in milliseconds
+    pub expires_at: i64,
+}
+
+/// One-time grants that let a device register without the access code
+pub struct PairingGrants {
+    grants: DashMap<String, Instant>,
+}
+
+singleton!(PairingGrants, PAIRING_GRANTS);
+
+impl PairingGrants {
+    fn new() -> Self {
+        Self { grants: DashMap::new() }
+    }
+
+    /// Issues a grant, returns it with its expiry as a unix timestamp in milliseconds
+    pub fn issue(&self) -> Result<(String, i64)> {
+        let mut grant = [0u8; 16];
+        getrandom::fill(&mut grant).map_err(|err| anyhow!("{err}"))?;
+        let grant: String = grant.iter().map(|b| format!("{b:02x}")).collect();
+
+        self.grants.retain(|_, issued| issued.elapsed() < GRANT_TTL);
+        self.grants.insert(grant.clone(), Instant::now());
+
+        let expires_at = chrono::Utc::now().timestamp_millis() + GRANT_TTL.as_millis() as i64;
+        Ok((grant, expires_at))
+    }
+
+    /// Uses up the grant, `false` if it is unknown, used or expired
+    pub fn redeem(&self, grant: &str) -> bool {
+        self.grants
+            .remove(grant)
+            .is_some_and(|(_, issued)| issued.elapsed() < GRANT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_is_single_use() {
+        let grants = PairingGrants::new();
+        let (grant, expires_at) = grants.issue().unwrap();
+        assert!(expires_at > chrono::Utc::now().timestamp_millis());
+
+        assert!(!grants.redeem("unknown"));
+        assert!(grants.redeem(&grant));
+        assert!(!grants.redeem(&grant));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    config::{Config, ISynclan},
+    singleton,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// What a request spends a token of, every device has its own bucket for each
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Message,
+    Upload,
+    Discovery,
+    /// Failed access codes, registrations and handshakes, counted per IP
+    /// since the client has no identity yet
+    AuthFailure,
+}
+
+impl Limit {
+    /// Requests per minute, `0` for no limit
+    fn per_minute(self, synclan: &ISynclan) -> u32 {
+        match self {
+            Limit::Message => synclan.rate_limit_messages.unwrap_or(120),
+            Limit::Upload => synclan.rate_limit_uploads.unwrap_or(60),
+            Limit::Discovery => synclan.rate_limit_discovery.unwrap_or(60),
+            Limit::AuthFailure => 10,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Too many requests, retry in {}s", .retry_after.as_secs_f64().ceil())]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+/// Buckets kept before the full ones are dropped, IPs come and go unlike devices
+const MAX_BUCKETS: usize = 1024;
+
+/// Holds up to a minute worth of requests and refills continuously,
+/// so a device may burst and is then slowed down to the configured rate
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Refilled to capacity, no different from a new bucket
+    fn is_full(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.updated) >= Duration::from_secs(60)
+    }
+
+    /// Refills the bucket, fails if there is no token to spend
+    fn check(&mut self, per_minute: u32, now: Instant) -> Result<(), RateLimited> {
+        let capacity = f64::from(per_minute);
+        let per_second = capacity / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            return Ok(());
+        }
+        Err(RateLimited {
+            retry_after: Duration::from_secs_f64((1.0 - self.tokens) / per_second),
+        })
+    }
+
+    fn take(&mut self, per_minute: u32, now: Instant) -> Result<(), RateLimited> {
+        self.check(per_minute, now)?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Token buckets per device, so one misbehaving client can't flood the server.
+/// Failed authentications are limited per IP, see [`Limit::AuthFailure`].
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<(String, Limit), TokenBucket>>,
+}
+
+singleton!(RateLimiter, RATE_LIMITER);
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spends a token of `device_id`'s `limit` bucket, or the IP's for [`Limit::AuthFailure`]
+    pub async fn acquire(&self, device_id: &str, limit: Limit) -> Result<(), RateLimited> {
+        let per_minute = limit.per_minute(&Config::synclan().await.data_arc());
+        self.take(device_id, limit, per_minute, Instant::now())
+    }
+
+    /// Fails like [`Self::acquire`] without spending a token,
+    /// for limits that only the failed requests spend
+    pub async fn check(&self, device_id: &str, limit: Limit) -> Result<(), RateLimited> {
+        let per_minute = limit.per_minute(&Config::synclan().await.data_arc());
+        self.with_bucket(device_id, limit, per_minute, Instant::now(), TokenBucket::check)
+    }
+
+    /// Forgets the buckets of a device that is gone
+    pub fn remove_device(&self, device_id: &str) {
+        self.buckets.lock().retain(|(id, _), _| id != device_id);
+    }
+
+    fn take(&self, device_id: &str, limit: Limit, per_minute: u32, now: Instant) -> Result<(), RateLimited> {
+        self.with_bucket(device_id, limit, per_minute, now, TokenBucket::take)
+    }
+
+    fn with_bucket(
+        &self,
+        device_id: &str,
+        limit: Limit,
+        per_minute: u32,
+        now: Instant,
+        f: impl FnOnce(&mut TokenBucket, u32, Instant) -> Result<(), RateLimited>,
+    ) -> Result<(), RateLimited> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let mut buckets = self.buckets.lock();
+        let key = (device_id.to_owned(), limit);
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| !bucket.is_full(now));
+        }
+        let bucket = buckets.entry(key).or_insert_with(|| TokenBucket {
+            tokens: f64::from(per_minute),
+            updated: now,
+        });
+        f(bucket, per_minute, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.take("a", Limit::Message, 3, now).is_ok());
+        }
+        let limited = limiter.take("a", Limit::Message, 3, now).unwrap_err();
+        assert_eq!(limited.retry_after.as_secs_f64().round(), 20.0);
+
+        // other devices and other limits have their own buckets
+        assert!(limiter.take("b", Limit::Message, 3, now).is_ok());
+        assert!(limiter.take("a", Limit::Upload, 3, now).is_ok());
+
+        // one token every 20 seconds
+        let later = now + Duration::from_secs(21);
+        assert!(limiter.take("a", Limit::Message, 3, later).is_ok());
+        assert!(limiter.take("a", Limit::Message, 3, later).is_err());
+
+        // no limit
+        for _ in 0..10 {
+            assert!(limiter.take("c", Limit::Message, 0, now).is_ok());
+        }
+
+        // checking does not spend a token
+        for _ in 0..5 {
+            assert!(
+                limiter
+                    .with_bucket("d", Limit::AuthFailure, 1, now, TokenBucket::check)
+                    .is_ok()
+            );
+        }
+        assert!(limiter.take("d", Limit::AuthFailure, 1, now).is_ok());
+        assert!(
+            limiter
+                .with_bucket("d", Limit::AuthFailure, 1, now, TokenBucket::check)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_evict_full_buckets() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+
+        for ip in 0..MAX_BUCKETS {
+            assert!(limiter.take(&ip.to_string(), Limit::AuthFailure, 10, now).is_ok());
+        }
+        // the buckets still refilling are kept, the map grows past the limit
+        assert!(limiter.take("late", Limit::AuthFailure, 10, now).is_ok());
+        assert_eq!(limiter.buckets.lock().len(), MAX_BUCKETS + 1);
+
+        // a minute later they are all full again and make room for the new one
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.take("new", Limit::AuthFailure, 10, later).is_ok());
+        assert_eq!(limiter.buckets.lock().len(), 1);
+    }
+}
@@ -0,0 +1,428 @@
+use super::HttpResponse;
+use crate::{
+    config::Config,
+    logging,
+    module::attachment::Attachment,
+    server::{
+        dtos::attachment_dto::DownloadDto,
+        exception::HttpException,
+        extractors::Query,
+        signed_url::UrlSigner,
+        workers::upload::{BUNDLE_MANIFEST, BundleManifest},
+    },
+    utils::{logging::Type, sniff},
+};
+use axum::{
+    body::Body,
+    extract::Path as RoutePath,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    io::{SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt as _, AsyncSeekExt as _},
+};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+/// Download an uploaded file.
+///
+/// Supports `Range` (single range) and `If-Range` requests so large files can be
+/// resumed and seeked, and `If-None-Match` revalidation with a weak `ETag`.
+/// `Content-Disposition` carries the original file name from the upload metadata.
+///
+/// The content type is the one sniffed at upload, types a browser could run scripts
+/// from (html, svg, ...) are always sent as a download with `X-Content-Type-Options: nosniff`.
+///
+/// A bundle folder returns its file list, or with `download` the whole folder as a zip stream.
+pub(crate) async fn download(
+    RoutePath(path): RoutePath<String>,
+    Query(query): Query<DownloadDto>,
+    headers: HeaderMap,
+) -> Result<Response, HttpException> {
+    let synclan = Config::synclan().await.data_arc();
+    let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
+        HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+    })?;
+
+    let relative_path = Path::new(&path);
+    if !is_downloadable(relative_path) {
+        return Err(HttpException::NotFoundException(None));
+    }
+
+    let file_path = Path::new(upload_dir).join(relative_path);
+    let metadata = match fs::metadata(&file_path).await {
+        Ok(metadata) if metadata.is_dir() => return bundle(&path, &file_path, query.download).await,
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Err(HttpException::NotFoundException(None)),
+    };
+
+    let len = metadata.len();
+    let modified = metadata.modified()?;
+    let etag = format!(
+        "W/\"{:x}-{:x}\"",
+        len,
+        modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+    );
+    let last_modified = DateTime::<Utc>::from(modified)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).map_err(anyhow::Error::from)?);
+    response_headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&last_modified).map_err(anyhow::Error::from)?,
+    );
+
+    if let Some(if_none_match) = header_str(&headers, header::IF_NONE_MATCH)
+        && etag_matches(if_none_match, &etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let (file_name, mime) = match Attachment::get_by_path(&path).await? {
+        Some(attachment) => (attachment.name, attachment.mime),
+        None => (
+            relative_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            None,
+        ),
+    };
+    // files uploaded before sniffing was introduced fall back to the extension
+    let mime = mime.unwrap_or_else(|| mime_guess::from_path(&file_name).first_or_octet_stream().to_string());
+    let disposition = if query.download || !sniff::is_inline_safe(&mime) {
+        "attachment"
+    } else {
+        "inline"
+    };
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&mime).map_err(anyhow::Error::from)?,
+    );
+    response_headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        content_disposition(disposition, &file_name)?,
+    );
+
+    // a stale or weak `If-Range` validator means the client has to start over
+    let settled = modified.elapsed().is_ok_and(|age| age >= Duration::from_secs(1));
+    let range = match header_str(&headers, header::IF_RANGE) {
+        Some(if_range) if !if_range_matches(if_range, &etag, &last_modified, settled) => ByteRange::Full,
+        _ => parse_range(header_str(&headers, header::RANGE), len),
+    };
+
+    let (status, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, len.saturating_sub(1)),
+        ByteRange::Partial(start, end) => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")).map_err(anyhow::Error::from)?,
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        },
+        ByteRange::Unsatisfiable => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{len}")).map_err(anyhow::Error::from)?,
+            );
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        },
+    };
+
+    let content_length = if len == 0 { 0 } else { end - start + 1 };
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    let mut file = fs::File::open(&file_path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(content_length)));
+
+    Ok((status, response_headers, body).into_response())
+}
+
+/// Only folders with a bundle manifest are listed, never plain upload directories.
+async fn bundle(folder: &str, dir: &Path, download: bool) -> Result<Response, HttpException> {
+    let manifest = BundleManifest::load(dir)
+        .await
+        .map_err(|_| HttpException::NotFoundException(None))?;
+
+    // files that are still being merged, or failed to, are left out
+    let mut files = Vec::with_capacity(manifest.files.len());
+    for file in manifest.files {
+        let file_path = dir.join(&file.path);
+        if fs::try_exists(&file_path).await.unwrap_or(false) {
+            files.push((file, file_path));
+        }
+    }
+
+    if !download {
+        let listing = BundleListing {
+            size: files.iter().map(|(file, _)| file.size).sum(),
+            files: files
+                .into_iter()
+                .map(|(file, _)| BundleEntry {
+                    url: UrlSigner::global().sign(&format!("{folder}/{}", file.path)),
+                    path: file.path,
+                    size: file.size,
+                })
+                .collect(),
+            name: manifest.name,
+            path: folder.to_string(),
+        };
+        return Ok(HttpResponse::Json {
+            payload: listing,
+            message: None,
+        }
+        .into_response());
+    }
+
+    //
+    // Stream the zip while it is written, stored without compression
+    // so the size of the folder doesn't matter
+    //
+    let entries: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|(file, file_path)| (format!("{}/{}", manifest.name, file.path), file_path))
+        .collect();
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    let writer = SyncIoBridge::new(writer);
+    let name = manifest.name.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = write_zip(writer, &entries) {
+            logging!(warn, Type::Server, "Failed to stream bundle {name}: {err}");
+        }
+    });
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+    response_headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        content_disposition("attachment", &format!("{}.zip", manifest.name))?,
+    );
+
+    Ok((
+        StatusCode::OK,
+        response_headers,
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response())
+}
+
+fn write_zip(writer: impl Write, entries: &[(String, PathBuf)]) -> anyhow::Result<()> {
+    let mut zip = ZipWriter::new_stream(writer);
+    for (name, path) in entries {
+        let mut file = std::fs::File::open(path)?;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(file.metadata()?.len() >= u32::MAX as u64);
+        zip.start_file(name.as_str(), options)?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+    zip.finish()?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleListing {
+    name: String,
+    /// Folder path relative to the file upload directory
+    path: String,
+    /// Total size in bytes of the listed files
+    size: u64,
+    files: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleEntry {
+    /// Path relative to the folder
+    path: String,
+    size: u64,
+    /// Signed url relative to `/attachments`
+    url: String,
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Weak comparison of an `If-None-Match` header against the current `ETag`
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| weak(tag) == weak(etag))
+}
+
+/// `inline; filename="video.mp4"; filename*=UTF-8''video.mp4`
+///
+/// The quoted `filename` is an ASCII fallback for old clients,
+/// `filename*` carries the real UTF-8 name percent-encoded.
+pub(super) fn content_disposition(disposition: &str, file_name: &str) -> Result<HeaderValue, HttpException> {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+
+    HeaderValue::from_str(&format!(
+        "{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+    .map_err(|err| HttpException::InternalServerErrorException(Some(err.to_string())))
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No (usable) range, send the whole file
+    Full,
+    /// Inclusive byte range
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// `If-Range` needs a strong match (RFC 9110, section 13.1.5): entity tags are compared
+/// strongly, so a weak one on either side never matches, and a date only counts when
+/// the file was `settled`, i.e. modified at least a second before it is served.
+fn if_range_matches(if_range: &str, etag: &str, last_modified: &str, settled: bool) -> bool {
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        !if_range.starts_with("W/") && !etag.starts_with("W/") && if_range == etag
+    } else {
+        settled && if_range == last_modified
+    }
+}
+
+/// Parses a `Range: bytes=...` header.
+///
+/// Only a single range is supported, multiple ranges and malformed headers
+/// are ignored and the whole file is sent, as allowed by RFC 9110.
+fn parse_range(range: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = range.and_then(|range| range.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        // suffix range: the last `n` bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial(len.saturating_sub(n), len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) if start >= len => ByteRange::Unsatisfiable,
+            Ok(start) => ByteRange::Partial(start, len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start > end => ByteRange::Full,
+            (Ok(start), Ok(_)) if start >= len => ByteRange::Unsatisfiable,
+            (Ok(start), Ok(end)) => ByteRange::Partial(start, end.min(len - 1)),
+            _ => ByteRange::Full,
+        },
+    }
+}
+
+/// Only plain relative paths, nothing may escape the upload directory. The chunks of
+/// unfinished uploads, the files still being received and the bundle manifests are not files
+/// of their own.
+fn is_downloadable(path: &Path) -> bool {
+    path.components().enumerate().all(|(index, component)| {
+        let Component::Normal(name) = component else {
+            return false;
+        };
+        let name = name.to_string_lossy();
+        let partial = name.starts_with('.') && name.ends_with(".part");
+        !(index == 0 && name == "chunks") && !partial && name != BUNDLE_MANIFEST
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_downloadable() {
+        assert!(is_downloadable(Path::new("2026-10-19/photo.png")));
+        assert!(is_downloadable(Path::new("2026-10-19/project/chunks/.gitignore")));
+        assert!(!is_downloadable(Path::new("../secret")));
+        assert!(!is_downloadable(Path::new("/etc/passwd")));
+        assert!(!is_downloadable(Path::new(
+            "chunks/0b6f1a1e-4f0c-4a0b-9c59-3c5f7a8e9d10/data.part"
+        )));
+        assert!(!is_downloadable(Path::new("chunks")));
+        assert!(!is_downloadable(Path::new(
+            "2026-10-19/.0b6f1a1e-4f0c-4a0b-9c59-3c5f7a8e9d10.part"
+        )));
+        assert!(!is_downloadable(Path::new("2026-10-19/project/.bundle.json")));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), ByteRange::Partial(0, 9));
+        assert_eq!(parse_range(Some("bytes=90-"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=50-500"), 100), ByteRange::Partial(50, 99));
+        assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-9"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("W/\"a-b\"", "W/\"a-b\""));
+        assert!(etag_matches("\"x\", \"a-b\"", "W/\"a-b\""));
+        assert!(etag_matches("*", "W/\"a-b\""));
+        assert!(!etag_matches("\"a-c\"", "W/\"a-b\""));
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let date = "Mon, 19 Oct 2026 12:00:00 GMT";
+        assert!(if_range_matches("\"a-b\"", "\"a-b\"", date, true));
+        assert!(!if_range_matches("W/\"a-b\"", "W/\"a-b\"", date, true));
+        assert!(!if_range_matches("\"a-b\"", "W/\"a-b\"", date, true));
+        assert!(!if_range_matches("\"a-c\"", "\"a-b\"", date, true));
+        assert!(if_range_matches(date, "W/\"a-b\"", date, true));
+        assert!(!if_range_matches(date, "W/\"a-b\"", date, false));
+        assert!(!if_range_matches(
+            "Tue, 20 Oct 2026 12:00:00 GMT",
+            "W/\"a-b\"",
+            date,
+            true
+        ));
+    }
+}
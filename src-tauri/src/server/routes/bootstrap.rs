@@ -0,0 +1,61 @@
+use super::HttpResponse;
+use crate::{http_exception, server::exception::HttpException, utils::tls};
+use axum::{
+    extract::State,
+    http::{HeaderMap, Uri, header},
+    response::IntoResponse,
+};
+
+/// Certificate to trust before switching to HTTPS, the last one of the served chain
+pub async fn download_ca_cert() -> Result<impl IntoResponse, HttpException> {
+    let Some(cert_pem) = tls::ServerTls::global().pinned_cert_pem() else {
+        http_exception!(NotFoundException, Some("The server is not serving HTTPS."));
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-x509-ca-cert"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"synclan-ca.crt\""),
+        ],
+        cert_pem,
+    ))
+}
+
+/// Sends everything else to the same host and path over HTTPS, 307 keeps the method and body
+pub async fn redirect_to_https(
+    State(https_port): State<u16>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<HttpResponse<()>, HttpException> {
+    let Some(host) = headers.get(header::HOST).and_then(|host| host.to_str().ok()) else {
+        http_exception!(BadRequestException, Some("Missing Host header."));
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    Ok(HttpResponse::RedirectTo {
+        uri: format!("https://{}:{https_port}{path}", strip_port(host)),
+    })
+}
+
+/// `192.168.1.2:80` -> `192.168.1.2`, `[::1]:80` -> `[::1]`
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') && (host.starts_with('[') || !host[..colon].contains(':')) => {
+            &host[..colon]
+        },
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_port() {
+        assert_eq!(strip_port("192.168.1.2:8080"), "192.168.1.2");
+        assert_eq!(strip_port("synclan.local"), "synclan.local");
+        assert_eq!(strip_port("[fe80::1]:8080"), "[fe80::1]");
+        assert_eq!(strip_port("[fe80::1]"), "[fe80::1]");
+    }
+}
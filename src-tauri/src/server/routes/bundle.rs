@@ -0,0 +1,275 @@
+use super::{
+    AppState, HttpResponse,
+    upload::{effective_chunk_size, schedule_merge, session_dir, upload_limits},
+};
+use crate::{
+    config::Config,
+    server::{
+        api_doc::UPLOAD_TAG,
+        dtos::upload_dto::{BundleCompleteDto, BundleInitDto},
+        exception::HttpException,
+        extractors::Body,
+        guards::{CanUpload, Claims},
+        rate_limit::{Limit, RateLimiter},
+        routes::JsonResponse,
+        workers::upload::{self, BUNDLE_MANIFEST, BundleFile, BundleManifest, UploadMeta, UploadProgress},
+    },
+};
+use axum::{extract::State, middleware};
+use axum_macros::debug_handler;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::{Component, Path},
+    sync::Arc,
+};
+use tokio::fs;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
+    let router = OpenApiRouter::new()
+        .routes(routes!(init_bundle))
+        .routes(routes!(complete_bundle))
+        .route_layer(middleware::from_extractor::<CanUpload>());
+    OpenApiRouter::new().nest("/upload/bundle", router)
+}
+
+/// Initialize a folder upload.
+///
+/// Every file of the folder gets its own chunked upload session, the chunks are sent
+/// to `/upload/chunk` as usual. The folder structure is kept in the bundle manifest.
+#[utoipa::path(
+    post,
+    path = "/init",
+    request_body = BundleInitDto,
+    responses(
+        (status = OK, body = JsonResponse<BundleInitResponse>),
+        (status = BAD_REQUEST, description = "Invalid or duplicate file paths"),
+        (status = PAYLOAD_TOO_LARGE, description = "Storage quota exceeded or not enough disk space"),
+        (status = TOO_MANY_REQUESTS, description = "Upload rate limit exceeded")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = UPLOAD_TAG
+)]
+#[debug_handler]
+async fn init_bundle(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    Body(input): Body<BundleInitDto>,
+) -> Result<HttpResponse<BundleInitResponse>, HttpException> {
+    RateLimiter::global().acquire(&claims.device_id, Limit::Upload).await?;
+    let synclan = Config::synclan().await.data_arc();
+    let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
+        HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+    })?;
+
+    if !is_relative_path(&input.name) || input.name.contains('/') {
+        return Err(HttpException::BadRequestException(Some("Invalid folder name.".into())));
+    }
+    let mut seen = HashSet::new();
+    for file in &input.files {
+        if !is_relative_path(&file.path) || !seen.insert(file.path.as_str()) {
+            return Err(HttpException::BadRequestException(Some(format!(
+                "Invalid file path {}.",
+                file.path
+            ))));
+        }
+    }
+
+    //
+    // Every file reserves its size, the bundle is refused as a whole if one of them doesn't fit
+    //
+    let upload_sessions = &app_state.upload_sessions;
+    let limits = upload_limits().await?;
+    let mut upload_ids = Vec::with_capacity(input.files.len());
+    for file in &input.files {
+        let upload_id = uuid::Uuid::new_v4().to_string();
+        let reserved = upload_sessions
+            .try_reserve(&upload_id, &claims.device_id, file.size, &limits)
+            .await;
+        if let Err(err) = reserved {
+            upload_ids
+                .iter()
+                .for_each(|upload_id| upload_sessions.release(upload_id));
+            return Err(err);
+        }
+        upload_ids.push(upload_id);
+    }
+
+    let created = create_bundle(upload_dir, &claims.device_id, input, upload_ids.clone()).await;
+    if created.is_err() {
+        upload_ids
+            .iter()
+            .for_each(|upload_id| upload_sessions.release(upload_id));
+    }
+
+    Ok(HttpResponse::Json {
+        payload: created?,
+        message: None,
+    })
+}
+
+/// Creates the bundle folder, the session of every file and the manifest
+async fn create_bundle(
+    upload_dir: &str,
+    device_id: &str,
+    input: BundleInitDto,
+    upload_ids: Vec<String>,
+) -> Result<BundleInitResponse, HttpException> {
+    //
+    // {today}/{name}, a folder of the same name gets a numbered suffix
+    //
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut folder = format!("{today}/{}", input.name);
+    let mut suffix = 1;
+    while fs::try_exists(Path::new(upload_dir).join(&folder)).await? {
+        folder = format!("{today}/{} ({suffix})", input.name);
+        suffix += 1;
+    }
+    let folder_dir = Path::new(upload_dir).join(&folder);
+    fs::create_dir_all(&folder_dir).await?;
+
+    let bundle_id = uuid::Uuid::new_v4().to_string();
+    let chunk_size = effective_chunk_size(input.chunk_size);
+    let mut files = Vec::with_capacity(input.files.len());
+    let mut sessions = Vec::with_capacity(input.files.len());
+
+    for (file, upload_id) in input.files.into_iter().zip(upload_ids) {
+        let total_chunks = file.size.div_ceil(chunk_size) as u32;
+        let chunk_dir = upload::chunk_dir(upload_dir, &upload_id);
+        fs::create_dir_all(&chunk_dir).await?;
+
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path).to_string();
+        UploadMeta {
+            upload_id: upload_id.clone(),
+            name,
+            size: file.size,
+            chunk_size,
+            total_chunks,
+            merged_chunks: 0,
+            device_id: device_id.to_string(),
+            target: Some(format!("{folder}/{}", file.path)),
+        }
+        .save(&chunk_dir)
+        .await?;
+
+        sessions.push(BundleFileSession {
+            path: file.path.clone(),
+            upload_id: upload_id.clone(),
+            chunk_size,
+            total_chunks,
+        });
+        files.push(BundleFile {
+            path: file.path,
+            size: file.size,
+            upload_id,
+        });
+    }
+
+    let manifest = BundleManifest {
+        bundle_id: bundle_id.clone(),
+        name: folder.rsplit('/').next().unwrap_or(&input.name).to_string(),
+        path: folder.clone(),
+        device_id: device_id.to_string(),
+        files,
+    };
+    // one copy to find the sessions on completion, one for the receivers
+    let bundle_dir = upload::chunk_dir(upload_dir, &bundle_id);
+    fs::create_dir_all(&bundle_dir).await?;
+    manifest.save(&bundle_dir).await?;
+    manifest.save(&folder_dir).await?;
+
+    Ok(BundleInitResponse {
+        bundle_id,
+        path: folder,
+        files: sessions,
+    })
+}
+
+/// Complete a folder upload.
+///
+/// Schedules the merge of every file, the progress of each file is emitted as
+/// `synclan://upload:progress`. Once all of them are completed, the folder path
+/// can be sent as a `folder` message.
+#[utoipa::path(
+    post,
+    path = "/complete",
+    request_body = BundleCompleteDto,
+    responses(
+        (status = OK, body = JsonResponse<Vec<UploadProgress>>),
+        (status = BAD_REQUEST, description = "Chunks are missing"),
+        (status = NOT_FOUND, description = "Bundle not found, or started by another device")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = UPLOAD_TAG
+)]
+#[debug_handler]
+async fn complete_bundle(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    Body(input): Body<BundleCompleteDto>,
+) -> Result<HttpResponse<Vec<UploadProgress>>, HttpException> {
+    let synclan = Config::synclan().await.data_arc();
+    let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
+        HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+    })?;
+
+    // another device's bundle does not exist for the caller
+    let bundle_dir = session_dir(upload_dir, &input.bundle_id)?;
+    let manifest = BundleManifest::load(&bundle_dir)
+        .await
+        .ok()
+        .filter(|manifest| manifest.device_id == claims.device_id)
+        .ok_or_else(|| HttpException::NotFoundException(Some("Bundle not found.".into())))?;
+
+    let mut progress = Vec::with_capacity(manifest.files.len());
+    for file in &manifest.files {
+        progress.push(schedule_merge(&app_state, &file.upload_id, &claims.device_id).await?);
+    }
+
+    fs::remove_dir_all(&bundle_dir).await?;
+
+    Ok(HttpResponse::Json {
+        payload: progress,
+        message: Some("Bundle merge scheduled.".into()),
+    })
+}
+
+/// Only plain relative paths, nothing may escape the bundle folder
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(name) if name.to_str() != Some(BUNDLE_MANIFEST)))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleInitResponse {
+    pub bundle_id: String,
+
+    /// Folder path relative to the file upload directory, the content of the `folder` message
+    pub path: String,
+
+    pub files: Vec<BundleFileSession>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BundleFileSession {
+    /// Path relative to the folder
+    pub path: String,
+
+    /// Chunk upload session of the file
+    pub upload_id: String,
+
+    pub chunk_size: u64,
+
+    pub total_chunks: u32,
+}
@@ -1,13 +1,21 @@
 use super::{AppState, HttpResponse, JsonResponse};
 use crate::{
-    json_response,
-    module::device::{Device, DevicePatch},
+    config::Config,
+    http_exception, json_response,
+    module::{
+        attachment::Attachment,
+        audit_log::AuditAction,
+        device::{BlockedDevice, Device, DevicePatch, DeviceRole, RegisteredDevice},
+    },
     server::{
-        api_doc,
+        api_doc, audit,
+        discovery::{DiscoveredHost, Discovery},
         dtos::device_dto::{DiscoverDeviceDto, RegistorDeviceDto, UpdateDeviceDto},
         exception::HttpException,
-        extractors::{Body, Query},
-        guards::Claims,
+        extractors::{Body, ClientInfo, Query},
+        guards::{CanListDevices, Claims},
+        pairing::PairingGrants,
+        rate_limit::{Limit, RateLimiter},
     },
 };
 use axum::extract::Path;
@@ -20,6 +28,7 @@ pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
         .routes(routes!(get_by_id))
         .routes(routes!(get_all))
         .routes(routes!(discover_all))
+        .routes(routes!(discover_hosts))
         .routes(routes!(update_one));
     OpenApiRouter::new().nest("/devices", router)
 }
@@ -32,30 +41,79 @@ pub fn public_route() -> OpenApiRouter<Arc<AppState>> {
 /// Registor new Device
 ///
 /// Tries to registor a new Device or fails with 409 conflict if already exists.
+/// With authorized access enabled, the access code or a grant from the pairing QR code is required.
+/// The response carries the bearer token of the device, it is not returned again.
 #[utoipa::path(
   post,
   path = "",
   request_body = RegistorDeviceDto,
   responses(
-    (status = 200, description = "Device created successfully", body = JsonResponse<Device>),
+    (status = 200, description = "Device created successfully", body = JsonResponse<RegisteredDevice>),
+    (status = 401, description = "Neither a valid access code nor a valid grant"),
+    (status = 403, description = "The device has been blocked"),
     (status = 409, description = "Device already exists"),
+    (status = 429, description = "Too many failed attempts from this address"),
   ),
   tag = api_doc::DEVICE_TAG
 )]
 #[debug_handler]
-pub(crate) async fn create_one(Body(input): Body<RegistorDeviceDto>) -> Result<HttpResponse<Device>, HttpException> {
+pub(crate) async fn create_one(
+    client: ClientInfo,
+    Body(input): Body<RegistorDeviceDto>,
+) -> Result<HttpResponse<RegisteredDevice>, HttpException> {
+    audit::check_failures(&client).await?;
+    let device_id = input.id.clone();
+    let result = register(input).await;
+    audit::record_result(AuditAction::Register, Some(&device_id), &client, &result, None).await;
+    result
+}
+
+async fn register(input: RegistorDeviceDto) -> Result<HttpResponse<RegisteredDevice>, HttpException> {
+    // checked first, a blocked device must not use up a grant
+    if BlockedDevice::is_blocked(&input.id, input.fingerprint_id.as_deref()).await? {
+        http_exception!(ForbiddenException, Some("This device has been blocked."));
+    }
+
+    // checked before the grant too, the client retries with a new id and the same grant
+    if Device::get_by_id(&input.id).await?.is_some() {
+        http_exception!(ConflictException, Some("Device already exists."));
+    }
+
+    let synclan = Config::synclan().await.data_arc();
+    // the grant is used up even if authorized access is off, a QR code registers one device
+    let granted = input
+        .grant
+        .as_deref()
+        .is_some_and(|grant| PairingGrants::global().redeem(grant));
+    if synclan.enable_authorized_access.unwrap_or(false) && !granted {
+        let authorized = synclan
+            .authorized_access_code
+            .as_deref()
+            .is_some_and(|code| input.access_code.as_deref() == Some(code));
+        if !authorized {
+            http_exception!(UnauthorizedException, Some("Invalid access code or grant."));
+        }
+    }
+
+    if let Some(avatar) = input.avatar.as_deref() {
+        ensure_avatar(&input.id, avatar).await?;
+    }
+
     let device = Device {
         id: input.id,
         name: input.name,
         avatar: input.avatar,
         fingerprint_id: input.fingerprint_id,
-        role: input.role,
+        // only the app registers the host device, over its command instead of this route
+        role: DeviceRole::Client,
         platform: input.platform,
         browser: input.browser,
         ..Device::default()
     };
-    let new_device = device.register().await?;
-    json_response!(new_device);
+    let Some(registered) = device.register_with_token().await? else {
+        http_exception!(ConflictException, Some("Device already exists."));
+    };
+    json_response!(registered);
 }
 
 /// Query Device by id
@@ -90,6 +148,7 @@ pub(crate) async fn get_by_id(Path(id): Path<String>) -> Result<HttpResponse<Opt
   path = "",
   responses(
     (status = 200, description = "Query all Device details successfully", body = JsonResponse<Vec<Device>>),
+    (status = 403, description = "The role of the device may not list devices"),
   ),
   security(
     ("bearer_auth" = [])
@@ -97,7 +156,7 @@ pub(crate) async fn get_by_id(Path(id): Path<String>) -> Result<HttpResponse<Opt
 	tag = api_doc::DEVICE_TAG
 )]
 #[debug_handler]
-pub(crate) async fn get_all(claims: Claims) -> Result<HttpResponse<Vec<Device>>, HttpException> {
+pub(crate) async fn get_all(_: CanListDevices, claims: Claims) -> Result<HttpResponse<Vec<Device>>, HttpException> {
     let devices = Device::get_all(Some(&claims.device_id)).await?;
     json_response!(devices);
 }
@@ -113,6 +172,8 @@ pub(crate) async fn get_all(claims: Claims) -> Result<HttpResponse<Vec<Device>>,
   ),
   responses(
     (status = 200, description = "Discover Device details successfully", body = JsonResponse<Vec<Device>>),
+    (status = 403, description = "The role of the device may not list devices"),
+    (status = 429, description = "Discovery rate limit exceeded"),
   ),
   security(
     ("bearer_auth" = [])
@@ -121,16 +182,50 @@ pub(crate) async fn get_all(claims: Claims) -> Result<HttpResponse<Vec<Device>>,
 )]
 #[debug_handler]
 pub(crate) async fn discover_all(
+    _: CanListDevices,
+    claims: Claims,
     Query(dto): Query<DiscoverDeviceDto>,
 ) -> Result<HttpResponse<Vec<Device>>, HttpException> {
+    RateLimiter::global()
+        .acquire(&claims.device_id, Limit::Discovery)
+        .await?;
     let exclude_ids = dto.ids.unwrap_or_default();
     let devices = Device::get_not_in(&exclude_ids).await?;
     json_response!(devices);
 }
 
+/// Discover Hosts
+///
+/// Other Synclan hosts announcing themselves on the local network.
+#[utoipa::path(
+  get,
+  path = "/discover/hosts",
+  responses(
+    (status = 200, description = "Discover hosts successfully", body = JsonResponse<Vec<DiscoveredHost>>),
+    (status = 403, description = "The role of the device may not list devices"),
+    (status = 429, description = "Discovery rate limit exceeded"),
+  ),
+  security(
+    ("bearer_auth" = [])
+  ),
+	tag = api_doc::DEVICE_TAG
+)]
+#[debug_handler]
+pub(crate) async fn discover_hosts(
+    _: CanListDevices,
+    claims: Claims,
+) -> Result<HttpResponse<Vec<DiscoveredHost>>, HttpException> {
+    RateLimiter::global()
+        .acquire(&claims.device_id, Limit::Discovery)
+        .await?;
+    let hosts = Discovery::global().hosts();
+    json_response!(hosts);
+}
+
 /// Update Device
 ///
 /// Update device profile information.
+/// A device may only update itself, the host may update every device.
 #[utoipa::path(
   patch,
   path = "/{id}",
@@ -141,6 +236,14 @@ pub(crate) async fn discover_all(
         description = "Device updated successfully",
         body = JsonResponse<Device>
     ),
+    (
+        status = 400,
+        description = "The avatar is not an image uploaded by this device"
+    ),
+    (
+        status = 403,
+        description = "Not allowed to update another device"
+    ),
     (
         status = 404,
         description = "Device not found"
@@ -156,9 +259,17 @@ pub(crate) async fn discover_all(
 )]
 #[debug_handler]
 pub(crate) async fn update_one(
+    claims: Claims,
     Path(id): Path<String>,
     Body(input): Body<UpdateDeviceDto>,
 ) -> Result<HttpResponse<Option<Device>>, HttpException> {
+    if !claims.can_manage(&id) {
+        http_exception!(ForbiddenException, Some("Not allowed to update another device."));
+    }
+    if let Some(avatar) = input.avatar.as_deref() {
+        ensure_avatar(&id, avatar).await?;
+    }
+
     #[allow(clippy::needless_update)]
     let patch = DevicePatch {
         id: id.clone(),
@@ -171,3 +282,117 @@ pub(crate) async fn update_one(
     let device = Device::get_by_id(&id).await?;
     json_response!(device);
 }
+
+/// An avatar is served without credentials, so it must be an image the device uploaded as permanent
+async fn ensure_avatar(device_id: &str, avatar: &str) -> Result<(), HttpException> {
+    let attachment = Attachment::get_by_path(avatar).await?;
+    let valid = attachment.is_some_and(|attachment| {
+        attachment.path.starts_with("assets/")
+            && attachment.device_id == device_id
+            && attachment.mime.is_some_and(|mime| mime.starts_with("image/"))
+    });
+    if !valid {
+        http_exception!(
+            BadRequestException,
+            Some("The avatar must be an image uploaded by this device.")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{module::device_access::AccessRole, utils::db::DBManager};
+    use axum::{extract::FromRequestParts, http::Request};
+
+    async fn device(id: &str, role: DeviceRole) -> Claims {
+        let device = Device {
+            id: id.to_string(),
+            name: id.to_string(),
+            role,
+            ..Device::default()
+        };
+        device.register().await.unwrap();
+        let access = match role {
+            DeviceRole::Host => AccessRole::Admin,
+            _ => AccessRole::Member,
+        };
+        Claims::new(device.id, device.role, access)
+    }
+
+    fn rename(name: &str) -> Body<UpdateDeviceDto> {
+        Body(UpdateDeviceDto {
+            name: Some(name.to_string()),
+            avatar: None,
+        })
+    }
+
+    fn payload<T>(response: HttpResponse<T>) -> T {
+        match response {
+            HttpResponse::Json { payload, .. } => payload,
+            HttpResponse::RedirectTo { .. } => panic!("unexpected redirect"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_taken_id() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let request = |id: &str| RegistorDeviceDto {
+            id: id.to_string(),
+            name: "Mallory".to_string(),
+            avatar: None,
+            fingerprint_id: None,
+            platform: None,
+            browser: None,
+            grant: Some(PairingGrants::global().issue().unwrap().0),
+            access_code: None,
+        };
+
+        // a client from before tokens has none, knowing its id must not get one
+        device("devices-legacy", DeviceRole::Client).await;
+        let result = register(request("devices-legacy")).await;
+        assert!(matches!(result, Err(HttpException::ConflictException(_))));
+
+        let registered = payload(register(request("devices-new")).await.unwrap());
+        assert_eq!(registered.device.id, "devices-new");
+        let result = register(request("devices-new")).await;
+        assert!(matches!(result, Err(HttpException::ConflictException(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_one() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let alice = device("devices-alice", DeviceRole::Client).await;
+        let bob = device("devices-bob", DeviceRole::Client).await;
+        let host = device("devices-host", DeviceRole::Host).await;
+
+        // a client can't rename another device, but itself
+        let result = update_one(alice.clone(), Path(bob.device_id.clone()), rename("Mallory")).await;
+        assert!(matches!(result, Err(HttpException::ForbiddenException(_))));
+        let stored = Device::get_by_id(&bob.device_id).await.unwrap().unwrap();
+        assert_eq!(stored.name, bob.device_id);
+
+        let renamed = update_one(alice.clone(), Path(alice.device_id.clone()), rename("Alice")).await;
+        assert_eq!(payload(renamed.unwrap()).unwrap().name, "Alice");
+
+        // the host manages every device
+        let renamed = update_one(host, Path(bob.device_id.clone()), rename("Bob")).await;
+        assert_eq!(payload(renamed.unwrap()).unwrap().name, "Bob");
+    }
+
+    #[tokio::test]
+    async fn test_can_list_devices() {
+        let can_list = |access| async move {
+            let (mut parts, _) = Request::new(()).into_parts();
+            parts
+                .extensions
+                .insert(Claims::new("devices-lister".into(), DeviceRole::Client, access));
+            CanListDevices::from_request_parts(&mut parts, &()).await.is_ok()
+        };
+
+        assert!(can_list(AccessRole::Member).await);
+        assert!(can_list(AccessRole::Guest).await);
+        assert!(!can_list(AccessRole::ReadOnly).await);
+    }
+}
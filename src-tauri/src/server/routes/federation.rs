@@ -0,0 +1,171 @@
+use super::{AppState, EmptyPayload, HttpResponse};
+use crate::{
+    config::Config,
+    http_exception, json_response, logging, logging_error,
+    module::{device::Device, message::Message},
+    server::{
+        api_doc::FEDERATION_TAG,
+        exception::HttpException,
+        extractors::Body,
+        federation::{self, ConfirmRequest, ForwardedMessage, PairRequest, PairResponse},
+        guards::PeerGuard,
+        routes::JsonResponse,
+    },
+    utils::{
+        logging::Type,
+        tls::{self, PinMigration},
+    },
+};
+use apalis::prelude::TaskSink as _;
+use axum::extract::State;
+use axum_macros::debug_handler;
+use std::sync::Arc;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+/// Requests between hosts, they authenticate with the secret shared at pairing instead of a device id
+pub fn public_route() -> OpenApiRouter<Arc<AppState>> {
+    let router = OpenApiRouter::new()
+        .routes(routes!(pair))
+        .routes(routes!(confirm))
+        .routes(routes!(get_pin_migration))
+        .routes(routes!(get_devices))
+        .routes(routes!(receive_message));
+    OpenApiRouter::new().nest("/federation", router)
+}
+
+/// Pair with this host
+///
+/// Succeeds only if this host trusts the sender's certificate fingerprint,
+/// which is verified by calling the sender back at `/federation/confirm`.
+#[utoipa::path(
+    post,
+    path = "/pair",
+    request_body = PairRequest,
+    responses(
+        (status = OK, description = "Paired", body = JsonResponse<PairResponse>),
+        (status = FORBIDDEN, description = "This host does not trust the sender yet")
+    ),
+    tag = FEDERATION_TAG
+)]
+#[debug_handler]
+async fn pair(Body(input): Body<PairRequest>) -> Result<HttpResponse<PairResponse>, HttpException> {
+    let response = federation::accept_pairing(input)
+        .await
+        .map_err(|err| HttpException::BadGatewayException(Some(err.to_string())))?
+        .ok_or_else(|| HttpException::ForbiddenException(Some("This host does not trust the sender yet.".into())))?;
+
+    json_response!(response);
+}
+
+/// Confirm a pairing
+///
+/// Called back by the host this host is pairing with, proves that this host holds the secret.
+#[utoipa::path(
+    post,
+    path = "/confirm",
+    request_body = ConfirmRequest,
+    responses(
+        (status = OK, description = "The pairing was started by this host", body = JsonResponse<EmptyPayload>),
+        (status = FORBIDDEN, description = "No pairing with this secret")
+    ),
+    tag = FEDERATION_TAG
+)]
+#[debug_handler]
+async fn confirm(Body(input): Body<ConfirmRequest>) -> Result<HttpResponse<()>, HttpException> {
+    if !federation::confirm_pairing(&input.secret).await? {
+        http_exception!(ForbiddenException);
+    }
+
+    json_response!(());
+}
+
+/// Certificate pin migration
+///
+/// Hosts paired before the local CA pinned a self-signed certificate of this host,
+/// the proof signed with its key lets them pin the CA instead.
+#[utoipa::path(
+    get,
+    path = "/pin",
+    responses(
+        (status = OK, description = "The CA replaced a self-signed certificate", body = JsonResponse<PinMigration>),
+        (status = NOT_FOUND, description = "No certificate was replaced")
+    ),
+    tag = FEDERATION_TAG
+)]
+#[debug_handler]
+async fn get_pin_migration() -> Result<HttpResponse<PinMigration>, HttpException> {
+    let synclan = Config::synclan().await.data_arc();
+    let Some(migration) = tls::pin_migration(&synclan) else {
+        http_exception!(NotFoundException);
+    };
+
+    json_response!(migration);
+}
+
+/// Devices of this host
+///
+/// Paired hosts list them next to their own devices.
+#[utoipa::path(
+    get,
+    path = "/devices",
+    responses(
+        (status = OK, description = "Devices registered with this host", body = JsonResponse<Vec<Device>>),
+        (status = UNAUTHORIZED, description = "Not a paired host")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = FEDERATION_TAG
+)]
+#[debug_handler]
+async fn get_devices(_peer: PeerGuard) -> Result<HttpResponse<Vec<Device>>, HttpException> {
+    let devices = Device::get_local().await?;
+    json_response!(devices);
+}
+
+/// Relay a message
+///
+/// A paired host hands over a message for a device of this host, which delivers it.
+#[utoipa::path(
+    post,
+    path = "/messages",
+    request_body = ForwardedMessage,
+    responses(
+        (status = OK, description = "The message is queued for delivery", body = JsonResponse<EmptyPayload>),
+        (status = UNAUTHORIZED, description = "Not a paired host"),
+        (status = FORBIDDEN, description = "The sender is not a device of the paired host"),
+        (status = NOT_FOUND, description = "The receiver is not a device of this host")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = FEDERATION_TAG
+)]
+#[debug_handler]
+async fn receive_message(
+    State(app_state): State<Arc<AppState>>,
+    PeerGuard(peer): PeerGuard,
+    Body(input): Body<ForwardedMessage>,
+) -> Result<HttpResponse<()>, HttpException> {
+    let ForwardedMessage { sender, message } = input;
+    let peer_id = peer.id.unwrap_or_default();
+
+    if sender.id != message.sender || federation::upsert_remote(&peer_id, sender).await?.is_none() {
+        http_exception!(ForbiddenException);
+    }
+    // messages are never relayed twice, a loop between hosts is impossible
+    let receiver = Device::get_by_id(&message.receiver).await?;
+    if !receiver.is_some_and(|receiver| receiver.home_host.is_none()) {
+        http_exception!(NotFoundException);
+    }
+
+    // a retried forward may arrive twice
+    if !Message::exists(&message.uuid).await? {
+        let message = Message { id: None, ..message }.create().await?;
+        let mut storage = app_state.message_storage.clone();
+        logging_error!(Type::Server, storage.push(message).await);
+        logging!(debug, Type::Server, "Relayed a message from {}", peer.alias);
+    }
+
+    json_response!(());
+}
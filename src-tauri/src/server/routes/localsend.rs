@@ -0,0 +1,304 @@
+use super::{
+    AppState,
+    upload::{ensure_mime_allowed, upload_limits},
+};
+use crate::{
+    config::Config,
+    http_exception, logging, logging_error,
+    module::{
+        attachment::Attachment,
+        audit_log::AuditAction,
+        device::{BlockedDevice, Device, DeviceRole},
+        message::{Message, MessageType},
+    },
+    server::{
+        audit,
+        dtos::localsend_dto::{
+            LocalSendDeviceDto, LocalSendPinDto, LocalSendPrepareUploadDto, LocalSendSessionDto, LocalSendUploadDto,
+        },
+        exception::HttpException,
+        extractors::{Body, ClientInfo, Query},
+        localsend::{self, LocalSendFile, LocalSendInfo},
+        workers::{
+            ThumbnailJob,
+            upload::{claim_file_name, is_valid_file_name},
+        },
+    },
+    utils::{logging::Type, sniff},
+};
+use apalis::prelude::TaskSink as _;
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt as _;
+use serde::Serialize;
+use std::{collections::HashMap, path::Path, sync::Arc};
+use tokio::{fs, io::AsyncWriteExt as _};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PrepareUploadResponse {
+    session_id: String,
+    /// `file_id -> token`
+    files: HashMap<String, String>,
+}
+
+/// `GET /info`, lets LocalSend apps identify this device
+pub(crate) async fn info() -> Result<Json<LocalSendInfo>, HttpException> {
+    Ok(Json(LocalSendInfo::host().await?))
+}
+
+/// `POST /register`, a LocalSend app announces itself and gets this device's info back
+pub(crate) async fn register(Body(input): Body<LocalSendDeviceDto>) -> Result<Json<LocalSendInfo>, HttpException> {
+    // without a PIN the app is only registered by its first upload
+    match register_device(&input, None).await {
+        Ok(_) | Err(HttpException::UnauthorizedException(_)) => {},
+        Err(err) => return Err(err),
+    }
+    Ok(Json(LocalSendInfo::host().await?))
+}
+
+/// `POST /prepare-upload`, incoming files are accepted right away
+/// as long as they fit in the upload quotas.
+///
+/// With authorized access the access code has to be entered as PIN in the LocalSend app.
+pub(crate) async fn prepare_upload(
+    State(app_state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Query(query): Query<LocalSendPinDto>,
+    Body(input): Body<LocalSendPrepareUploadDto>,
+) -> Result<Response, HttpException> {
+    audit::check_failures(&client).await?;
+    let sender = register_device(&input.info, query.pin.as_deref()).await;
+    // a wrong PIN is a wrong access code, recorded and limited like one
+    if let Err(HttpException::UnauthorizedException(_)) = &sender {
+        let device_id = localsend::device_id(&input.info.fingerprint);
+        audit::record_result(AuditAction::Register, Some(&device_id), &client, &sender, None).await;
+    }
+    let sender = sender?;
+    if input.files.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let mut files = Vec::with_capacity(input.files.len());
+    for (file_id, file) in input.files {
+        // folders are sent as `dir/file`, only the file itself is kept
+        let file_name = file.file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+        if !is_valid_file_name(file_name) {
+            return Err(HttpException::BadRequestException(Some(format!(
+                "Invalid file name {}.",
+                file.file_name
+            ))));
+        }
+        files.push((file_id, file_name.to_string(), file.size));
+    }
+
+    let total_size = files.iter().map(|(_, _, size)| size).sum();
+    app_state
+        .upload_sessions
+        .check(&sender.id, total_size, &upload_limits().await?)
+        .await?;
+
+    let (session_id, files) = app_state
+        .localsend
+        .start(&sender.id, files)
+        .map_err(|err| HttpException::ConflictException(Some(err.to_string())))?;
+
+    logging!(
+        info,
+        Type::Server,
+        "LocalSend session {session_id} from {} ({} files)",
+        sender.name,
+        files.len()
+    );
+
+    Ok(Json(PrepareUploadResponse { session_id, files }).into_response())
+}
+
+/// `POST /upload`, stores the file and delivers it as a `File` message from the LocalSend device
+pub(crate) async fn upload(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LocalSendUploadDto>,
+    body: axum::body::Body,
+) -> Result<StatusCode, HttpException> {
+    let sessions = &app_state.localsend;
+    let (sender, file) = sessions
+        .claim(&query.session_id, &query.file_id, &query.token)
+        .ok_or_else(|| HttpException::ForbiddenException(Some("Invalid token.".into())))?;
+
+    // the file counts against the quotas while it is received
+    let reservation_id = format!("localsend/{}/{}", query.session_id, query.file_id);
+    app_state
+        .upload_sessions
+        .try_reserve(&reservation_id, &sender, file.size, &upload_limits().await?)
+        .await?;
+    let result = store_file(&app_state, &query, sender, file, body).await;
+    app_state.upload_sessions.release(&reservation_id);
+    result
+}
+
+async fn store_file(
+    app_state: &AppState,
+    query: &LocalSendUploadDto,
+    sender: String,
+    file: LocalSendFile,
+    body: axum::body::Body,
+) -> Result<StatusCode, HttpException> {
+    let sessions = &app_state.localsend;
+    let synclan = Config::synclan().await.data_arc();
+    let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
+        HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+    })?;
+    let host = Device::get_host_device("")
+        .await?
+        .ok_or_else(|| HttpException::ServiceUnavailableException(Some("Host device not found.".into())))?;
+
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let dir = Path::new(upload_dir).join(&today);
+    fs::create_dir_all(&dir).await?;
+    let tmp_path = dir.join(format!(".{}-{}.part", query.session_id, query.file_id));
+
+    //
+    // Stream the body to disk, the session may be cancelled in between
+    //
+    let received = async {
+        let mut output = fs::File::create(&tmp_path).await?;
+        let mut stream = body.into_data_stream();
+        let mut written = 0u64;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|err| HttpException::BadRequestException(Some(err.to_string())))?;
+            written += chunk.len() as u64;
+            if written > file.size {
+                return Err(HttpException::BadRequestException(Some(
+                    "More bytes than announced.".into(),
+                )));
+            }
+            if !sessions.is_active(&query.session_id) {
+                return Err(HttpException::GoneException(Some("Session cancelled.".into())));
+            }
+            output.write_all(&chunk).await?;
+        }
+        output.flush().await?;
+        if written != file.size {
+            return Err(HttpException::BadRequestException(Some(
+                "Fewer bytes than announced.".into(),
+            )));
+        }
+
+        let mime = sniff::sniff_file(&tmp_path, &file.file_name).await?;
+        ensure_mime_allowed(&mime).await?;
+        Ok((written, mime))
+    }
+    .await;
+    let (written, mime) = match received {
+        Ok(received) => received,
+        Err(err) => {
+            fs::remove_file(&tmp_path).await.ok();
+            return Err(err);
+        },
+    };
+
+    // an existing file of the same name is kept, this one gets a numbered suffix
+    let (file_name, path) = match claim_file_name(&dir, &file.file_name).await {
+        Ok(claimed) => claimed,
+        Err(err) => {
+            fs::remove_file(&tmp_path).await.ok();
+            return Err(err.into());
+        },
+    };
+    fs::rename(&tmp_path, &path).await?;
+    let relative_path = format!("{today}/{file_name}");
+    Attachment {
+        path: relative_path.clone(),
+        name: file.file_name.clone(),
+        size: written as i64,
+        device_id: sender.clone(),
+        mime: Some(mime.clone()),
+        ..Attachment::default()
+    }
+    .create()
+    .await?;
+
+    if let Some(job) = ThumbnailJob::for_file(&relative_path, &mime) {
+        let mut storage = app_state.thumbnail_storage.clone();
+        logging_error!(Type::Server, storage.push(job).await);
+    }
+
+    let message = Message {
+        id: None,
+        uuid: uuid::Uuid::new_v4().to_string(),
+        sender,
+        receiver: host.id,
+        r#type: MessageType::File,
+        content: Some(relative_path),
+        plain_content: Some("[File]".into()),
+        extra: Some(
+            serde_json::json!({
+                "name": file.file_name,
+                "size": written,
+                "mimeType": mime,
+            })
+            .to_string(),
+        ),
+        created_at: None,
+        updated_at: None,
+        attachment_urls: None,
+        thumbnail_urls: None,
+    }
+    .create()
+    .await?;
+    let mut storage = app_state.message_storage.clone();
+    logging_error!(Type::Server, storage.push(message).await);
+
+    sessions.complete(&query.session_id, &query.file_id);
+
+    Ok(StatusCode::OK)
+}
+
+/// `POST /cancel`
+pub(crate) async fn cancel(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LocalSendSessionDto>,
+) -> StatusCode {
+    if app_state.localsend.cancel(&query.session_id) {
+        logging!(info, Type::Server, "LocalSend session {} cancelled", query.session_id);
+    }
+    StatusCode::OK
+}
+
+/// The LocalSend app shows up as a device of its own, keyed by its fingerprint.
+/// It can't authenticate as a device, see [`Device::can_authenticate`].
+async fn register_device(info: &LocalSendDeviceDto, pin: Option<&str>) -> Result<Device, HttpException> {
+    let id = localsend::device_id(&info.fingerprint);
+    if BlockedDevice::is_blocked(&id, Some(&info.fingerprint)).await? {
+        http_exception!(ForbiddenException, Some("This device has been blocked."));
+    }
+
+    let synclan = Config::synclan().await.data_arc();
+    if synclan.enable_authorized_access.unwrap_or(false) {
+        let authorized = synclan
+            .authorized_access_code
+            .as_deref()
+            .is_some_and(|code| pin == Some(code));
+        if !authorized {
+            http_exception!(UnauthorizedException, Some("Invalid PIN."));
+        }
+    }
+
+    let device = Device {
+        id,
+        name: info.alias.clone(),
+        fingerprint_id: Some(info.fingerprint.clone()),
+        role: DeviceRole::LocalSend,
+        platform: info.device_model.clone().or_else(|| info.device_type.clone()),
+        browser: Some("LocalSend".into()),
+        ..Device::default()
+    }
+    .upsert()
+    .await?;
+
+    Ok(device)
+}
@@ -1,14 +1,19 @@
 use super::AppState;
 use crate::{
     json_response,
-    module::message::{CursorPaginatedMessages, Message, MessageAck, OfflineMessagesInfoMap, PaginatedMessages},
+    module::{
+        audit_log::AuditAction,
+        message::{CursorPaginatedMessages, Message, MessageAck, OfflineMessagesInfoMap, PaginatedMessages},
+    },
     server::{
         api_doc::MESSAGE_TAG,
+        audit,
         dtos::message_dto::{CursorPagination, DeleteMessagesDto, UpdateAckDto},
         exception::HttpException,
-        extractors::{Body, Query},
-        guards::Claims,
+        extractors::{Body, ClientInfo, Query},
+        guards::{CanDelete, Claims},
         routes::{HttpResponse, JsonResponse},
+        signed_url::UrlSigner,
     },
 };
 use axum::extract::Path;
@@ -27,7 +32,7 @@ pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
 
 /// Get message record list.
 ///
-/// Get message list by paging.
+/// Get the conversation of the current device with `targetId` by paging.
 #[utoipa::path(
   get,
   path = "",
@@ -45,16 +50,18 @@ pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
   tag = MESSAGE_TAG
 )]
 #[debug_handler]
-async fn get_messages(
+pub(crate) async fn get_messages(
+    claims: Claims,
     Query(pagination): Query<CursorPagination>,
 ) -> Result<HttpResponse<CursorPaginatedMessages>, HttpException> {
-    let data = Message::get_messages(
-        &pagination.self_id,
+    let mut data = Message::get_messages(
+        &claims.device_id,
         &pagination.target_id,
         pagination.last_id,
         pagination.page_size,
     )
     .await?;
+    UrlSigner::global().sign_messages(&mut data.messages).await?;
     json_response!(data);
 }
 
@@ -74,7 +81,8 @@ async fn get_messages(
 )]
 #[debug_handler]
 async fn get_offline_messages(claims: Claims) -> Result<HttpResponse<Vec<Message>>, HttpException> {
-    let messages = Message::get_offline_messages(&claims.device_id).await?;
+    let mut messages = Message::get_offline_messages(&claims.device_id).await?;
+    UrlSigner::global().sign_messages(&mut messages).await?;
 
     json_response!(messages);
 }
@@ -97,7 +105,12 @@ async fn get_offline_messages(claims: Claims) -> Result<HttpResponse<Vec<Message
 async fn get_offline_messages_summary(
     claims: Claims,
 ) -> Result<HttpResponse<Option<OfflineMessagesInfoMap>>, HttpException> {
-    let messages = Message::get_offline_msgs_summary(&claims.device_id).await?;
+    let mut messages = Message::get_offline_msgs_summary(&claims.device_id).await?;
+    if let Some(summary) = messages.as_mut() {
+        UrlSigner::global()
+            .sign_messages(summary.values_mut().map(|group| &mut group.last_msg))
+            .await?;
+    }
     json_response!(messages);
 }
 
@@ -117,8 +130,11 @@ async fn get_offline_messages_summary(
   tag = MESSAGE_TAG
 )]
 #[debug_handler]
-async fn update_ack(Body(input): Body<UpdateAckDto>) -> Result<HttpResponse<()>, HttpException> {
-    let ack_record = MessageAck::new(input.receiver, Some(input.last_ack));
+pub(crate) async fn update_ack(
+    claims: Claims,
+    Body(input): Body<UpdateAckDto>,
+) -> Result<HttpResponse<()>, HttpException> {
+    let ack_record = MessageAck::new(claims.device_id, Some(input.last_ack));
     ack_record.received().await?;
     json_response!(());
 }
@@ -135,7 +151,8 @@ async fn update_ack(Body(input): Body<UpdateAckDto>) -> Result<HttpResponse<()>,
   responses(
     (status = OK, description = "Messages deleted successfully"),
     (status = 400, description = "Invalid query parameters"),
-    (status = 401, description = "Unauthorized")
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "The role of the device may not delete")
   ),
   security(
     ("bearer_auth" = [])
@@ -144,10 +161,24 @@ async fn update_ack(Body(input): Body<UpdateAckDto>) -> Result<HttpResponse<()>,
 )]
 #[debug_handler]
 async fn delete_conversation_messages(
+    _: CanDelete,
     claims: Claims,
+    client: ClientInfo,
     Path(target_id): Path<String>,
 ) -> Result<HttpResponse<()>, HttpException> {
-    Message::delete_conversation_messages(&claims.device_id, &target_id).await?;
+    let result = Message::delete_conversation_messages(&claims.device_id, &target_id)
+        .await
+        .map_err(HttpException::from);
+    let device_id = Some(claims.device_id.as_str());
+    audit::record_result(
+        AuditAction::DeleteConversation,
+        device_id,
+        &client,
+        &result,
+        Some(target_id),
+    )
+    .await;
+    result?;
 
     json_response!(());
 }
@@ -164,7 +195,8 @@ async fn delete_conversation_messages(
   responses(
     (status = OK, description = "Message deleted successfully"),
     (status = 404, description = "Message not found"),
-    (status = 401, description = "Unauthorized")
+    (status = 401, description = "Unauthorized"),
+    (status = 403, description = "The role of the device may not delete")
   ),
   security(
     ("bearer_auth" = [])
@@ -172,8 +204,134 @@ async fn delete_conversation_messages(
   tag = MESSAGE_TAG
 )]
 #[debug_handler]
-async fn delete_message(claims: Claims, Path(uuid): Path<String>) -> Result<HttpResponse<bool>, HttpException> {
-    let result = Message::delete_by_uuid(&claims.device_id, &uuid).await?;
+async fn delete_message(
+    _: CanDelete,
+    claims: Claims,
+    client: ClientInfo,
+    Path(uuid): Path<String>,
+) -> Result<HttpResponse<bool>, HttpException> {
+    let result = Message::delete_by_uuid(&claims.device_id, &uuid)
+        .await
+        .map_err(HttpException::from);
+    audit::record_result(
+        AuditAction::DeleteMessage,
+        Some(&claims.device_id),
+        &client,
+        &result,
+        Some(uuid),
+    )
+    .await;
+    let result = result?;
 
     json_response!(result);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        module::{
+            device::{Device, DeviceRole},
+            device_access::AccessRole,
+            message::MessageType,
+        },
+        utils::db::DBManager,
+    };
+    use axum::{extract::FromRequestParts, http::Request};
+
+    async fn device(id: &str) -> Claims {
+        let device = Device {
+            id: id.to_string(),
+            name: id.to_string(),
+            ..Device::default()
+        };
+        device.register().await.unwrap();
+        Claims::new(device.id, DeviceRole::Client, AccessRole::Member)
+    }
+
+    fn payload<T>(response: HttpResponse<T>) -> T {
+        match response {
+            HttpResponse::Json { payload, .. } => payload,
+            HttpResponse::RedirectTo { .. } => panic!("unexpected redirect"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_messages() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let alice = device("messages-alice").await;
+        let bob = device("messages-bob").await;
+        let carol = device("messages-carol").await;
+
+        let message = Message {
+            id: None,
+            uuid: "messages-secret".to_string(),
+            sender: bob.device_id.clone(),
+            receiver: carol.device_id.clone(),
+            r#type: MessageType::Text,
+            content: None,
+            plain_content: Some("secret".to_string()),
+            extra: None,
+            created_at: None,
+            updated_at: None,
+            attachment_urls: None,
+            thumbnail_urls: None,
+        };
+        message.create().await.unwrap();
+
+        // a client only reads its own conversations, asking for bob's partner gives nothing
+        let pagination = |target: &Claims| {
+            Query(CursorPagination {
+                target_id: target.device_id.clone(),
+                last_id: None,
+                page_size: 20,
+            })
+        };
+        let result = get_messages(alice, pagination(&carol)).await;
+        assert!(payload(result.unwrap()).messages.is_empty());
+        let result = get_messages(bob, pagination(&carol)).await;
+        assert_eq!(payload(result.unwrap()).messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_ack() {
+        DBManager::global().init_in_memory().await.unwrap();
+        let alice = device("acks-alice").await;
+        let bob = device("acks-bob").await;
+
+        // an ack always belongs to the sending device
+        MessageAck::new(bob.device_id.clone(), Some(1))
+            .received()
+            .await
+            .unwrap();
+        update_ack(alice.clone(), Body(UpdateAckDto { last_ack: 42 }))
+            .await
+            .unwrap();
+        let ack = |claims: &Claims| {
+            let id = claims.device_id.clone();
+            async move {
+                MessageAck::get_last_ack(&id)
+                    .await
+                    .unwrap()
+                    .and_then(|ack| ack.last_ack)
+            }
+        };
+        assert_eq!(ack(&alice).await, Some(42));
+        assert_eq!(ack(&bob).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_can_delete() {
+        let can_delete = |access| async move {
+            let (mut parts, _) = Request::new(()).into_parts();
+            parts
+                .extensions
+                .insert(Claims::new("messages-deleter".into(), DeviceRole::Client, access));
+            CanDelete::from_request_parts(&mut parts, &()).await.is_ok()
+        };
+
+        assert!(can_delete(AccessRole::Member).await);
+        assert!(!can_delete(AccessRole::ReadOnly).await);
+        assert!(!can_delete(AccessRole::Guest).await);
+    }
+}
@@ -1,18 +1,26 @@
 use super::AppState;
-use crate::server::guards::AuthGuard;
+use crate::server::guards::{AttachmentGuard, AuthGuard};
 use axum::{
+    Router,
     http::StatusCode,
     middleware,
     response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
 };
 use serde::Serialize;
 use std::sync::Arc;
 use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 
+mod attachment;
+mod bootstrap;
+mod bundle;
 mod device;
+mod federation;
+mod localsend;
 mod message;
 mod synclan;
+mod transfer;
 mod upload;
 
 #[allow(clippy::tabs_in_doc_comments)]
@@ -46,13 +54,43 @@ pub fn router() -> OpenApiRouter<Arc<AppState>> {
         .merge(device::protected_route())
         .merge(message::protected_route())
         .merge(upload::protected_route())
+        .merge(bundle::protected_route())
+        .merge(transfer::protected_route())
         .route_layer(middleware::from_extractor::<AuthGuard>())
         .merge(synclan::public_route())
-        .merge(device::public_route());
+        .merge(device::public_route())
+        .merge(federation::public_route());
 
     OpenApiRouter::new().nest("/v1", api_v1_router)
 }
 
+/// Uploaded files, nested at `/attachments`, readable with a bearer token or a signed url
+pub fn attachment_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{*path}", get(attachment::download))
+        .route_layer(middleware::from_extractor::<AttachmentGuard>())
+}
+
+/// LocalSend v2 protocol, nested at `/api/localsend/v2` when `enable_localsend` is on.
+/// Answers are plain JSON as LocalSend apps expect, not wrapped in a `JsonResponse`.
+pub fn localsend_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/info", get(localsend::info))
+        .route("/register", post(localsend::register))
+        .route("/prepare-upload", post(localsend::prepare_upload))
+        .route("/upload", post(localsend::upload))
+        .route("/cancel", post(localsend::cancel))
+}
+
+/// Plain HTTP next to HTTPS: serves the certificate to trust on first contact
+/// and redirects everything else to `https_port`
+pub fn http_redirect_router(https_port: u16) -> Router {
+    Router::new()
+        .route("/synclan-ca.crt", get(bootstrap::download_ca_cert))
+        .fallback(bootstrap::redirect_to_https)
+        .with_state(https_port)
+}
+
 #[allow(unused)]
 enum HttpResponse<T> {
     Json { payload: T, message: Option<String> },
@@ -1,9 +1,14 @@
 use super::{AppState, EmptyPayload, HttpResponse};
 use crate::{
     config::Config,
-    http_exception, json_response,
+    feat, http_exception, json_response,
+    module::audit_log::{AuditAction, AuditOutcome},
     server::{
-        api_doc::SYNCLAN_TAG, dtos::synclan_dto::AccessCodeDto, exception::HttpException, extractors::Body,
+        api_doc::SYNCLAN_TAG,
+        audit,
+        dtos::synclan_dto::AccessCodeDto,
+        exception::HttpException,
+        extractors::{Body, ClientInfo},
         routes::JsonResponse,
     },
 };
@@ -12,7 +17,9 @@ use std::sync::Arc;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 pub fn public_route() -> OpenApiRouter<Arc<AppState>> {
-    let router = OpenApiRouter::new().routes(routes!(verify_access_code));
+    let router = OpenApiRouter::new()
+        .routes(routes!(verify_access_code))
+        .routes(routes!(get_fingerprint));
     OpenApiRouter::new().nest("/synclan", router)
 }
 
@@ -25,22 +32,50 @@ pub fn public_route() -> OpenApiRouter<Arc<AppState>> {
     request_body = AccessCodeDto,
     responses(
         (status = OK, description = "Authorization code verification passed.", body = JsonResponse<EmptyPayload>),
-        (status = UNAUTHORIZED, description = "Authorization code verification failed.")
+        (status = UNAUTHORIZED, description = "Authorization code verification failed."),
+        (status = TOO_MANY_REQUESTS, description = "Too many failed attempts from this address.")
     ),
     tag = SYNCLAN_TAG
 )]
 #[debug_handler]
-async fn verify_access_code(Body(input): Body<AccessCodeDto>) -> Result<HttpResponse<()>, HttpException> {
-    let authorized_access_code = Config::synclan()
-        .await
-        .data_arc()
-        .authorized_access_code
-        .clone()
-        .ok_or(HttpException::UnauthorizedException(None))?;
+async fn verify_access_code(
+    client: ClientInfo,
+    Body(input): Body<AccessCodeDto>,
+) -> Result<HttpResponse<()>, HttpException> {
+    audit::check_failures(&client).await?;
+    let authorized_access_code = Config::synclan().await.data_arc().authorized_access_code.clone();
 
-    if authorized_access_code.ne(&input.code) {
+    // only failures are recorded, they are what guessing the code looks like
+    if authorized_access_code.is_none_or(|code| code.ne(&input.code)) {
+        let detail = Some("Invalid access code.".to_owned());
+        audit::record(
+            AuditAction::VerifyAccessCode,
+            None,
+            &client,
+            AuditOutcome::Failure,
+            detail,
+        )
+        .await;
         http_exception!(UnauthorizedException);
     }
 
     json_response!((), "Authorization code verification passed.");
 }
+
+/// Server certificate fingerprint.
+///
+/// A client that scanned the pairing QR code compares it with the fingerprint in the code,
+/// a different host answering on the address is caught before registering.
+#[utoipa::path(
+    get,
+    path = "/fingerprint",
+    responses(
+        (status = OK, description = "SHA-256 of the server certificate, null over plain HTTP", body = JsonResponse<Option<String>>)
+    ),
+    tag = SYNCLAN_TAG
+)]
+#[debug_handler]
+async fn get_fingerprint() -> Result<HttpResponse<Option<String>>, HttpException> {
+    let fingerprint = feat::get_server_fingerprint().await?;
+    json_response!(fingerprint);
+}
@@ -0,0 +1,365 @@
+use super::{
+    AppState, HttpResponse,
+    attachment::content_disposition,
+    upload::{ensure_mime_allowed, upload_limits},
+};
+use crate::{
+    config::Config,
+    logging, logging_error,
+    module::attachment::Attachment,
+    server::{
+        api_doc::TRANSFER_TAG,
+        exception::HttpException,
+        guards::Claims,
+        routes::JsonResponse,
+        transfer::{ProgressThrottle, RelayChunk, Transfer, TransferState, Transfers},
+        workers::{ThumbnailJob, upload::claim_file_name},
+    },
+    utils::{logging::Type, sniff},
+};
+use anyhow::{anyhow, bail};
+use apalis::prelude::TaskSink as _;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path as RoutePath, State},
+    http::{HeaderValue, header},
+    response::Response,
+};
+use axum_macros::debug_handler;
+use futures::{SinkExt as _, StreamExt as _, channel::mpsc};
+use sha2::{Digest as _, Sha256};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{fs, io::AsyncWriteExt as _};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
+    let router = OpenApiRouter::new().routes(routes!(send_transfer, receive_transfer));
+    OpenApiRouter::new().nest("/transfer", router)
+}
+
+/// Send the bytes of an accepted transfer.
+///
+/// The offer is made with the `synclan://transfer:offer` socket event. Once the receiver
+/// accepts it, the sender streams the file as the request body, the bytes are relayed to
+/// the receiver's `GET` as they arrive. A copy is kept on the host only if the offer asks for it.
+#[utoipa::path(
+    put,
+    path = "/{transfer_id}",
+    params(
+        ("transfer_id" = String, Path, description = "Transfer id from the offer")
+    ),
+    request_body(content_type = "application/octet-stream", content = String),
+    responses(
+        (status = OK, description = "Path of the kept copy, if any", body = JsonResponse<Option<String>>),
+        (status = NOT_FOUND, description = "Transfer not found"),
+        (status = CONFLICT, description = "The transfer has not been accepted or is already being sent"),
+        (status = GONE, description = "The transfer was cancelled"),
+        (status = BAD_REQUEST, description = "The bytes do not match the offer")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = TRANSFER_TAG
+)]
+#[debug_handler]
+async fn send_transfer(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    RoutePath(transfer_id): RoutePath<String>,
+    body: Body,
+) -> Result<HttpResponse<Option<String>>, HttpException> {
+    let transfers = &app_state.transfers;
+    let transfer = transfers
+        .get(&transfer_id)
+        .filter(|transfer| transfer.offer.sender == claims.device_id)
+        .ok_or_else(|| HttpException::NotFoundException(Some("Transfer not found.".into())))?;
+
+    // the kept copy counts against the quotas while it is relayed
+    let upload_sessions = &app_state.upload_sessions;
+    if transfer.offer.persist {
+        upload_sessions
+            .try_reserve(
+                &transfer_id,
+                &claims.device_id,
+                transfer.offer.size,
+                &upload_limits().await?,
+            )
+            .await?;
+    }
+    let result = relay_transfer(&app_state, &transfer, body).await;
+    upload_sessions.release(&transfer_id);
+    result
+}
+
+async fn relay_transfer(
+    app_state: &AppState,
+    transfer: &Arc<Transfer>,
+    body: Body,
+) -> Result<HttpResponse<Option<String>>, HttpException> {
+    let transfers = &app_state.transfers;
+    let mut tx = transfers
+        .take_sender(transfer)
+        .map_err(|err| HttpException::ConflictException(Some(err.to_string())))?;
+
+    let mut copy = None;
+    if transfer.offer.persist {
+        match KeptCopy::create(transfer).await {
+            Ok(created) => copy = Some(created),
+            Err(err) => {
+                let message = Some("Failed to keep a copy on the host.".to_string());
+                transfers.finish(transfer, TransferState::Failed, 0, None, message);
+                return Err(err);
+            },
+        }
+    }
+
+    match relay(transfers, transfer, body, &mut tx, copy.as_mut()).await {
+        Ok(transferred) => {
+            // the receiver already has the file, a failed copy does not fail the transfer
+            let (path, message) = match copy {
+                Some(copy) => match copy.keep(app_state, transfer).await {
+                    Ok(path) => (Some(path), None),
+                    Err(err) => {
+                        logging!(
+                            error,
+                            Type::Server,
+                            "Failed to keep transfer {}: {err:?}",
+                            transfer.offer.transfer_id
+                        );
+                        (None, Some("Failed to keep a copy on the host.".to_string()))
+                    },
+                },
+                None => (None, None),
+            };
+            transfers.finish(
+                transfer,
+                TransferState::Completed,
+                transferred,
+                path.clone(),
+                message.clone(),
+            );
+
+            Ok(HttpResponse::Json { payload: path, message })
+        },
+        Err(err) => {
+            // the receiver's download fails instead of ending with a truncated file
+            tx.try_send(Err(io::Error::other(err.to_string()))).ok();
+            if let Some(copy) = copy {
+                fs::remove_file(&copy.tmp_path).await.ok();
+            }
+
+            let cancelled = transfer.cancel.is_cancelled();
+            transfers.finish(transfer, TransferState::Failed, 0, None, Some(err.to_string()));
+            if cancelled {
+                Err(HttpException::GoneException(Some("The transfer was cancelled.".into())))
+            } else {
+                Err(HttpException::BadRequestException(Some(err.to_string())))
+            }
+        },
+    }
+}
+
+/// Receive the bytes of an accepted transfer.
+///
+/// Streams the file while the sender is sending it. The download fails if the
+/// transfer is cancelled or the bytes do not match the offered size and hash.
+#[utoipa::path(
+    get,
+    path = "/{transfer_id}",
+    params(
+        ("transfer_id" = String, Path, description = "Transfer id from the offer")
+    ),
+    responses(
+        (status = OK, description = "The file", content_type = "application/octet-stream", body = String),
+        (status = NOT_FOUND, description = "Transfer not found"),
+        (status = CONFLICT, description = "The transfer has not been accepted or is already being received")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = TRANSFER_TAG
+)]
+#[debug_handler]
+async fn receive_transfer(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    RoutePath(transfer_id): RoutePath<String>,
+) -> Result<Response, HttpException> {
+    let transfers = &app_state.transfers;
+    let transfer = transfers
+        .get(&transfer_id)
+        .filter(|transfer| transfer.offer.receiver == claims.device_id)
+        .ok_or_else(|| HttpException::NotFoundException(Some("Transfer not found.".into())))?;
+
+    let rx = transfers
+        .take_receiver(&transfer)
+        .map_err(|err| HttpException::ConflictException(Some(err.to_string())))?;
+
+    let offer = &transfer.offer;
+    let mime = offer
+        .mime_type
+        .clone()
+        .unwrap_or_else(|| mime_guess::from_path(&offer.name).first_or_octet_stream().to_string());
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CONTENT_LENGTH, offer.size)
+        .header(
+            header::CONTENT_DISPOSITION,
+            content_disposition("attachment", &offer.name)?,
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))
+        .body(Body::from_stream(rx))
+        .map_err(|err| HttpException::InternalServerErrorException(Some(err.to_string())))
+}
+
+/// Forwards the request body to the receiver, returns the number of bytes relayed
+async fn relay(
+    transfers: &Transfers,
+    transfer: &Transfer,
+    body: Body,
+    tx: &mut mpsc::Sender<RelayChunk>,
+    mut copy: Option<&mut KeptCopy>,
+) -> anyhow::Result<u64> {
+    let offer = &transfer.offer;
+    let mut stream = body.into_data_stream();
+    let mut hasher = Sha256::new();
+    let mut transferred = 0u64;
+    let mut throttle = ProgressThrottle::default();
+    // the last chunk is held back until the hash is verified,
+    // so the receiver never gets a complete file with the wrong content
+    let mut pending: Option<Bytes> = None;
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => chunk,
+            _ = transfer.cancel.cancelled() => bail!("The transfer was cancelled"),
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        let chunk = chunk?;
+
+        if transferred == 0
+            && let Some(copy) = copy.as_deref_mut()
+        {
+            let mime = sniff::sniff_bytes(&chunk, &offer.name);
+            if ensure_mime_allowed(&mime).await.is_err() {
+                bail!("File type {mime} is not allowed");
+            }
+            copy.mime = Some(mime);
+        }
+
+        transferred += chunk.len() as u64;
+        if transferred > offer.size {
+            bail!("Received more than the offered {} bytes", offer.size);
+        }
+        hasher.update(&chunk);
+        if let Some(copy) = copy.as_deref_mut() {
+            copy.file.write_all(&chunk).await?;
+        }
+
+        if let Some(previous) = pending.replace(chunk) {
+            send(tx, previous, transfer).await?;
+        }
+        if throttle.ready() {
+            transfers.emit_progress(transfer, TransferState::Transferring, transferred, None, None);
+        }
+    }
+
+    if transferred != offer.size {
+        bail!("Received {transferred} of the offered {} bytes", offer.size);
+    }
+    if let Some(hash) = &offer.hash
+        && !hash.eq_ignore_ascii_case(&format!("{:x}", hasher.finalize()))
+    {
+        bail!("The file does not match the offered hash");
+    }
+    if let Some(last) = pending {
+        send(tx, last, transfer).await?;
+    }
+
+    Ok(transferred)
+}
+
+async fn send(tx: &mut mpsc::Sender<RelayChunk>, chunk: Bytes, transfer: &Transfer) -> anyhow::Result<()> {
+    tokio::select! {
+        sent = tx.send(Ok(chunk)) => sent.map_err(|_| anyhow!("The receiver disconnected")),
+        _ = transfer.cancel.cancelled() => bail!("The transfer was cancelled"),
+    }
+}
+
+/// Copy of a transfer kept in today's upload directory, written next to the final path first
+struct KeptCopy {
+    file: fs::File,
+    tmp_path: PathBuf,
+    mime: Option<String>,
+}
+
+impl KeptCopy {
+    async fn create(transfer: &Transfer) -> Result<Self, HttpException> {
+        let synclan = Config::synclan().await.data_arc();
+        let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
+            HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+        })?;
+
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let dir = Path::new(upload_dir).join(today);
+        fs::create_dir_all(&dir).await?;
+
+        let tmp_path = dir.join(format!(".{}.part", transfer.offer.transfer_id));
+        let file = fs::File::create(&tmp_path).await?;
+
+        Ok(Self {
+            file,
+            tmp_path,
+            mime: None,
+        })
+    }
+
+    /// Moves the copy to `{today}/{name}`, numbered if taken, and records it as an attachment of the sender
+    async fn keep(mut self, app_state: &AppState, transfer: &Transfer) -> Result<String, HttpException> {
+        self.file.flush().await?;
+        drop(self.file);
+
+        let offer = &transfer.offer;
+        let dir = self.tmp_path.parent().unwrap_or(Path::new("."));
+        // an existing file of the same name is kept, this one gets a numbered suffix
+        let (file_name, path) = match claim_file_name(dir, &offer.name).await {
+            Ok(claimed) => claimed,
+            Err(err) => {
+                fs::remove_file(&self.tmp_path).await.ok();
+                return Err(err.into());
+            },
+        };
+        fs::rename(&self.tmp_path, &path).await?;
+
+        let today = dir.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let relative_path = format!("{today}/{file_name}");
+        Attachment {
+            path: relative_path.clone(),
+            name: offer.name.clone(),
+            size: offer.size as i64,
+            device_id: offer.sender.clone(),
+            mime: self.mime.clone(),
+            ..Attachment::default()
+        }
+        .create()
+        .await?;
+
+        if let Some(job) = self
+            .mime
+            .as_deref()
+            .and_then(|mime| ThumbnailJob::for_file(&relative_path, mime))
+        {
+            let mut storage = app_state.thumbnail_storage.clone();
+            logging_error!(Type::Server, storage.push(job).await);
+        }
+
+        Ok(relative_path)
+    }
+}
@@ -1,24 +1,40 @@
 use super::{AppState, HttpResponse};
 use crate::{
     config::Config,
+    logging, logging_error,
+    module::{attachment::Attachment, audit_log::AuditAction},
     server::{
         api_doc::UPLOAD_TAG,
+        audit,
         dtos::upload_dto::{UploadCompleteDto, UploadInitDto},
         exception::HttpException,
-        extractors::Body,
+        extractors::{Body, ClientInfo},
+        guards::{CanUpload, Claims},
+        rate_limit::{Limit, RateLimiter},
         routes::JsonResponse,
+        workers::{
+            ThumbnailJob,
+            upload::{self, UploadLimits, UploadMergeJob, UploadMeta, UploadProgress, UploadState},
+        },
     },
+    utils::{logging::Type, sniff},
 };
+use apalis::prelude::TaskSink as _;
 use axum::{
+    extract::{Path as RoutePath, State},
     http::StatusCode,
+    middleware,
     response::{IntoResponse, Response},
 };
 use axum_macros::debug_handler;
 use axum_typed_multipart::{BaseMultipart, FieldData, TryFromMultipart, TypedMultipartError};
 use serde::{Deserialize, Serialize};
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tempfile::NamedTempFile;
-use tokio::{fs, io::AsyncWriteExt as _};
+use tokio::fs;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -36,7 +52,9 @@ pub fn protected_route() -> OpenApiRouter<Arc<AppState>> {
         .routes(routes!(upload_handler))
         .routes(routes!(init_upload))
         .routes(routes!(upload_chunk))
-        .routes(routes!(complete_upload));
+        .routes(routes!(complete_upload))
+        .routes(routes!(get_upload_progress))
+        .route_layer(middleware::from_extractor::<CanUpload>());
     OpenApiRouter::new().nest("/upload", router)
 }
 
@@ -68,7 +86,10 @@ struct FileUpload {
     path = "",
     request_body(content_type = "multipart/form-data", content = FileUpload),
     responses(
-        (status = OK, description = "the file URL", body = JsonResponse<String>)
+        (status = OK, description = "the file URL", body = JsonResponse<String>),
+        (status = PAYLOAD_TOO_LARGE, description = "Storage quota exceeded or not enough disk space"),
+        (status = UNSUPPORTED_MEDIA_TYPE, description = "The file type is not allowed"),
+        (status = TOO_MANY_REQUESTS, description = "Upload rate limit exceeded")
     ),
     security(
         ("bearer_auth" = [])
@@ -78,25 +99,70 @@ struct FileUpload {
 #[debug_handler]
 // Step 5: Define a handler that takes the custom multipart as argument.
 // If the request is malformed, a `MultipartException` will be returned.
-async fn upload_handler(input: SelfTypedMultipart<FileUpload>) -> Result<HttpResponse<String>, HttpException> {
+async fn upload_handler(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    client: ClientInfo,
+    input: SelfTypedMultipart<FileUpload>,
+) -> Result<HttpResponse<String>, HttpException> {
+    RateLimiter::global().acquire(&claims.device_id, Limit::Upload).await?;
+
+    let result = save_upload(&app_state, &claims.device_id, input).await;
+    let detail = result.as_ref().ok().cloned();
+    audit::record_result(AuditAction::Upload, Some(&claims.device_id), &client, &result, detail).await;
+
+    Ok(HttpResponse::Json {
+        payload: result?,
+        message: None,
+    })
+}
+
+async fn save_upload(
+    app_state: &AppState,
+    device_id: &str,
+    input: SelfTypedMultipart<FileUpload>,
+) -> Result<String, HttpException> {
+    ensure_file_name(&input.data.name)?;
+    let size = input.data.file.contents.as_file().metadata()?.len();
+
+    // the file counts against the quotas until it is recorded as an attachment
+    let upload_id = uuid::Uuid::new_v4().to_string();
+    let sessions = &app_state.upload_sessions;
+    sessions
+        .try_reserve(&upload_id, device_id, size, &upload_limits().await?)
+        .await?;
+    let result = store_upload(app_state, device_id, size, input).await;
+    sessions.release(&upload_id);
+    result
+}
+
+async fn store_upload(
+    app_state: &AppState,
+    device_id: &str,
+    size: u64,
+    input: SelfTypedMultipart<FileUpload>,
+) -> Result<String, HttpException> {
     let synclan = Config::synclan().await.data_arc();
     let file_upload_dir = synclan.file_upload_dir.as_ref();
     let file_upload_dir = file_upload_dir.ok_or_else(|| {
         HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".to_owned()))
     })?;
 
+    let mime = sniff::sniff_file(input.data.file.contents.path(), &input.data.name).await?;
+    ensure_mime_allowed(&mime).await?;
+
     let sub_path = if input.permanent.unwrap_or(false) {
         "assets".to_string()
     } else {
         chrono::Local::now().format("%Y-%m-%d").to_string()
     };
 
-    let mut path = Path::new(&file_upload_dir).join(&sub_path);
+    let dir = Path::new(&file_upload_dir).join(&sub_path);
 
-    fs::create_dir_all(&path).await?;
+    fs::create_dir_all(&dir).await?;
 
-    let file_name = &input.data.name;
-    path.push(file_name);
+    // an existing file of the same name is kept, this one gets a numbered suffix
+    let (file_name, path) = upload::claim_file_name(&dir, &input.data.name).await?;
 
     input
         .data
@@ -105,10 +171,24 @@ async fn upload_handler(input: SelfTypedMultipart<FileUpload>) -> Result<HttpRes
         .persist(&path)
         .map_err(|err| HttpException::InternalServerErrorException(Some(err.to_string())))?;
 
-    Ok(HttpResponse::Json {
-        payload: format!("{}/{}", sub_path, file_name),
-        message: None,
-    })
+    let relative_path = format!("{}/{}", sub_path, file_name);
+    Attachment {
+        path: relative_path.clone(),
+        name: input.data.name.clone(),
+        size: size as i64,
+        device_id: device_id.to_owned(),
+        mime: Some(mime.clone()),
+        ..Attachment::default()
+    }
+    .create()
+    .await?;
+
+    if let Some(job) = ThumbnailJob::for_file(&relative_path, &mime) {
+        let mut storage = app_state.thumbnail_storage.clone();
+        logging_error!(Type::Server, storage.push(job).await);
+    }
+
+    Ok(relative_path)
 }
 
 /// Initialize a chunked file upload session.
@@ -119,7 +199,9 @@ async fn upload_handler(input: SelfTypedMultipart<FileUpload>) -> Result<HttpRes
     path = "/chunk/init",
     request_body = UploadInitDto,
     responses(
-        (status = OK, body = JsonResponse<UploadInitResponse>)
+        (status = OK, body = JsonResponse<UploadInitResponse>),
+        (status = PAYLOAD_TOO_LARGE, description = "Storage quota exceeded or not enough disk space"),
+        (status = TOO_MANY_REQUESTS, description = "Upload rate limit exceeded")
     ),
     security(
         ("bearer_auth" = [])
@@ -127,7 +209,12 @@ async fn upload_handler(input: SelfTypedMultipart<FileUpload>) -> Result<HttpRes
     tag = UPLOAD_TAG
 )]
 #[debug_handler]
-async fn init_upload(Body(input): Body<UploadInitDto>) -> Result<HttpResponse<UploadInitResponse>, HttpException> {
+async fn init_upload(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    Body(input): Body<UploadInitDto>,
+) -> Result<HttpResponse<UploadInitResponse>, HttpException> {
+    RateLimiter::global().acquire(&claims.device_id, Limit::Upload).await?;
     let upload_id = uuid::Uuid::new_v4().to_string();
 
     let synclan = Config::synclan().await.data_arc();
@@ -135,32 +222,44 @@ async fn init_upload(Body(input): Body<UploadInitDto>) -> Result<HttpResponse<Up
         HttpException::ServiceUnavailableException(Some("File upload directory is not configured".into()))
     })?;
 
-    let chunk_size = if input.chunk_size == 0 {
-        DEFAULT_CHUNK_SIZE
-    } else {
-        input.chunk_size.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
-    };
+    ensure_file_name(&input.name)?;
+    let sessions = &app_state.upload_sessions;
+    sessions
+        .try_reserve(&upload_id, &claims.device_id, input.size, &upload_limits().await?)
+        .await?;
+
+    let chunk_size = effective_chunk_size(input.chunk_size);
     //
     // ceil(size / chunk_size)
     //
     let total_chunks = input.size.div_ceil(chunk_size) as u32;
 
-    let chunk_dir = Path::new(upload_dir).join("chunks").join(&upload_id);
-
-    fs::create_dir_all(&chunk_dir).await?;
+    let chunk_dir = upload::chunk_dir(upload_dir, &upload_id);
 
     //
     // Store upload metadata
     //
-    let meta = serde_json::json!({
-        "uploadId": upload_id,
-        "name": input.name,
-        "size": input.size,
-        "chunkSize": chunk_size,
-        "totalChunks": total_chunks,
-    });
+    let meta = UploadMeta {
+        upload_id: upload_id.clone(),
+        name: input.name,
+        size: input.size,
+        chunk_size,
+        total_chunks,
+        merged_chunks: 0,
+        device_id: claims.device_id,
+        target: None,
+    };
 
-    fs::write(chunk_dir.join("meta.json"), meta.to_string()).await?;
+    let saved = async {
+        fs::create_dir_all(&chunk_dir).await?;
+        meta.save(&chunk_dir).await
+    }
+    .await;
+    if let Err(err) = saved {
+        sessions.release(&upload_id);
+        return Err(err.into());
+    }
+    sessions.set(UploadProgress::new(&meta, UploadState::Uploading));
 
     Ok(HttpResponse::Json {
         payload: UploadInitResponse {
@@ -195,13 +294,16 @@ struct ChunkUpload {
 
 /// Upload a chunk of a file
 ///
-/// The chunk will be stored in the upload directory under `chunks/{upload_id}/{index}.chunk`.
+/// The chunk will be stored in the upload directory under `chunks/{upload_id}/{index}.chunk`,
+/// and appended to the partially merged file in the background as soon as all previous chunks have arrived.
 #[utoipa::path(
     post,
     path = "/chunk",
     request_body(content_type = "multipart/form-data", content = ChunkUpload),
     responses(
-        (status = OK, description = "Chunk uploaded successfully")
+        (status = OK, description = "Chunk uploaded successfully"),
+        (status = NOT_FOUND, description = "Upload session not found, or started by another device"),
+        (status = UNSUPPORTED_MEDIA_TYPE, description = "The file type is not allowed")
     ),
     security(
         ("bearer_auth" = [])
@@ -209,7 +311,11 @@ struct ChunkUpload {
     tag = UPLOAD_TAG
 )]
 #[debug_handler]
-async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResponse<()>, HttpException> {
+async fn upload_chunk(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    input: SelfTypedMultipart<ChunkUpload>,
+) -> Result<HttpResponse<()>, HttpException> {
     let synclan = Config::synclan().await.data_arc();
     let file_upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
         HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".to_owned()))
@@ -218,22 +324,53 @@ async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResp
     //
     // chunks/{upload_id}
     //
-    let chunk_dir = Path::new(file_upload_dir).join("chunks").join(&input.data.upload_id);
+    let chunk_dir = session_dir(file_upload_dir, &input.data.upload_id)?;
+
+    let meta = UploadMeta::load(&chunk_dir)
+        .await
+        .ok()
+        .filter(|meta| meta.device_id == claims.device_id)
+        .ok_or_else(|| HttpException::NotFoundException(Some("Upload session not found.".into())))?;
+    if input.data.index >= meta.total_chunks {
+        return Err(HttpException::BadRequestException(Some(format!(
+            "Invalid chunk index {}.",
+            input.data.index
+        ))));
+    }
 
-    fs::create_dir_all(&chunk_dir).await?;
+    //
+    // A chunk must not exceed its declared share of the file,
+    // otherwise the size checked against the quotas at init would be meaningless
+    //
+    let offset = input.data.index as u64 * meta.chunk_size;
+    let expected_size = meta.chunk_size.min(meta.size.saturating_sub(offset));
+    if input.data.file.contents.as_file().metadata()?.len() > expected_size {
+        return Err(HttpException::PayloadTooLargeException(Some(format!(
+            "Chunk {} exceeds {} bytes.",
+            input.data.index, expected_size
+        ))));
+    }
 
     //
     // {index}.chunk
     //
     let chunk_path = chunk_dir.join(format!("{}.chunk", input.data.index));
 
-    if chunk_path.exists() {
+    if input.data.index < meta.merged_chunks || chunk_path.exists() {
         return Ok(HttpResponse::Json {
             payload: (),
             message: Some("Chunk already uploaded".into()),
         });
     }
 
+    //
+    // The first chunk holds the magic bytes, reject disallowed types before the rest is sent
+    //
+    if input.data.index == 0 {
+        let mime = sniff::sniff_file(input.data.file.contents.path(), &meta.name).await?;
+        ensure_mime_allowed(&mime).await?;
+    }
+
     let tmp_path = chunk_dir.join(format!("{}.chunk.tmp", input.data.index));
 
     input
@@ -245,6 +382,28 @@ async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResp
 
     fs::rename(tmp_path, chunk_path).await?;
 
+    //
+    // Append the chunks that are ready without holding up the request
+    //
+    let upload_id = input.data.upload_id.clone();
+    tokio::spawn(async move {
+        let sessions = &app_state.upload_sessions;
+        let lock = sessions.lock(&upload_id);
+        let _guard = lock.lock().await;
+
+        // the session may have been completed while waiting for the lock
+        let Ok(mut meta) = UploadMeta::load(&chunk_dir).await else {
+            return;
+        };
+        logging_error!(
+            Type::Server,
+            upload::append_ready_chunks(&chunk_dir, &mut meta, |meta| {
+                sessions.set(UploadProgress::new(meta, UploadState::Uploading));
+            })
+            .await
+        );
+    });
+
     Ok(HttpResponse::Json {
         payload: (),
         message: None,
@@ -253,7 +412,9 @@ async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResp
 
 /// Complete chunked upload.
 ///
-/// Merge all chunks into the final file.
+/// Schedules a background job that merges the remaining chunks into the final file.
+/// Progress is emitted to the uploading device as `synclan://upload:progress`,
+/// the final path can also be polled from `/upload/chunk/{upload_id}`.
 #[utoipa::path(
     post,
     path = "/chunk/complete",
@@ -261,8 +422,9 @@ async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResp
     responses(
         (
             status = OK,
-            body = JsonResponse<UploadCompleteResponse>
-        )
+            body = JsonResponse<UploadProgress>
+        ),
+        (status = NOT_FOUND, description = "Upload session not found, or started by another device")
     ),
     security(
         ("bearer_auth" = [])
@@ -271,39 +433,68 @@ async fn upload_chunk(input: SelfTypedMultipart<ChunkUpload>) -> Result<HttpResp
 )]
 #[debug_handler]
 async fn complete_upload(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    client: ClientInfo,
     Body(input): Body<UploadCompleteDto>,
-) -> Result<HttpResponse<UploadCompleteResponse>, HttpException> {
+) -> Result<HttpResponse<UploadProgress>, HttpException> {
+    let result = schedule_merge(&app_state, &input.upload_id, &claims.device_id).await;
+    let device_id = Some(claims.device_id.as_str());
+    audit::record_result(AuditAction::Upload, device_id, &client, &result, Some(input.upload_id)).await;
+    let progress = result?;
+
+    Ok(HttpResponse::Json {
+        payload: progress,
+        message: Some("File merge scheduled.".into()),
+    })
+}
+
+/// Checks that every chunk of the upload has arrived and queues the [`UploadMergeJob`].
+///
+/// Scheduling an upload that is already merging or completed returns its progress.
+pub(super) async fn schedule_merge(
+    app_state: &AppState,
+    upload_id: &str,
+    device_id: &str,
+) -> Result<UploadProgress, HttpException> {
+    //
+    // The merge has already been scheduled
+    //
+    if let Some(progress) = app_state.upload_sessions.get(upload_id)
+        && progress.device_id == device_id
+        && matches!(progress.state, UploadState::Merging | UploadState::Completed)
+    {
+        return Ok(progress);
+    }
+
     let synclan = Config::synclan().await.data_arc();
     let upload_dir = synclan.file_upload_dir.as_ref().ok_or_else(|| {
         HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
     })?;
 
-    let chunk_dir = Path::new(upload_dir).join("chunks").join(&input.upload_id);
+    let chunk_dir = session_dir(upload_dir, upload_id)?;
     if !chunk_dir.exists() {
-        return Err(HttpException::BadRequestException(Some(
+        return Err(HttpException::NotFoundException(Some(
             "Upload session not found.".into(),
         )));
     }
 
     //
-    // Read metadata
+    // Read metadata, another device's session does not exist for the caller
     //
-    let meta_path = chunk_dir.join("meta.json");
-    let meta_content = fs::read_to_string(&meta_path).await?;
-    let meta: serde_json::Value = serde_json::from_str(&meta_content)
+    let meta = UploadMeta::load(&chunk_dir)
+        .await
         .map_err(|err| HttpException::InternalServerErrorException(Some(err.to_string())))?;
-
-    let filename = meta["name"]
-        .as_str()
-        .ok_or_else(|| HttpException::InternalServerErrorException(Some("Invalid upload metadata.".into())))?;
-    let total_chunks = meta["totalChunks"]
-        .as_u64()
-        .ok_or_else(|| HttpException::InternalServerErrorException(Some("Invalid total chunks.".into())))?;
+    if meta.device_id != device_id {
+        return Err(HttpException::NotFoundException(Some(
+            "Upload session not found.".into(),
+        )));
+    }
 
     //
-    // Check all chunks exist
+    // Check all chunks that are not merged yet exist
     //
-    for index in 0..total_chunks {
+    for index in meta.merged_chunks..meta.total_chunks {
         let chunk_path = chunk_dir.join(format!("{}.chunk", index));
 
         if !chunk_path.exists() {
@@ -314,49 +505,111 @@ async fn complete_upload(
         }
     }
 
-    let sub_path = chrono::Local::now().format("%Y-%m-%d").to_string();
-    //
-    // Create final directory
-    //
-    let final_dir = Path::new(upload_dir).join(&sub_path);
+    let progress = UploadProgress::new(&meta, UploadState::Merging);
+    app_state.upload_sessions.set(progress.clone());
 
-    fs::create_dir_all(&final_dir).await?;
+    let mut storage = app_state.upload_storage.clone();
+    storage
+        .push(UploadMergeJob {
+            upload_id: upload_id.to_string(),
+            device_id: device_id.to_string(),
+        })
+        .await
+        .map_err(|err| HttpException::InternalServerErrorException(Some(err.to_string())))?;
 
-    let final_path = final_dir.join(filename);
+    logging!(info, Type::Server, "Scheduled merge of upload {}", progress.upload_id);
 
-    //
-    // Merge chunks
-    //
-    let mut output = fs::File::create(&final_path).await?;
+    Ok(progress)
+}
 
-    for index in 0..total_chunks {
-        let chunk_path = chunk_dir.join(format!("{}.chunk", index));
+/// Query chunked upload progress.
+///
+/// Once the state is `completed`, `path` holds the uploaded file path.
+#[utoipa::path(
+    get,
+    path = "/chunk/{upload_id}",
+    params(
+        ("upload_id" = String, Path, description = "Upload id returned by /upload/chunk/init"),
+    ),
+    responses(
+        (status = OK, body = JsonResponse<UploadProgress>),
+        (status = 404, description = "Upload session not found, or started by another device")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = UPLOAD_TAG
+)]
+#[debug_handler]
+async fn get_upload_progress(
+    State(app_state): State<Arc<AppState>>,
+    claims: Claims,
+    RoutePath(upload_id): RoutePath<String>,
+) -> Result<HttpResponse<UploadProgress>, HttpException> {
+    let progress = app_state
+        .upload_sessions
+        .get(&upload_id)
+        .filter(|progress| progress.device_id == claims.device_id)
+        .ok_or_else(|| HttpException::NotFoundException(Some("Upload session not found.".into())))?;
 
-        let mut chunk_file = fs::File::open(&chunk_path).await?;
+    Ok(HttpResponse::Json {
+        payload: progress,
+        message: None,
+    })
+}
 
-        tokio::io::copy(&mut chunk_file, &mut output).await?;
+/// The preferred chunk size clamped to the supported range, `0` selects the default
+pub(super) fn effective_chunk_size(preferred: u64) -> u64 {
+    if preferred == 0 {
+        DEFAULT_CHUNK_SIZE
+    } else {
+        preferred.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
     }
+}
 
-    output.flush().await?;
+/// The session directory of `upload_id`, which must be one of the uuids handed out by the server
+pub(super) fn session_dir(upload_dir: &str, upload_id: &str) -> Result<PathBuf, HttpException> {
+    uuid::Uuid::parse_str(upload_id)
+        .map(|upload_id| upload::chunk_dir(upload_dir, &upload_id.to_string()))
+        .map_err(|_| HttpException::NotFoundException(Some("Upload session not found.".into())))
+}
 
-    //
-    // Remove temporary chunks
-    //
-    fs::remove_dir_all(&chunk_dir).await?;
+/// Rejects a file name that could leave its folder or hide in it, see [`upload::is_valid_file_name`]
+pub(super) fn ensure_file_name(name: &str) -> Result<(), HttpException> {
+    if upload::is_valid_file_name(name) {
+        Ok(())
+    } else {
+        Err(HttpException::BadRequestException(Some(format!(
+            "Invalid file name {name}."
+        ))))
+    }
+}
 
-    let relative_path = format!("{}/{}", sub_path, filename);
+/// The storage limits an upload is reserved against, see [`upload::UploadSessions::try_reserve`]
+pub(super) async fn upload_limits() -> Result<UploadLimits, HttpException> {
+    let synclan = Config::synclan().await.data_arc();
+    let upload_dir = synclan.file_upload_dir.clone().ok_or_else(|| {
+        HttpException::ServiceUnavailableException(Some("File upload directory is not configured.".into()))
+    })?;
 
-    Ok(HttpResponse::Json {
-        payload: UploadCompleteResponse { path: relative_path },
-        message: Some("File uploaded successfully.".into()),
+    Ok(UploadLimits {
+        upload_dir,
+        device_quota: synclan.upload_device_quota,
+        total_quota: synclan.upload_total_quota,
+        min_free_space: synclan.upload_min_free_space.unwrap_or(0),
     })
 }
 
-#[derive(Debug, Serialize, ToSchema)]
-#[serde(rename_all = "camelCase")]
-pub(crate) struct UploadCompleteResponse {
-    /// Uploaded file path
-    pub path: String,
+/// Checks the sniffed MIME type against `upload_mime_allowlist` and `upload_mime_denylist`
+pub(super) async fn ensure_mime_allowed(mime: &str) -> Result<(), HttpException> {
+    let synclan = Config::synclan().await.data_arc();
+    if !synclan.is_upload_mime_allowed(mime) {
+        return Err(HttpException::UnsupportedMediaTypeException(Some(format!(
+            "File type {mime} is not allowed."
+        ))));
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Serialize, ToSchema)]
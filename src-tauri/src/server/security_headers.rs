@@ -0,0 +1,55 @@
+use axum::{
+    Router,
+    http::{HeaderName, HeaderValue, header},
+};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+/// Policy for the web UI and the Swagger UI. Both only load their own scripts, inline
+/// styles come from the component library, images and videos of messages may be blobs,
+/// the default avatars are remote images and the lottie player compiles WebAssembly.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'wasm-unsafe-eval'; \
+    style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data: blob: https:; \
+    media-src 'self' blob:; \
+    font-src 'self' data:; \
+    connect-src 'self'; \
+    worker-src 'self' blob:; \
+    object-src 'none'; \
+    base-uri 'self'; \
+    form-action 'self'; \
+    frame-ancestors 'none'";
+
+/// Short, encryption can be switched off again and the browser would then refuse the
+/// plain HTTP server for as long as it remembers this
+const STRICT_TRANSPORT_SECURITY: &str = "max-age=86400";
+
+/// Adds the security headers to every response that doesn't set them itself.
+/// `hsts` only for HTTPS, browsers ignore it over plain HTTP anyway.
+pub fn layer<S>(router: Router<S>, hsts: bool) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let headers = [
+        (header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        (header::X_FRAME_OPTIONS, "DENY"),
+        // pairing links carry the grant in the query
+        (header::REFERRER_POLICY, "no-referrer"),
+    ];
+
+    let mut router = headers.into_iter().fold(router, |router, (name, value)| {
+        router.layer(if_not_present(name, value))
+    });
+    if hsts {
+        router = router.layer(if_not_present(
+            header::STRICT_TRANSPORT_SECURITY,
+            STRICT_TRANSPORT_SECURITY,
+        ));
+    }
+    router
+}
+
+fn if_not_present(name: HeaderName, value: &'static str) -> SetResponseHeaderLayer<HeaderValue> {
+    SetResponseHeaderLayer::if_not_present(name, HeaderValue::from_static(value))
+}
@@ -0,0 +1,118 @@
+use crate::{
+    module::{attachment::Attachment, message::Message},
+    singleton,
+};
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a signed attachment url stays valid
+const SIGNED_URL_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// Signs attachment urls so they can be loaded by `<img>` / `<video>` tags,
+/// which cannot send the `Authorization` header.
+///
+/// The key only lives in memory, urls signed before a restart are invalid
+/// and the client simply gets fresh ones with the next message fetch.
+pub struct UrlSigner {
+    key: [u8; 32],
+}
+
+singleton!(UrlSigner, URLSIGNER);
+
+impl UrlSigner {
+    fn new() -> Self {
+        let mut key = [0u8; 32];
+        getrandom::fill(&mut key).expect("Failed to generate the url signing key");
+        Self { key }
+    }
+
+    /// Relative url of the attachment, e.g. `2025-06-04/a%20b.png?expires=..&signature=..`
+    pub fn sign(&self, path: &str) -> String {
+        let expires = chrono::Utc::now().timestamp() + SIGNED_URL_TTL_SECS;
+        format!(
+            "{}?expires={expires}&signature={}",
+            encode_path(path),
+            self.signature(path, expires)
+        )
+    }
+
+    /// Checks the `expires` and `signature` query of a (decoded) attachment path
+    pub fn verify(&self, path: &str, expires: i64, signature: &str) -> bool {
+        if expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+
+        self.mac(path, expires).verify_slice(&signature).is_ok()
+    }
+
+    /// Fills `attachment_urls` and `thumbnail_urls` of the messages that reference uploaded files
+    pub async fn sign_messages<'a>(&self, messages: impl IntoIterator<Item = &'a mut Message>) -> Result<()> {
+        let messages: Vec<(&mut Message, Vec<String>)> = messages
+            .into_iter()
+            .map(|message| {
+                let paths = message.attachment_paths();
+                (message, paths)
+            })
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect();
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let all_paths: Vec<String> = messages.iter().flat_map(|(_, paths)| paths.iter().cloned()).collect();
+        let thumbnails = Attachment::get_thumbnails(&all_paths).await?;
+
+        for (message, paths) in messages {
+            let mut attachment_urls = HashMap::new();
+            let mut thumbnail_urls = HashMap::new();
+            for path in paths {
+                if let Some(thumbnail) = thumbnails.get(&path) {
+                    thumbnail_urls.insert(path.clone(), self.sign(thumbnail));
+                }
+                let url = self.sign(&path);
+                attachment_urls.insert(path, url);
+            }
+
+            message.attachment_urls = Some(attachment_urls);
+            message.thumbnail_urls = (!thumbnail_urls.is_empty()).then_some(thumbnail_urls);
+        }
+
+        Ok(())
+    }
+
+    fn signature(&self, path: &str, expires: i64) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(path, expires).finalize().into_bytes())
+    }
+
+    fn mac(&self, path: &str, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any size");
+        mac.update(path.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
+/// Percent-encodes every path segment, keeping the `/` separators
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            segment
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+                    _ => format!("%{b:02X}"),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
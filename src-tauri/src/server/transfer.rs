@@ -0,0 +1,383 @@
+use crate::{
+    logging,
+    server::{events::store::Clients, workers::upload::is_valid_file_name},
+    utils::logging::Type,
+};
+use anyhow::{Result, anyhow, bail};
+use axum::body::Bytes;
+use dashmap::DashMap;
+use futures::channel::mpsc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use socketioxide::SocketIo;
+use std::{
+    io,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+/// How long an offer waits for the receiver to answer
+const OFFER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long an accepted transfer waits for the receiver's `GET /transfer/{id}`
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Chunks buffered between the sender and the receiver
+const RELAY_BUFFER: usize = 16;
+
+/// Minimum interval between two `synclan://transfer:progress` events of a transfer
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub type RelayChunk = Result<Bytes, io::Error>;
+
+/// A file offered by the sender, emitted to the receiver as `synclan://transfer:offer`
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOffer {
+    /// Assigned by the server
+    #[serde(default)]
+    pub transfer_id: String,
+
+    /// Assigned by the server, the device that made the offer
+    #[serde(default)]
+    pub sender: String,
+
+    pub receiver: String,
+
+    pub name: String,
+
+    pub size: u64,
+
+    /// Hex encoded SHA-256 of the file, the relay fails if the bytes do not match
+    pub hash: Option<String>,
+
+    pub mime_type: Option<String>,
+
+    /// Keep a copy in the file upload directory, by default the bytes only pass through the host
+    #[serde(default)]
+    pub persist: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferAnswer {
+    pub transfer_id: String,
+    pub accept: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferCancel {
+    pub transfer_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferState {
+    /// Waiting for the receiver to answer
+    Pending,
+    /// Accepted, waiting for the bytes
+    Accepted,
+    Transferring,
+    Completed,
+    Declined,
+    Cancelled,
+    Failed,
+}
+
+impl TransferState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Declined | Self::Cancelled | Self::Failed)
+    }
+}
+
+/// Emitted to both ends as `synclan://transfer:progress` on every state change
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    pub state: TransferState,
+    pub transferred: u64,
+    pub total: u64,
+    /// Path of the kept copy, relative to the file upload directory
+    pub path: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Both halves of the relay channel, each end takes its half once
+struct Relay {
+    tx: Option<mpsc::Sender<RelayChunk>>,
+    rx: Option<mpsc::Receiver<RelayChunk>>,
+}
+
+pub struct Transfer {
+    pub offer: TransferOffer,
+    pub cancel: CancellationToken,
+    state: Mutex<TransferState>,
+    relay: Mutex<Relay>,
+}
+
+impl Transfer {
+    pub fn state(&self) -> TransferState {
+        *self.state.lock()
+    }
+}
+
+/// Direct device-to-device transfers, the bytes are relayed from the sender's
+/// `PUT /transfer/{id}` to the receiver's `GET /transfer/{id}` without a full copy on the host.
+#[derive(Clone, Default)]
+pub struct Transfers {
+    transfers: Arc<DashMap<String, Arc<Transfer>>>,
+    io: Arc<OnceLock<SocketIo>>,
+    clients: Clients,
+}
+
+impl Transfers {
+    pub fn new(clients: Clients) -> Self {
+        Self {
+            clients,
+            ..Self::default()
+        }
+    }
+
+    /// The socket.io server only exists once the layer is built
+    pub fn bind(&self, io: SocketIo) {
+        self.io.set(io).ok();
+    }
+
+    pub fn get(&self, transfer_id: &str) -> Option<Arc<Transfer>> {
+        self.transfers.get(transfer_id).map(|r| r.value().clone())
+    }
+
+    /// Registers the offer and forwards it to the receiver, who has to be online
+    pub fn offer(&self, sender: &str, mut offer: TransferOffer) -> Result<TransferOffer> {
+        if offer.receiver == sender {
+            bail!("Cannot send a file to yourself");
+        }
+        if !is_valid_file_name(&offer.name) {
+            bail!("Invalid file name");
+        }
+        if !self.clients.contains(&offer.receiver) {
+            bail!("The receiver is offline");
+        }
+
+        offer.transfer_id = uuid::Uuid::new_v4().to_string();
+        offer.sender = sender.to_string();
+
+        let (tx, rx) = mpsc::channel(RELAY_BUFFER);
+        let transfer = Arc::new(Transfer {
+            offer: offer.clone(),
+            cancel: CancellationToken::new(),
+            state: Mutex::new(TransferState::Pending),
+            relay: Mutex::new(Relay {
+                tx: Some(tx),
+                rx: Some(rx),
+            }),
+        });
+        self.transfers.insert(offer.transfer_id.clone(), transfer.clone());
+        self.emit(&offer.receiver, "synclan://transfer:offer", &offer);
+
+        // unanswered offers expire
+        let transfers = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(OFFER_TIMEOUT) => {
+                    if transfer.state() == TransferState::Pending {
+                        let message = Some("The offer expired.".to_string());
+                        transfers.finish(&transfer, TransferState::Cancelled, 0, None, message);
+                    }
+                },
+                _ = transfer.cancel.cancelled() => {},
+            }
+        });
+
+        Ok(offer)
+    }
+
+    pub fn answer(&self, device_id: &str, answer: &TransferAnswer) -> Result<()> {
+        let transfer = self
+            .get(&answer.transfer_id)
+            .filter(|transfer| transfer.offer.receiver == device_id)
+            .ok_or_else(|| anyhow!("Transfer not found"))?;
+
+        if answer.accept {
+            self.set_state(&transfer, TransferState::Pending, TransferState::Accepted)?;
+            self.emit_progress(&transfer, TransferState::Accepted, 0, None, None);
+
+            // the sender would block on a full relay otherwise
+            let transfers = self.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep(RECEIVE_TIMEOUT) => {
+                        if transfer.relay.lock().rx.is_some() {
+                            let message = Some("The receiver did not start the download.".to_string());
+                            transfers.finish(&transfer, TransferState::Cancelled, 0, None, message);
+                        }
+                    },
+                    _ = transfer.cancel.cancelled() => {},
+                }
+            });
+        } else {
+            if transfer.state() != TransferState::Pending {
+                bail!("The transfer is {:?}", transfer.state());
+            }
+            self.finish(&transfer, TransferState::Declined, 0, None, None);
+        }
+
+        Ok(())
+    }
+
+    /// Either end can cancel, the relay is torn down and both ends are notified
+    pub fn cancel(&self, device_id: &str, transfer_id: &str) -> Result<()> {
+        let transfer = self
+            .get(transfer_id)
+            .filter(|transfer| transfer.offer.sender == device_id || transfer.offer.receiver == device_id)
+            .ok_or_else(|| anyhow!("Transfer not found"))?;
+
+        self.finish(
+            &transfer,
+            TransferState::Cancelled,
+            0,
+            None,
+            Some(format!("Cancelled by {device_id}.")),
+        );
+        Ok(())
+    }
+
+    /// Cancels the transfers of a device that went offline, once its last socket is gone
+    pub fn cancel_device(&self, device_id: &str) {
+        let transfers: Vec<_> = self
+            .transfers
+            .iter()
+            .filter(|r| r.offer.sender == device_id || r.offer.receiver == device_id)
+            .map(|r| r.value().clone())
+            .collect();
+        for transfer in transfers {
+            self.finish(
+                &transfer,
+                TransferState::Cancelled,
+                0,
+                None,
+                Some("The device went offline.".into()),
+            );
+        }
+    }
+
+    /// Takes the sending half of an accepted transfer, only once
+    pub fn take_sender(&self, transfer: &Transfer) -> Result<mpsc::Sender<RelayChunk>> {
+        self.set_state(transfer, TransferState::Accepted, TransferState::Transferring)?;
+        transfer
+            .relay
+            .lock()
+            .tx
+            .take()
+            .ok_or_else(|| anyhow!("The transfer is already being sent"))
+    }
+
+    /// Takes the receiving half of an accepted transfer, only once
+    pub fn take_receiver(&self, transfer: &Transfer) -> Result<mpsc::Receiver<RelayChunk>> {
+        if !matches!(transfer.state(), TransferState::Accepted | TransferState::Transferring) {
+            bail!("The transfer has not been accepted");
+        }
+        transfer
+            .relay
+            .lock()
+            .rx
+            .take()
+            .ok_or_else(|| anyhow!("The transfer is already being received"))
+    }
+
+    /// Moves the transfer to a final state, removes it and notifies both ends.
+    /// Does nothing if the transfer is already finished.
+    pub fn finish(
+        &self,
+        transfer: &Transfer,
+        state: TransferState,
+        transferred: u64,
+        path: Option<String>,
+        message: Option<String>,
+    ) {
+        {
+            let mut current = transfer.state.lock();
+            if current.is_finished() {
+                return;
+            }
+            *current = state;
+        }
+
+        transfer.cancel.cancel();
+        self.transfers.remove(&transfer.offer.transfer_id);
+        if state != TransferState::Completed {
+            logging!(
+                info,
+                Type::Server,
+                "Transfer {} {state:?}: {}",
+                transfer.offer.transfer_id,
+                message.as_deref().unwrap_or("-")
+            );
+        }
+        self.emit_progress(transfer, state, transferred, path, message);
+    }
+
+    pub fn emit_progress(
+        &self,
+        transfer: &Transfer,
+        state: TransferState,
+        transferred: u64,
+        path: Option<String>,
+        message: Option<String>,
+    ) {
+        let progress = TransferProgress {
+            transfer_id: transfer.offer.transfer_id.clone(),
+            state,
+            transferred,
+            total: transfer.offer.size,
+            path,
+            message,
+        };
+        self.emit(&transfer.offer.sender, "synclan://transfer:progress", &progress);
+        self.emit(&transfer.offer.receiver, "synclan://transfer:progress", &progress);
+    }
+
+    fn set_state(&self, transfer: &Transfer, from: TransferState, to: TransferState) -> Result<()> {
+        let mut state = transfer.state.lock();
+        if *state != from {
+            bail!("The transfer is {:?}", *state);
+        }
+        *state = to;
+        Ok(())
+    }
+
+    fn emit<T: Serialize + ?Sized>(&self, device_id: &str, event: &'static str, data: &T) {
+        if let Some(io) = self.io.get()
+            && let Some(client) = self.clients.get(device_id)
+            && let Some(ns) = io.of("/socket")
+            && let Some(socket) = ns.get_socket(client.socket_id)
+        {
+            socket.emit(event, data).ok();
+        }
+    }
+}
+
+/// Throttles the `synclan://transfer:progress` events of a running relay
+pub struct ProgressThrottle {
+    last: Instant,
+}
+
+impl Default for ProgressThrottle {
+    fn default() -> Self {
+        Self { last: Instant::now() }
+    }
+}
+
+impl ProgressThrottle {
+    pub fn ready(&mut self) -> bool {
+        if self.last.elapsed() < PROGRESS_INTERVAL {
+            return false;
+        }
+        self.last = Instant::now();
+        true
+    }
+}
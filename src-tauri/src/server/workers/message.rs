@@ -1,6 +1,13 @@
 use crate::{
-    module::message::{Message, MessageAck},
-    server::events::{AckResponse, store::Clients},
+    module::{
+        device::Device,
+        message::{Message, MessageAck},
+    },
+    server::{
+        events::{AckResponse, store::Clients},
+        federation,
+        signed_url::UrlSigner,
+    },
 };
 use anyhow::Result;
 use apalis::prelude::{Data, WorkerContext};
@@ -14,6 +21,7 @@ impl MessageWorker {
     /// Executes a single message delivery job.
     ///
     /// # Behavior
+    /// - If the receiver is a device of a paired host, forwards the message to that host.
     /// - Checks whether the receiver is online.
     /// - If online, retrieves the corresponding socket.
     /// - Emits an `"on-message"` event with a 6-second timeout.
@@ -27,15 +35,26 @@ impl MessageWorker {
     /// * `Ok(())` if the job completed successfully.
     /// * `Err` if the message could not be delivered or ACK failed.
     pub async fn send_message(
-        message: Message,
+        mut message: Message,
         io: Data<SocketIo>,
         _worker: WorkerContext,
         clients: Data<Clients>,
     ) -> Result<()> {
+        if let Some(home_host) = Device::get_by_id(&message.receiver)
+            .await?
+            .and_then(|receiver| receiver.home_host)
+        {
+            // retried with backoff while the paired host is unreachable
+            federation::forward(&home_host, &message).await?;
+            MessageAck::new(message.receiver, message.id).received().await?;
+            return Ok(());
+        }
+
         if let Some(client) = clients.get(&message.receiver)
             && let Some(ns) = io.of("/socket")
             && let Some(socket) = ns.get_socket(client.socket_id)
         {
+            UrlSigner::global().sign_messages([&mut message]).await?;
             let response = socket
                 .timeout(Duration::from_secs(6))
                 .emit_with_ack::<_, AckResponse<()>>("synclan://message", &message)?
@@ -14,12 +14,20 @@ use apalis_sqlite::{CompactType, SqliteStorage, fetcher::SqliteFetcher};
 use futures::FutureExt as _;
 use parking_lot::Mutex;
 use socketioxide::SocketIo;
+use sqlx::{Pool, Sqlite};
 use std::{sync::Arc, time::Duration};
 use tokio_util::sync::CancellationToken;
 
 mod message;
+pub mod thumbnail;
+pub mod upload;
+
+pub use thumbnail::ThumbnailJob;
+pub use upload::{UploadMergeJob, UploadSessions};
 
 pub type MessageBackend = SqliteStorage<Message, JsonCodec<CompactType>, SqliteFetcher>;
+pub type UploadBackend = SqliteStorage<UploadMergeJob, JsonCodec<CompactType>, SqliteFetcher>;
+pub type ThumbnailBackend = SqliteStorage<ThumbnailJob, JsonCodec<CompactType>, SqliteFetcher>;
 
 pub struct WorkerMonitor {
     shutdown_token: Arc<Mutex<Option<CancellationToken>>>,
@@ -34,7 +42,15 @@ impl WorkerMonitor {
         }
     }
 
-    pub async fn run(&self, message_backend: MessageBackend, io: SocketIo, clients: Clients) -> anyhow::Result<()> {
+    pub async fn run(
+        &self,
+        message_backend: MessageBackend,
+        upload_backend: UploadBackend,
+        thumbnail_backend: ThumbnailBackend,
+        io: SocketIo,
+        clients: Clients,
+        upload_sessions: UploadSessions,
+    ) -> anyhow::Result<()> {
         let token = CancellationToken::new();
         *self.shutdown_token.lock() = Some(token.clone());
 
@@ -46,6 +62,10 @@ impl WorkerMonitor {
         )?
         .make_backoff();
 
+        let upload_io = io.clone();
+        let upload_clients = clients.clone();
+        let upload_thumbnail_backend = thumbnail_backend.clone();
+
         Monitor::new()
             .register(move |_run_id| {
                 WorkerBuilder::new("synclan-message-dispatcher")
@@ -64,6 +84,28 @@ impl WorkerMonitor {
                     .data(clients.clone())
                     .build(message::MessageWorker::send_message)
             })
+            .register(move |_run_id| {
+                WorkerBuilder::new("synclan-upload-merger")
+                    .backend(upload_backend.clone())
+                    .enable_tracing()
+                    .catch_panic()
+                    // merging is disk bound, keep it low so other requests are not starved
+                    .concurrency(2)
+                    .data(upload_io.clone())
+                    .data(upload_clients.clone())
+                    .data(upload_sessions.clone())
+                    .data(upload_thumbnail_backend.clone())
+                    .build(upload::UploadWorker::merge_chunks)
+            })
+            .register(move |_run_id| {
+                WorkerBuilder::new("synclan-thumbnail-generator")
+                    .backend(thumbnail_backend.clone())
+                    .enable_tracing()
+                    .catch_panic()
+                    // decoding is cpu bound
+                    .concurrency(2)
+                    .build(thumbnail::ThumbnailWorker::generate)
+            })
             .on_event(|ctx, evt| {
                 let name = ctx.name();
                 match evt {
@@ -98,3 +140,101 @@ impl WorkerMonitor {
         }
     }
 }
+
+/// Jobs dropped by [`purge_device_jobs`]
+#[derive(Debug, Default)]
+pub struct PurgedJobs {
+    pub count: usize,
+    /// Uploads whose merge was dropped, their chunks and reservations are still there
+    pub upload_ids: Vec<String>,
+}
+
+/// Drops the jobs of a device that have not started yet: messages it would receive and
+/// merges of its uploads. The messages it sent are still delivered to the other devices.
+/// Jobs are stored by apalis as JSON in the `Jobs` table.
+pub async fn purge_device_jobs(db_pool: &Pool<Sqlite>, device_id: &str) -> anyhow::Result<PurgedJobs> {
+    let upload_ids: Vec<Option<String>> = sqlx::query_scalar(
+        r#"
+        DELETE FROM Jobs
+        WHERE status IN ('Pending', 'Queued')
+            AND json_valid(CAST(job AS TEXT))
+            AND $1 IN (
+                json_extract(CAST(job AS TEXT), '$.receiver'),
+                json_extract(CAST(job AS TEXT), '$.deviceId')
+            )
+        RETURNING json_extract(CAST(job AS TEXT), '$.uploadId')
+        "#,
+    )
+    .bind(device_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(PurgedJobs {
+        count: upload_ids.len(),
+        upload_ids: upload_ids.into_iter().flatten().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::message::MessageType;
+    use apalis::prelude::TaskSink as _;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn message(sender: &str, receiver: &str) -> Message {
+        Message {
+            id: None,
+            uuid: format!("{sender}-{receiver}"),
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            r#type: MessageType::Text,
+            content: None,
+            plain_content: Some("hello".to_string()),
+            extra: None,
+            created_at: None,
+            updated_at: None,
+            attachment_urls: None,
+            thumbnail_urls: None,
+        }
+    }
+
+    /// Its own database, apalis stores the jobs the way the query expects
+    #[tokio::test]
+    async fn test_purge_device_jobs() {
+        let db_pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        SqliteStorage::setup(&db_pool).await.unwrap();
+        let mut messages: MessageBackend =
+            SqliteStorage::new_with_config(&db_pool, &apalis_sqlite::Config::new("test-messages"));
+        let mut uploads: UploadBackend =
+            SqliteStorage::new_with_config(&db_pool, &apalis_sqlite::Config::new("test-uploads"));
+
+        messages.push(message("alice", "bob")).await.unwrap();
+        messages.push(message("bob", "alice")).await.unwrap();
+        for (upload_id, device_id) in [("bob-upload", "bob"), ("alice-upload", "alice")] {
+            let job = UploadMergeJob {
+                upload_id: upload_id.to_string(),
+                device_id: device_id.to_string(),
+            };
+            uploads.push(job).await.unwrap();
+        }
+
+        // the message to bob and bob's upload, the one bob sent still goes out
+        let purged = purge_device_jobs(&db_pool, "bob").await.unwrap();
+        assert_eq!(purged.count, 2);
+        assert_eq!(purged.upload_ids, ["bob-upload"]);
+
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM Jobs")
+            .fetch_one(&db_pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 2);
+        assert_eq!(purge_device_jobs(&db_pool, "bob").await.unwrap().count, 0);
+    }
+}
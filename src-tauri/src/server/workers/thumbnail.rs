@@ -0,0 +1,185 @@
+use crate::{config::Config, logging, module::attachment::Attachment, utils::logging::Type};
+use anyhow::{Result, anyhow};
+use apalis::prelude::WorkerContext;
+use image::{ImageReader, codecs::jpeg::JpegEncoder};
+use mime_guess::mime;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, process::Stdio};
+use tokio::{fs, process::Command};
+
+/// Longest side of a thumbnail in pixels, matches the max media width of the chat view.
+const THUMBNAIL_SIZE: u32 = 640;
+
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Thumbnails are stored beside the attachment: `2025-06-04/.thumbnails/video.mp4.jpg`
+const THUMBNAIL_DIR: &str = ".thumbnails";
+
+/// Background job that creates the thumbnail of an uploaded image or video.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailJob {
+    /// Attachment path relative to the file upload directory
+    pub path: String,
+}
+
+impl ThumbnailJob {
+    /// `None` if no preview can be generated for the MIME type sniffed at upload
+    pub fn for_file(path: &str, mime: &str) -> Option<Self> {
+        media_kind(mime).map(|_| Self { path: path.to_string() })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MediaKind {
+    Image,
+    Video,
+}
+
+/// By the sniffed MIME type, the extension is chosen by the uploader
+fn media_kind(mime: &str) -> Option<MediaKind> {
+    let mime = mime.parse::<mime::Mime>().ok()?;
+    if mime.type_() == mime::IMAGE && mime.subtype() != mime::SVG {
+        Some(MediaKind::Image)
+    } else if mime.type_() == mime::VIDEO {
+        Some(MediaKind::Video)
+    } else {
+        None
+    }
+}
+
+/// `2025-06-04/a.png` -> `2025-06-04/.thumbnails/a.png.jpg`
+fn thumbnail_path(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, name)) => format!("{dir}/{THUMBNAIL_DIR}/{name}.jpg"),
+        None => format!("{THUMBNAIL_DIR}/{path}.jpg"),
+    }
+}
+
+pub struct ThumbnailWorker;
+
+impl ThumbnailWorker {
+    /// Executes a single thumbnail job.
+    ///
+    /// # Behavior
+    /// - Images are decoded and scaled down to [`THUMBNAIL_SIZE`].
+    /// - Videos get a poster frame extracted by `ffmpeg`, skipped if `ffmpeg` is not installed.
+    /// - The thumbnail path is stored with the attachment record.
+    ///
+    /// A file that cannot be decoded is only logged, the job is not retried.
+    pub async fn generate(job: ThumbnailJob, _worker: WorkerContext) -> Result<()> {
+        if let Err(err) = Self::create(&job).await {
+            logging!(warn, Type::Server, "Failed to create thumbnail of {}: {err}", job.path);
+        }
+        Ok(())
+    }
+
+    async fn create(job: &ThumbnailJob) -> Result<()> {
+        let Some(attachment) = Attachment::get_by_path(&job.path).await? else {
+            return Ok(());
+        };
+        let Some(kind) = attachment.mime.as_deref().and_then(media_kind) else {
+            return Ok(());
+        };
+
+        let synclan = Config::synclan().await.data_arc();
+        let upload_dir = synclan
+            .file_upload_dir
+            .as_ref()
+            .ok_or_else(|| anyhow!("File upload directory is not configured."))?;
+
+        let source = Path::new(upload_dir).join(&job.path);
+        let thumbnail = thumbnail_path(&job.path);
+        let target = Path::new(upload_dir).join(&thumbnail);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        match kind {
+            MediaKind::Image => {
+                let (source, target) = (source.clone(), target.clone());
+                tokio::task::spawn_blocking(move || Self::resize_image(&source, &target)).await??;
+            },
+            MediaKind::Video => {
+                if !Self::extract_poster(&source, &target).await? {
+                    return Ok(());
+                }
+            },
+        }
+
+        // counted towards the quota of the uploader
+        let size = fs::metadata(&target).await?.len();
+        Attachment::set_thumbnail(&job.path, &thumbnail, size).await?;
+
+        Ok(())
+    }
+
+    fn resize_image(source: &Path, target: &Path) -> Result<()> {
+        let image = ImageReader::open(source)?.with_guessed_format()?.decode()?;
+        // never upscale, `thumbnail` keeps the aspect ratio
+        let image = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+            image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        } else {
+            image
+        };
+
+        let file = std::fs::File::create(target)?;
+        let mut encoder = JpegEncoder::new_with_quality(std::io::BufWriter::new(file), THUMBNAIL_QUALITY);
+        // jpeg has no alpha channel
+        encoder.encode_image(&image.to_rgb8())?;
+
+        Ok(())
+    }
+
+    /// Extracts a representative frame with `ffmpeg`.
+    ///
+    /// Returns `false` if `ffmpeg` is not available.
+    async fn extract_poster(source: &Path, target: &Path) -> Result<bool> {
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args(["-v", "error", "-y", "-i"])
+            .arg(source)
+            .arg("-vf")
+            .arg(format!(
+                "thumbnail,scale=w={THUMBNAIL_SIZE}:h={THUMBNAIL_SIZE}:force_original_aspect_ratio=decrease"
+            ))
+            .args(["-frames:v", "1"])
+            .arg(target)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        #[cfg(windows)]
+        {
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+
+        let output = match cmd.output().await {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        if !output.status.success() {
+            return Err(anyhow!(
+                "ffmpeg exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_kind() {
+        assert_eq!(media_kind("image/png"), Some(MediaKind::Image));
+        assert_eq!(media_kind("video/mp4"), Some(MediaKind::Video));
+        assert_eq!(media_kind("image/svg+xml"), None);
+        assert_eq!(media_kind("application/pdf"), None);
+        assert_eq!(media_kind("not a mime"), None);
+    }
+}
@@ -0,0 +1,658 @@
+use super::{ThumbnailBackend, ThumbnailJob};
+use crate::{
+    config::Config,
+    logging, logging_error,
+    module::attachment::Attachment,
+    server::{events::store::Clients, exception::HttpException},
+    utils::{logging::Type, sniff},
+};
+use anyhow::{Context, Result, anyhow};
+use apalis::prelude::{Data, TaskSink as _, WorkerContext};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use socketioxide::SocketIo;
+use std::{
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs,
+    io::{AsyncSeekExt as _, AsyncWriteExt as _},
+    sync::Mutex,
+};
+use utoipa::ToSchema;
+
+/// Name of the file that the chunks are appended to while the upload is in progress.
+const PART_FILE: &str = "data.part";
+
+/// Name of the upload session metadata file.
+const META_FILE: &str = "meta.json";
+
+/// Name of the bundle manifest, kept in the bundle session directory and in the bundle folder.
+pub const BUNDLE_MANIFEST: &str = ".bundle.json";
+
+/// How long the final state of an upload can still be polled
+const FINISHED_PROGRESS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A chunk upload without a new chunk for this long is abandoned
+const STALE_SESSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often abandoned chunk uploads are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Background job that finalizes a chunked upload.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadMergeJob {
+    pub upload_id: String,
+    /// The device that started the upload, progress events are sent to it.
+    pub device_id: String,
+}
+
+/// Upload session metadata, stored as `chunks/{upload_id}/meta.json`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadMeta {
+    pub upload_id: String,
+    pub name: String,
+    pub size: u64,
+    pub chunk_size: u64,
+    pub total_chunks: u32,
+    /// Number of leading chunks already appended to `data.part`.
+    #[serde(default)]
+    pub merged_chunks: u32,
+    /// The device that started the upload
+    #[serde(default)]
+    pub device_id: String,
+    /// Destination relative to the file upload directory, set for the files of a bundle.
+    /// Defaults to `{today}/{name}`.
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+impl UploadMeta {
+    pub async fn load(chunk_dir: &Path) -> Result<Self> {
+        let content = fs::read_to_string(chunk_dir.join(META_FILE)).await?;
+        serde_json::from_str(&content).context("Invalid upload metadata")
+    }
+
+    pub async fn save(&self, chunk_dir: &Path) -> Result<()> {
+        fs::write(chunk_dir.join(META_FILE), serde_json::to_string(self)?).await?;
+        Ok(())
+    }
+}
+
+/// A folder uploaded as a single operation, every file is a chunked upload of its own.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub bundle_id: String,
+    /// Folder name
+    pub name: String,
+    /// Folder path relative to the file upload directory, e.g. `2025-06-04/project`
+    pub path: String,
+    /// Device that uploads the bundle
+    pub device_id: String,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleFile {
+    /// Path relative to the bundle folder, e.g. `src/main.rs`
+    pub path: String,
+    pub size: u64,
+    pub upload_id: String,
+}
+
+impl BundleManifest {
+    pub async fn load(dir: &Path) -> Result<Self> {
+        let content = fs::read_to_string(dir.join(BUNDLE_MANIFEST)).await?;
+        serde_json::from_str(&content).context("Invalid bundle manifest")
+    }
+
+    pub async fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(BUNDLE_MANIFEST), serde_json::to_string(self)?).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadState {
+    Uploading,
+    Merging,
+    Completed,
+    Failed,
+}
+
+/// Progress of a chunked upload, emitted as `synclan://upload:progress`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadProgress {
+    pub upload_id: String,
+    pub state: UploadState,
+    pub merged_chunks: u32,
+    pub total_chunks: u32,
+    /// Final file path, available once the upload is completed
+    pub path: Option<String>,
+    pub message: Option<String>,
+    /// The device that started the upload, only it may poll the progress
+    #[serde(skip)]
+    pub device_id: String,
+}
+
+impl UploadProgress {
+    pub fn new(meta: &UploadMeta, state: UploadState) -> Self {
+        Self {
+            upload_id: meta.upload_id.clone(),
+            state,
+            merged_chunks: meta.merged_chunks,
+            total_chunks: meta.total_chunks,
+            path: None,
+            message: None,
+            device_id: meta.device_id.clone(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.state, UploadState::Completed | UploadState::Failed)
+    }
+}
+
+/// In-memory state of the running chunked uploads.
+#[derive(Clone, Debug, Default)]
+pub struct UploadSessions {
+    /// Progress and when it was last updated
+    progress: Arc<DashMap<String, (UploadProgress, Instant)>>,
+    locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
+    /// Disk space reserved by the unfinished uploads: `upload_id -> (device_id, size)`
+    reserved: Arc<DashMap<String, (String, u64)>>,
+    /// Serializes the quota checks, so two uploads can't both take the last free bytes
+    quota: Arc<Mutex<()>>,
+}
+
+/// Storage limits of the upload directory, from the `upload_*` settings
+#[derive(Debug, Clone, Default)]
+pub struct UploadLimits {
+    pub upload_dir: String,
+    /// MB per device, `None` or `0` for no quota
+    pub device_quota: Option<u64>,
+    /// MB of all devices together, `None` or `0` for no quota
+    pub total_quota: Option<u64>,
+    /// MB that must stay free on the upload directory's disk
+    pub min_free_space: u64,
+}
+
+impl UploadSessions {
+    pub fn get(&self, upload_id: &str) -> Option<UploadProgress> {
+        self.progress.get(upload_id).map(|r| r.value().0.clone())
+    }
+
+    /// Finishing an upload also drops the finished ones that nobody polled for a while
+    pub fn set(&self, progress: UploadProgress) {
+        let now = Instant::now();
+        if progress.is_finished() {
+            self.progress
+                .retain(|_, (progress, updated)| !progress.is_finished() || now - *updated < FINISHED_PROGRESS_TTL);
+        }
+        self.progress.insert(progress.upload_id.clone(), (progress, now));
+    }
+
+    /// Per-upload lock, serializes all writes to the `data.part` file.
+    pub fn lock(&self, upload_id: &str) -> Arc<Mutex<()>> {
+        self.locks.entry(upload_id.to_string()).or_default().clone()
+    }
+
+    /// Reserves the declared size of an upload until it is released, so quotas also cover
+    /// the uploads that are still in progress. Fails if the device quota, the total quota
+    /// or the free disk space would be exceeded, counting the other reservations as well.
+    pub async fn try_reserve(
+        &self,
+        upload_id: &str,
+        device_id: &str,
+        size: u64,
+        limits: &UploadLimits,
+    ) -> Result<(), HttpException> {
+        let _guard = self.quota.lock().await;
+        self.ensure_fits(device_id, size, limits).await?;
+        self.reserved
+            .insert(upload_id.to_string(), (device_id.to_string(), size));
+        Ok(())
+    }
+
+    /// The check of [`Self::try_reserve`] without reserving anything, for an early answer
+    pub async fn check(&self, device_id: &str, size: u64, limits: &UploadLimits) -> Result<(), HttpException> {
+        let _guard = self.quota.lock().await;
+        self.ensure_fits(device_id, size, limits).await
+    }
+
+    async fn ensure_fits(&self, device_id: &str, size: u64, limits: &UploadLimits) -> Result<(), HttpException> {
+        const MB: u64 = 1024 * 1024;
+
+        if let Some(quota) = limits.device_quota.filter(|quota| *quota > 0) {
+            let used = Attachment::device_usage(device_id).await? + self.reserved(Some(device_id));
+            if used + size > quota * MB {
+                return Err(HttpException::PayloadTooLargeException(Some(format!(
+                    "Device storage quota of {quota} MB exceeded."
+                ))));
+            }
+        }
+
+        if let Some(quota) = limits.total_quota.filter(|quota| *quota > 0) {
+            let used = Attachment::total_usage().await? + self.reserved(None);
+            if used + size > quota * MB {
+                return Err(HttpException::PayloadTooLargeException(Some(format!(
+                    "Total storage quota of {quota} MB exceeded."
+                ))));
+            }
+        }
+
+        fs::create_dir_all(&limits.upload_dir).await?;
+        let available = fs4::available_space(&limits.upload_dir)?;
+        // the unfinished uploads will take their share of the free space as well
+        let pending = self.reserved(None);
+        if available < size + pending + limits.min_free_space * MB {
+            return Err(HttpException::PayloadTooLargeException(Some(
+                "Not enough free disk space.".into(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Bytes reserved by the unfinished uploads, of one device or of all devices
+    pub fn reserved(&self, device_id: Option<&str>) -> u64 {
+        self.reserved
+            .iter()
+            .filter(|r| device_id.is_none_or(|id| r.value().0 == id))
+            .map(|r| r.value().1)
+            .sum()
+    }
+
+    /// Gives the reservation of a finished upload back
+    pub fn release(&self, upload_id: &str) {
+        self.locks.remove(upload_id);
+        self.reserved.remove(upload_id);
+    }
+
+    /// Drops an upload whose merge job was deleted: its chunks and its reservation
+    pub async fn discard(&self, upload_dir: &str, upload_id: &str) -> Result<()> {
+        let lock = self.lock(upload_id);
+        let _guard = lock.lock().await;
+        match fs::remove_dir_all(chunk_dir(upload_dir, upload_id)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {},
+        }
+        self.release(upload_id);
+        self.progress.remove(upload_id);
+        Ok(())
+    }
+
+    /// Deletes the session directories under `chunks/` that haven't changed for `max_age`,
+    /// bundle sessions included, and gives their reservations back. Also covers the
+    /// directories left over from before a restart, whose reservations are gone already.
+    pub async fn sweep_stale(&self, upload_dir: &str, max_age: Duration) -> Result<usize> {
+        let chunks_dir = Path::new(upload_dir).join("chunks");
+        let mut entries = match fs::read_dir(&chunks_dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut swept = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(upload_id) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            // a merge is queued or running, it removes the directory itself
+            if self
+                .get(&upload_id)
+                .is_some_and(|progress| progress.state == UploadState::Merging)
+            {
+                continue;
+            }
+            // adding or appending a chunk changes the directory
+            let modified = entry.metadata().await?.modified()?;
+            if modified.elapsed().unwrap_or_default() < max_age {
+                continue;
+            }
+
+            let lock = self.lock(&upload_id);
+            let _guard = lock.lock().await;
+            fs::remove_dir_all(entry.path()).await?;
+            self.release(&upload_id);
+            self.progress.remove(&upload_id);
+            swept += 1;
+        }
+
+        Ok(swept)
+    }
+}
+
+/// Sweeps abandoned chunk uploads until the server stops, see [`UploadSessions::sweep_stale`]
+pub async fn sweep_stale_sessions(sessions: UploadSessions) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let synclan = Config::synclan().await.data_arc();
+        let Some(upload_dir) = synclan.file_upload_dir.as_ref() else {
+            continue;
+        };
+        match sessions.sweep_stale(upload_dir, STALE_SESSION_TTL).await {
+            Ok(0) => {},
+            Ok(swept) => logging!(info, Type::Server, "Removed {swept} abandoned chunk uploads"),
+            Err(err) => logging!(error, Type::Server, "Failed to remove abandoned chunk uploads: {err}"),
+        }
+    }
+}
+
+/// A file name chosen by a client, it must stay a single visible entry of its folder
+pub fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// Claims `name` in `dir` by creating an empty file, or `name (1).ext`, `name (2).ext`...
+/// when it is taken. The caller moves its file over the returned path.
+pub async fn claim_file_name(dir: &Path, name: &str) -> io::Result<(String, PathBuf)> {
+    if !is_valid_file_name(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid file name {name}"),
+        ));
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) => (stem, Some(extension)),
+        None => (name, None),
+    };
+
+    let mut suffix = 0;
+    loop {
+        let candidate = match (suffix, extension) {
+            (0, _) => name.to_string(),
+            (_, Some(extension)) => format!("{stem} ({suffix}).{extension}"),
+            (_, None) => format!("{stem} ({suffix})"),
+        };
+        let path = dir.join(&candidate);
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+            Ok(_) => return Ok((candidate, path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// `{file_upload_dir}/chunks/{upload_id}`
+pub fn chunk_dir(upload_dir: &str, upload_id: &str) -> PathBuf {
+    Path::new(upload_dir).join("chunks").join(upload_id)
+}
+
+/// Appends every consecutive chunk that has already arrived to `data.part`.
+///
+/// Appended chunks are removed, and the number of merged chunks is persisted in the
+/// session metadata. The part file is truncated to the expected length first, so an
+/// interrupted append can safely be repeated.
+///
+/// The caller must hold the lock returned by [`UploadSessions::lock`].
+pub async fn append_ready_chunks(
+    chunk_dir: &Path,
+    meta: &mut UploadMeta,
+    mut on_chunk: impl FnMut(&UploadMeta),
+) -> Result<()> {
+    if meta.merged_chunks >= meta.total_chunks {
+        return Ok(());
+    }
+
+    let mut output = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(chunk_dir.join(PART_FILE))
+        .await?;
+    let offset = meta.merged_chunks as u64 * meta.chunk_size;
+    output.set_len(offset).await?;
+    output.seek(SeekFrom::Start(offset)).await?;
+
+    while meta.merged_chunks < meta.total_chunks {
+        let chunk_path = chunk_dir.join(format!("{}.chunk", meta.merged_chunks));
+        if !fs::try_exists(&chunk_path).await.unwrap_or(false) {
+            break;
+        }
+
+        let mut chunk_file = fs::File::open(&chunk_path).await?;
+        tokio::io::copy(&mut chunk_file, &mut output).await?;
+        output.flush().await?;
+
+        meta.merged_chunks += 1;
+        meta.save(chunk_dir).await?;
+        fs::remove_file(&chunk_path).await?;
+
+        on_chunk(meta);
+    }
+
+    Ok(())
+}
+
+pub struct UploadWorker;
+
+impl UploadWorker {
+    /// Executes a single chunk merge job.
+    ///
+    /// # Behavior
+    /// - Appends the remaining chunks to the part file, emitting a
+    ///   `"synclan://upload:progress"` event after each chunk.
+    /// - Moves the merged file into today's upload directory, or into its bundle folder.
+    /// - Removes the upload session directory.
+    /// - Queues a [`ThumbnailJob`] for images and videos, except for bundle files.
+    ///
+    /// The final state (`completed` or `failed`) is kept in [`UploadSessions`],
+    /// so the client can also poll for it.
+    pub async fn merge_chunks(
+        job: UploadMergeJob,
+        io: Data<SocketIo>,
+        _worker: WorkerContext,
+        clients: Data<Clients>,
+        sessions: Data<UploadSessions>,
+        thumbnails: Data<ThumbnailBackend>,
+    ) -> Result<()> {
+        let progress = match Self::merge(&job, &io, &clients, &sessions, &thumbnails).await {
+            Ok(progress) => progress,
+            Err(err) => {
+                logging!(error, Type::Server, "Failed to merge upload {}: {err}", job.upload_id);
+                let mut progress = sessions.get(&job.upload_id).unwrap_or(UploadProgress {
+                    upload_id: job.upload_id.clone(),
+                    state: UploadState::Failed,
+                    merged_chunks: 0,
+                    total_chunks: 0,
+                    path: None,
+                    message: None,
+                    device_id: job.device_id.clone(),
+                });
+                progress.state = UploadState::Failed;
+                progress.message = Some(err.to_string());
+                progress
+            },
+        };
+
+        sessions.set(progress.clone());
+        sessions.release(&job.upload_id);
+        // a failed merge is not retried, the chunks are gone or incomplete
+        Self::emit_progress(&io, &clients, &job.device_id, &progress);
+
+        Ok(())
+    }
+
+    async fn merge(
+        job: &UploadMergeJob,
+        io: &SocketIo,
+        clients: &Clients,
+        sessions: &UploadSessions,
+        thumbnails: &ThumbnailBackend,
+    ) -> Result<UploadProgress> {
+        let synclan = Config::synclan().await.data_arc();
+        let upload_dir = synclan
+            .file_upload_dir
+            .as_ref()
+            .ok_or_else(|| anyhow!("File upload directory is not configured."))?;
+
+        let chunk_dir = chunk_dir(upload_dir, &job.upload_id);
+        let lock = sessions.lock(&job.upload_id);
+        let _guard = lock.lock().await;
+
+        let mut meta = UploadMeta::load(&chunk_dir).await?;
+        append_ready_chunks(&chunk_dir, &mut meta, |meta| {
+            let progress = UploadProgress::new(meta, UploadState::Merging);
+            sessions.set(progress.clone());
+            Self::emit_progress(io, clients, &job.device_id, &progress);
+        })
+        .await?;
+
+        if meta.merged_chunks < meta.total_chunks {
+            return Err(anyhow!("Missing chunk {}.", meta.merged_chunks));
+        }
+        // empty files of a bundle have no chunks at all
+        if meta.total_chunks == 0 {
+            fs::File::create(chunk_dir.join(PART_FILE)).await?;
+        }
+
+        // the first chunk was checked on arrival, the settings may have changed since
+        let mime = sniff::sniff_file(&chunk_dir.join(PART_FILE), &meta.name).await?;
+        if !synclan.is_upload_mime_allowed(&mime) {
+            fs::remove_dir_all(&chunk_dir).await?;
+            return Err(anyhow!("File type {mime} is not allowed."));
+        }
+
+        // the folder of a bundle is new, anything else must not replace an existing file
+        let (path, final_path) = match &meta.target {
+            Some(target) => {
+                let final_path = Path::new(upload_dir).join(target);
+                if let Some(final_dir) = final_path.parent() {
+                    fs::create_dir_all(final_dir).await?;
+                }
+                (target.clone(), final_path)
+            },
+            None => {
+                let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                let final_dir = Path::new(upload_dir).join(&today);
+                fs::create_dir_all(&final_dir).await?;
+                let (name, final_path) = claim_file_name(&final_dir, &meta.name).await?;
+                (format!("{today}/{name}"), final_path)
+            },
+        };
+        fs::rename(chunk_dir.join(PART_FILE), &final_path).await?;
+
+        Attachment {
+            path: path.clone(),
+            name: meta.name.clone(),
+            size: meta.size as i64,
+            device_id: job.device_id.clone(),
+            mime: Some(mime.clone()),
+            ..Attachment::default()
+        }
+        .create()
+        .await?;
+
+        //
+        // Remove temporary chunks
+        //
+        fs::remove_dir_all(&chunk_dir).await?;
+
+        if meta.target.is_none()
+            && let Some(thumbnail_job) = ThumbnailJob::for_file(&path, &mime)
+        {
+            logging_error!(Type::Server, thumbnails.clone().push(thumbnail_job).await);
+        }
+
+        let mut progress = UploadProgress::new(&meta, UploadState::Completed);
+        progress.path = Some(path);
+
+        Ok(progress)
+    }
+
+    fn emit_progress(io: &SocketIo, clients: &Clients, device_id: &str, progress: &UploadProgress) {
+        if let Some(client) = clients.get(device_id)
+            && let Some(ns) = io.of("/socket")
+            && let Some(socket) = ns.get_socket(client.socket_id)
+        {
+            socket.emit("synclan://upload:progress", progress).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_claim_file_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let claim = |name: &'static str| {
+            let dir = dir.path().to_path_buf();
+            async move { claim_file_name(&dir, name).await.unwrap().0 }
+        };
+
+        assert_eq!(claim("photo.png").await, "photo.png");
+        assert_eq!(claim("photo.png").await, "photo (1).png");
+        assert_eq!(claim("photo.png").await, "photo (2).png");
+        assert_eq!(claim("README").await, "README");
+        assert_eq!(claim("README").await, "README (1)");
+
+        for name in ["", ".env", "../photo.png", "a/b.png", "a\\b.png", "photo..png"] {
+            assert!(claim_file_name(dir.path(), name).await.is_err(), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweep_stale() {
+        let upload_dir = tempfile::tempdir().unwrap();
+        let upload_dir = upload_dir.path().to_str().unwrap();
+        let sessions = UploadSessions::default();
+        let limits = UploadLimits {
+            upload_dir: upload_dir.into(),
+            ..UploadLimits::default()
+        };
+        for upload_id in ["abandoned", "merging"] {
+            fs::create_dir_all(chunk_dir(upload_dir, upload_id)).await.unwrap();
+            sessions.try_reserve(upload_id, "device", 100, &limits).await.unwrap();
+        }
+        let mut progress = UploadProgress::new(
+            &UploadMeta {
+                upload_id: "merging".into(),
+                name: "a.bin".into(),
+                size: 100,
+                chunk_size: 100,
+                total_chunks: 1,
+                merged_chunks: 0,
+                device_id: "device".into(),
+                target: None,
+            },
+            UploadState::Merging,
+        );
+        sessions.set(progress.clone());
+
+        // nothing is old enough yet
+        assert_eq!(
+            sessions
+                .sweep_stale(upload_dir, Duration::from_secs(3600))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(sessions.reserved(Some("device")), 200);
+
+        assert_eq!(sessions.sweep_stale(upload_dir, Duration::ZERO).await.unwrap(), 1);
+        assert!(!chunk_dir(upload_dir, "abandoned").exists());
+        assert!(chunk_dir(upload_dir, "merging").exists());
+        assert_eq!(sessions.reserved(Some("device")), 100);
+
+        progress.state = UploadState::Failed;
+        sessions.set(progress);
+        assert_eq!(sessions.sweep_stale(upload_dir, Duration::ZERO).await.unwrap(), 1);
+        assert_eq!(sessions.reserved(None), 0);
+    }
+}
@@ -67,6 +67,38 @@ impl DBManager {
         Ok(())
     }
 
+    /// In-memory database with the migrations applied, for tests that go through the models.
+    /// Shared by all tests of the binary, they must not rely on each other's rows.
+    #[cfg(test)]
+    pub async fn init_in_memory(&self) -> Result<()> {
+        use sqlx::{Connection as _, sqlite::SqliteConnection};
+        static INIT: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+
+        INIT.get_or_try_init(|| async {
+            // the database is gone with its last connection, and a pooled connection goes
+            // with the runtime of the test that used it last, this one is never closed
+            let options = SqliteConnectOptions::from_str("sqlite:file:synclan-test?mode=memory&cache=shared")?;
+            std::mem::forget(SqliteConnection::connect_with(&options).await?);
+
+            let db_pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?;
+            let migration_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/migrations");
+            let mut migrator = migrate::Migrator::new(std::path::Path::new(migration_dir)).await?;
+            migrator.dangerous_set_table_name("_synclan_sqlx_migrations");
+            migrator.run(&db_pool).await?;
+
+            self.db_pool
+                .set(db_pool)
+                .map_err(|_| anyhow!("database already initialized"))
+        })
+        .await?;
+        Ok(())
+    }
+
     pub fn db_pool(&self) -> Option<&Pool<Sqlite>> {
         self.db_pool.get()
     }
@@ -37,6 +37,7 @@ fn tr_en(key: &str) -> &str {
         "menu.open_dev_tools" => "Open Dev Tools",
         "menu.about" => "About Synclan",
         "menu.quit" => "Quit Synclan",
+        "menu.server_not_running" => "Server not running",
         _ => key,
     }
 }
@@ -51,6 +52,7 @@ fn tr_zh_cn(key: &str) -> &str {
         "menu.open_dev_tools" => "打开开发者工具",
         "menu.about" => "关于 Synclan",
         "menu.quit" => "退出 Synclan",
+        "menu.server_not_running" => "服务未运行",
         _ => key,
     }
 }
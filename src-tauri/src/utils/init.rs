@@ -1,6 +1,7 @@
 use crate::{
     config::{Config, ISynclan},
     logging,
+    module::audit_log::AuditLog,
     process::AsyncHandler,
     utils::{
         dirs::{self, PathBufExec as _},
@@ -12,23 +13,33 @@ use anyhow::Result;
 use chrono::{Local, TimeZone as _};
 use tokio::fs;
 
-pub async fn delete_log() -> Result<()> {
-    let log_dir = dirs::app_logs_dir()?;
-    if !log_dir.exists() {
-        return Ok(());
-    }
+/// Audit log entries kept whatever the retention, a flood of failed requests can't fill the disk
+const MAX_AUDIT_LOG_ROWS: i64 = 100_000;
 
+/// Days the logs are kept for with `auto_log_clean`, `None` to keep them
+async fn log_retention_days() -> Option<i64> {
     let auto_log_clean = {
         let synclan = Config::synclan().await;
         let synclan = synclan.data_arc();
         synclan.auto_log_clean.unwrap_or(0)
     };
-    let day = match auto_log_clean {
-        1 => 1,
-        2 => 7,
-        3 => 30,
-        4 => 90,
-        _ => return Ok(()),
+    match auto_log_clean {
+        1 => Some(1),
+        2 => Some(7),
+        3 => Some(30),
+        4 => Some(90),
+        _ => None,
+    }
+}
+
+pub async fn delete_log() -> Result<()> {
+    let log_dir = dirs::app_logs_dir()?;
+    if !log_dir.exists() {
+        return Ok(());
+    }
+
+    let Some(day) = log_retention_days().await else {
+        return Ok(());
     };
 
     logging!(info, Type::Setup, "try to delete log files, day: {day}");
@@ -69,6 +80,13 @@ pub async fn delete_log() -> Result<()> {
     Ok(())
 }
 
+/// Applies the log retention to the audit log as well, needs the database
+pub async fn delete_audit_log() -> Result<()> {
+    let deleted = AuditLog::prune(log_retention_days().await, MAX_AUDIT_LOG_ROWS).await?;
+    logging!(info, Type::Setup, "deleted {deleted} audit log entries");
+    Ok(())
+}
+
 async fn ensure_directories() -> Result<()> {
     let directories = [
         ("app_home", dirs::app_home_dir()?),
@@ -6,9 +6,11 @@ pub mod init;
 #[cfg(target_os = "linux")]
 pub mod linux;
 pub mod logging;
+pub mod network;
 pub mod resolve;
 #[cfg(target_os = "windows")]
 pub mod schtasks;
 pub mod singleton;
+pub mod sniff;
 pub mod tls;
 pub mod window_manager;
@@ -0,0 +1,142 @@
+use crate::{config::ISynclan, logging, utils::logging::Type};
+use anyhow::{Result, bail};
+use local_ip_address::{list_afinet_netifas, local_ip};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+/// Addresses the server listens on, from `listen_addresses`. Interface names expand to
+/// their addresses. The unspecified IPv6 address comes first, so on systems where it is
+/// dual-stack it takes the port before `0.0.0.0` does.
+pub fn listen_ips(synclan: &ISynclan) -> Result<Vec<IpAddr>> {
+    let entries: Vec<&str> = synclan
+        .listen_addresses
+        .iter()
+        .flatten()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    if entries.is_empty() {
+        return Ok(vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]);
+    }
+
+    let interfaces = list_afinet_netifas().unwrap_or_default();
+    let mut ips = vec![];
+    for entry in entries {
+        if let Ok(ip) = entry.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            ips.push(ip);
+            continue;
+        }
+        let found: Vec<IpAddr> = interfaces
+            .iter()
+            .filter(|(name, ip)| name == entry && !is_ipv6_link_local(ip))
+            .map(|(_, ip)| *ip)
+            .collect();
+        if found.is_empty() {
+            logging!(
+                warn,
+                Type::Server,
+                "No address on interface {entry}, not listening on it"
+            );
+        }
+        ips.extend(found);
+    }
+
+    let mut seen = HashSet::new();
+    ips.retain(|ip| seen.insert(*ip));
+    ips.sort_by_key(|ip| !(ip.is_ipv6() && ip.is_unspecified()));
+    if ips.is_empty() {
+        bail!("None of the listen addresses is available");
+    }
+    Ok(ips)
+}
+
+/// Addresses other devices can reach the server at, the interface with the default route
+/// first. Unspecified listen addresses expand to the interfaces of their family, loopback
+/// is only listed when nothing else is.
+pub fn reachable_ips(synclan: &ISynclan) -> Result<Vec<IpAddr>> {
+    let interfaces: Vec<IpAddr> = list_afinet_netifas()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, ip)| ip)
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified() && !is_ipv6_link_local(ip))
+        .collect();
+
+    let listen = listen_ips(synclan)?;
+    let mut ips = vec![];
+    for ip in &listen {
+        if ip.is_unspecified() {
+            ips.extend(
+                interfaces
+                    .iter()
+                    .filter(|interface| interface.is_ipv4() == ip.is_ipv4()),
+            );
+        } else if !ip.is_loopback() {
+            ips.push(*ip);
+        }
+    }
+    if ips.is_empty() {
+        ips = listen
+            .into_iter()
+            .map(|ip| match ip {
+                IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+                ip => ip,
+            })
+            .collect();
+    }
+
+    let mut seen = HashSet::new();
+    ips.retain(|ip| seen.insert(*ip));
+    if let Ok(primary) = local_ip() {
+        ips.sort_by_key(|ip| *ip != primary);
+    }
+    Ok(ips)
+}
+
+/// e.g. `https://192.168.1.2:53317`, `https://[fd00::2]:53317`
+pub fn server_urls(synclan: &ISynclan, port: u16) -> Result<Vec<String>> {
+    let protocol = if synclan.enable_encryption.unwrap_or(false) {
+        "https"
+    } else {
+        "http"
+    };
+
+    Ok(reachable_ips(synclan)?
+        .into_iter()
+        .map(|ip| format!("{protocol}://{}", SocketAddr::new(ip, port)))
+        .collect())
+}
+
+/// Link-local IPv6 addresses need a zone, they can't be used in a URL the browser accepts
+pub fn is_ipv6_link_local(ip: &IpAddr) -> bool {
+    matches!(ip, IpAddr::V6(ip) if (ip.segments()[0] & 0xffc0) == 0xfe80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_ips() {
+        let synclan = |addresses: &[&str]| ISynclan {
+            listen_addresses: Some(addresses.iter().map(ToString::to_string).collect()),
+            ..ISynclan::default()
+        };
+
+        assert_eq!(
+            listen_ips(&synclan(&[])).unwrap(),
+            vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]
+        );
+        assert_eq!(
+            listen_ips(&synclan(&["0.0.0.0", "[::]", "127.0.0.1", "::"])).unwrap(),
+            vec![
+                "::".parse::<IpAddr>().unwrap(),
+                "0.0.0.0".parse().unwrap(),
+                "127.0.0.1".parse().unwrap(),
+            ]
+        );
+        assert!(listen_ips(&synclan(&["no-such-interface0"])).is_err());
+    }
+}
@@ -23,6 +23,7 @@ pub fn init_work_dir_and_logger() -> Result<()> {
 pub fn resolve_server_setup_async() {
     AsyncHandler::spawn(|| async {
         logging_error!(Type::Server, db::DBManager::global().init().await);
+        logging_error!(Type::Server, init::delete_audit_log().await);
         logging_error!(Type::Server, server::start_http_server().await);
     });
 }
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use mime_guess::mime;
+use std::path::Path;
+use tokio::io::AsyncReadExt as _;
+
+/// Number of leading bytes inspected to detect the content type
+const SNIFF_LEN: usize = 8192;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+const HTML_TAGS: [&str; 8] = [
+    "<!doctype html",
+    "<html",
+    "<head",
+    "<body",
+    "<script",
+    "<iframe",
+    "<style",
+    "<title",
+];
+
+/// Detects the MIME type of an uploaded file from its content.
+///
+/// The file name is only a hint for plain text formats, which have no magic bytes.
+pub async fn sniff_file(path: &Path, name: &str) -> Result<String> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head).await?;
+
+    Ok(sniff_bytes(&head, name))
+}
+
+pub fn sniff_bytes(head: &[u8], name: &str) -> String {
+    let text = as_text(head);
+
+    // markup is what browsers would execute, detect it whatever the extension says
+    if let Some(text) = text {
+        let start = text.trim_start_matches('\u{feff}').trim_start().to_ascii_lowercase();
+        if HTML_TAGS.iter().any(|tag| start.starts_with(tag)) {
+            return "text/html".to_string();
+        }
+        if start.starts_with("<svg") || start.starts_with("<?xml") && start.contains("<svg") {
+            return "image/svg+xml".to_string();
+        }
+    }
+
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type().to_string();
+    }
+
+    if text.is_none() {
+        return OCTET_STREAM.to_string();
+    }
+
+    match mime_guess::from_path(name).first() {
+        Some(guess)
+            if guess.type_() == mime::TEXT || guess.subtype() == mime::JSON || guess.subtype() == mime::JAVASCRIPT =>
+        {
+            guess.essence_str().to_string()
+        },
+        _ => "text/plain".to_string(),
+    }
+}
+
+/// `Some` if the bytes look like UTF-8 text, the last character may be cut off
+fn as_text(head: &[u8]) -> Option<&str> {
+    if head.is_empty() || head.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        Err(err) if err.error_len().is_none() => std::str::from_utf8(&head[..err.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+/// Whether the type can be displayed by the browser without running scripts on our origin,
+/// everything else is served as a download.
+pub fn is_inline_safe(mime: &str) -> bool {
+    let mime = mime.to_ascii_lowercase();
+    match mime.split_once('/') {
+        Some(("image", subtype)) => !subtype.starts_with("svg"),
+        Some(("video" | "audio", _)) => true,
+        _ => matches!(mime.as_str(), "text/plain" | "application/pdf"),
+    }
+}
+
+/// `image/*` matches `image/png`, `*` or `*/*` match everything
+pub fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let mime = mime.to_ascii_lowercase();
+    match pattern.as_str() {
+        "*" | "*/*" => true,
+        _ => match pattern.strip_suffix("/*") {
+            Some(kind) => mime.split('/').next() == Some(kind),
+            None => pattern == mime,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_bytes() {
+        assert_eq!(sniff_bytes(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "a.txt"), "image/png");
+        assert_eq!(sniff_bytes(b"<!DOCTYPE html><p>hi</p>", "a.png"), "text/html");
+        assert_eq!(
+            sniff_bytes(b"<?xml version=\"1.0\"?><svg></svg>", "a.png"),
+            "image/svg+xml"
+        );
+        assert_eq!(sniff_bytes(b"hello world", "a.bin"), "text/plain");
+        assert_eq!(sniff_bytes(b"a,b\n1,2", "a.csv"), "text/csv");
+        assert_eq!(sniff_bytes(b"\0\x01\x02", "a.txt"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_mime_matches() {
+        assert!(mime_matches("image/*", "image/png"));
+        assert!(mime_matches("*", "text/html"));
+        assert!(mime_matches("Text/HTML", "text/html"));
+        assert!(!mime_matches("image/*", "text/html"));
+        assert!(!mime_matches("image/png", "image/jpeg"));
+    }
+
+    #[test]
+    fn test_is_inline_safe() {
+        assert!(is_inline_safe("image/png"));
+        assert!(is_inline_safe("video/mp4"));
+        assert!(!is_inline_safe("image/svg+xml"));
+        assert!(!is_inline_safe("text/html"));
+        assert!(!is_inline_safe("application/octet-stream"));
+    }
+}
@@ -1,48 +1,758 @@
-use crate::config::{Config, ISynclan};
-use anyhow::Result;
+use crate::{
+    config::{Config, ISynclan},
+    logging, singleton,
+    utils::{logging::Type, network::is_ipv6_link_local},
+};
+use anyhow::{Context, Result, anyhow, bail, ensure};
 use axum_server::tls_rustls::RustlsConfig;
-use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, SanType};
-use std::net::IpAddr;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use parking_lot::Mutex;
+use rcgen::{
+    BasicConstraints, CertificateParams, CidrSubnet, DistinguishedName, DnType, ExtendedKeyUsagePurpose,
+    GeneralSubtree, IsCa, Issuer, KeyPair, KeyUsagePurpose, NameConstraints, SanType,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair, UnparsedPublicKey},
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::{
+    collections::BTreeSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer, public_key::PublicKey};
 
-/// Self-signed certificate to support HTTPS with given IP.
-pub async fn build_rustls_config_with_ip(ip: &IpAddr) -> Result<RustlsConfig> {
+/// Lifetime of the local root CA
+const CA_VALIDITY: time::Duration = time::Duration::days(10 * 365);
+
+/// Lifetime of a leaf, short as it is re-issued automatically
+const LEAF_VALIDITY: time::Duration = time::Duration::days(90);
+
+/// A leaf expiring within this window is re-issued
+const LEAF_RENEW_BEFORE: time::Duration = time::Duration::days(14);
+
+/// How often the certificate is checked for renewal
+const CERT_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const CA_COMMON_NAME: &str = "Synclan Local CA";
+
+/// Networks the local CA may issue for, its key is of no use against public sites
+const PERMITTED_NETWORKS: [(IpAddr, u8); 8] = [
+    (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(172, 16, 0, 0)), 12),
+    (IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)), 16),
+    (IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(169, 254, 0, 0)), 16),
+    // carrier-grade NAT, also used by VPNs like Tailscale
+    (IpAddr::V4(Ipv4Addr::new(100, 64, 0, 0)), 10),
+    (IpAddr::V6(Ipv6Addr::LOCALHOST), 128),
+    // unique local addresses
+    (IpAddr::V6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0)), 7),
+];
+
+/// DNS names the local CA may issue for, `local` covers every mDNS hostname
+const PERMITTED_DNS_NAMES: [&str; 2] = ["localhost", "local"];
+
+/// Where the served certificate comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CertSource {
+    /// Configured with `tls_cert_file`/`tls_key_file` or pasted as PEM
+    Custom,
+    /// Issued by the local CA, see [`ensure_leaf_cert`]
+    LocalCa,
+}
+
+/// The certificate the HTTPS server presents
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertInfo {
+    pub source: CertSource,
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names and IP addresses the certificate is valid for
+    pub subject_alt_names: Vec<String>,
+    /// Unix timestamp in milliseconds
+    pub not_before: i64,
+    /// Unix timestamp in milliseconds
+    pub not_after: i64,
+    /// SHA-256 of the certificate
+    pub fingerprint: String,
+    /// SHA-256 peers pin, of the last certificate of the chain
+    pub pinned_fingerprint: String,
+    pub chain_length: usize,
+}
+
+/// Proof that the local CA replaced the self-signed certificate served before it,
+/// peers that pinned that certificate move their pin to the CA with it
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PinMigration {
+    /// The self-signed certificate peers pinned
+    pub legacy_cert_pem: String,
+    /// The fingerprint to pin from now on, of the local CA
+    pub fingerprint: String,
+    /// Base64 ECDSA P-256 signature of `fingerprint` by the key of the legacy certificate
+    pub signature: String,
+}
+
+impl PinMigration {
+    /// Whether the certificate pinned as `pinned` vouches for the new fingerprint
+    pub fn verify(&self, pinned: &str) -> bool {
+        let Some(der) = parse_chain(&self.legacy_cert_pem)
+            .ok()
+            .and_then(|chain| chain.into_iter().next())
+        else {
+            return false;
+        };
+        if format!("{:X}", Sha256::digest(&der)) != pinned.to_uppercase() {
+            return false;
+        }
+        let Ok((_, cert)) = X509Certificate::from_der(&der) else {
+            return false;
+        };
+        let (Ok(PublicKey::EC(point)), Ok(signature)) = (cert.public_key().parsed(), STANDARD.decode(&self.signature))
+        else {
+            return false;
+        };
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, point.data())
+            .verify(self.fingerprint.as_bytes(), &signature)
+            .is_ok()
+    }
+}
+
+/// TLS of the running HTTPS server, swaps the certificate without a restart
+pub struct ServerTls {
+    config: Mutex<Option<RustlsConfig>>,
+    /// Source and PEM chain currently served
+    served: Mutex<Option<(CertSource, String)>>,
+}
+
+singleton!(ServerTls, SERVER_TLS);
+
+impl ServerTls {
+    fn new() -> Self {
+        Self {
+            config: Mutex::new(None),
+            served: Mutex::new(None),
+        }
+    }
+
+    /// HTTPS config for the server. An invalid custom certificate is logged
+    /// and the local CA serves instead, so the devices can still connect.
+    pub async fn build_config(&self) -> Result<RustlsConfig> {
+        let synclan = Config::synclan().await.latest_arc();
+        let (source, cert_pem, signing_key_pem) = match custom_cert(&synclan).await {
+            Ok(Some((cert_pem, signing_key_pem))) => (CertSource::Custom, cert_pem, signing_key_pem),
+            Ok(None) => local_cert().await?,
+            Err(err) => {
+                logging!(error, Type::Server, "Ignoring the custom certificate: {err:?}");
+                local_cert().await?
+            },
+        };
+
+        let config = RustlsConfig::from_pem(cert_pem.clone().into_bytes(), signing_key_pem.into_bytes()).await?;
+        *self.config.lock() = Some(config.clone());
+        *self.served.lock() = Some((source, cert_pem));
+        Ok(config)
+    }
+
+    /// Loads the certificate from the latest config, the running server switches
+    /// to it if it changed. Fails on an invalid custom certificate, which is not served.
+    pub async fn reload(&self) -> Result<()> {
+        let synclan = Config::synclan().await.latest_arc();
+        let custom = custom_cert(&synclan).await?;
+        let Some(config) = self.config.lock().clone() else {
+            return Ok(());
+        };
+        let (source, cert_pem, signing_key_pem) = match custom {
+            Some((cert_pem, signing_key_pem)) => (CertSource::Custom, cert_pem, signing_key_pem),
+            None => local_cert().await?,
+        };
+        if self
+            .served
+            .lock()
+            .as_ref()
+            .is_some_and(|(_, served)| *served == cert_pem)
+        {
+            return Ok(());
+        }
+
+        config
+            .reload_from_pem(cert_pem.clone().into_bytes(), signing_key_pem.into_bytes())
+            .await?;
+        *self.served.lock() = Some((source, cert_pem));
+        logging!(info, Type::Server, "Server certificate reloaded");
+        Ok(())
+    }
+
+    /// Picks up certificate files renewed on disk, new interfaces and expiring leaves
+    pub async fn watch(&self) {
+        let mut interval = tokio::time::interval(CERT_CHECK_INTERVAL);
+        // the first tick completes immediately, the certificate was just loaded
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.reload().await {
+                logging!(error, Type::Server, "Failed to reload the server certificate: {err:?}");
+            }
+        }
+    }
+
+    /// The server stopped
+    pub fn clear(&self) {
+        self.config.lock().take();
+        self.served.lock().take();
+    }
+
+    /// Details of the served certificate, `None` when the server is not serving HTTPS
+    pub fn info(&self) -> Result<Option<CertInfo>> {
+        let Some((source, cert_pem)) = self.served.lock().clone() else {
+            return Ok(None);
+        };
+        cert_info(source, &cert_pem).map(Some)
+    }
+
+    /// PEM of the last certificate of the served chain, the one clients install as trusted
+    pub fn pinned_cert_pem(&self) -> Option<String> {
+        let served = self.served.lock();
+        let (_, cert_pem) = served.as_ref()?;
+        let begin = cert_pem.rfind("-----BEGIN CERTIFICATE-----")?;
+        Some(format!("{}\n", cert_pem[begin..].trim_end()))
+    }
+
+    /// Fingerprint of the last certificate of the served chain
+    fn pinned_fingerprint(&self) -> Option<String> {
+        let served = self.served.lock();
+        let (_, cert_pem) = served.as_ref()?;
+        parse_chain(cert_pem)
+            .ok()?
+            .last()
+            .map(|der| format!("{:X}", Sha256::digest(der)))
+    }
+}
+
+async fn local_cert() -> Result<(CertSource, String, String)> {
+    let (cert_pem, signing_key_pem) = ensure_leaf_cert().await?;
+    Ok((CertSource::LocalCa, cert_pem, signing_key_pem))
+}
+
+/// The configured certificate chain and key, validated. Files take precedence over
+/// pasted PEM, `None` when neither is set.
+async fn custom_cert(synclan: &ISynclan) -> Result<Option<(String, String)>> {
+    async fn load(file: &Option<String>, pem: &Option<String>) -> Result<Option<String>> {
+        if let Some(file) = file.as_deref().filter(|file| !file.trim().is_empty()) {
+            let pem = tokio::fs::read_to_string(file)
+                .await
+                .with_context(|| format!("Failed to read {file}"))?;
+            return Ok(Some(pem));
+        }
+        Ok(pem.clone().filter(|pem| !pem.trim().is_empty()))
+    }
+
+    let cert_pem = load(&synclan.tls_cert_file, &synclan.tls_cert_pem).await?;
+    let signing_key_pem = load(&synclan.tls_key_file, &synclan.tls_key_pem).await?;
+    match (cert_pem, signing_key_pem) {
+        (None, None) => Ok(None),
+        (Some(cert_pem), Some(signing_key_pem)) => {
+            validate_cert(&cert_pem, &signing_key_pem)?;
+            Ok(Some((cert_pem, signing_key_pem)))
+        },
+        _ => bail!("A custom certificate needs both the certificate chain and its private key"),
+    }
+}
+
+fn parse_chain(cert_pem: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let chain = CertificateDer::pem_slice_iter(cert_pem.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid certificate PEM")?;
+    ensure!(!chain.is_empty(), "No certificate found");
+    Ok(chain)
+}
+
+/// Checks a PEM chain (leaf first) and its key: every certificate is valid now, each one
+/// is signed by the next, the chain ends with a root or a CA the clients trust, and the
+/// key belongs to the leaf.
+pub fn validate_cert(cert_pem: &str, signing_key_pem: &str) -> Result<()> {
+    let chain = parse_chain(cert_pem)?;
+    let certs = chain
+        .iter()
+        .map(|der| X509Certificate::from_der(der).map(|(_, cert)| cert))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid certificate")?;
+
+    for cert in &certs {
+        ensure!(
+            cert.validity().is_valid(),
+            "The certificate {} is expired or not yet valid",
+            cert.subject()
+        );
+    }
+    for pair in certs.windows(2) {
+        let (cert, issuer) = (&pair[0], &pair[1]);
+        ensure!(
+            cert.issuer() == issuer.subject() && cert.verify_signature(Some(issuer.public_key())).is_ok(),
+            "The chain is broken, {} is not issued by {}",
+            cert.subject(),
+            issuer.subject()
+        );
+    }
+    let last = certs.last().expect("the chain is not empty");
+    let self_signed = last.issuer() == last.subject() && last.verify_signature(None).is_ok();
+    ensure!(
+        self_signed || last.is_ca(),
+        "The chain is incomplete, add the certificates issuing {}",
+        last.issuer()
+    );
+
+    let signing_key = PrivateKeyDer::from_pem_slice(signing_key_pem.as_bytes()).context("Invalid private key PEM")?;
+    let signing_key =
+        rustls::crypto::ring::sign::any_supported_type(&signing_key).context("Unsupported private key")?;
+    let public_key = signing_key
+        .public_key()
+        .ok_or_else(|| anyhow!("Unsupported private key"))?;
+    ensure!(
+        public_key.as_ref() == certs[0].public_key().raw,
+        "The private key does not match the certificate"
+    );
+
+    Ok(())
+}
+
+fn cert_info(source: CertSource, cert_pem: &str) -> Result<CertInfo> {
+    let chain = parse_chain(cert_pem)?;
+    let (_, leaf) = X509Certificate::from_der(&chain[0]).context("Invalid certificate")?;
+
+    let subject_alt_names = leaf
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|ext| {
+            ext.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_string()),
+                    GeneralName::IPAddress(octets) => match octets.len() {
+                        4 => Some(IpAddr::from(<[u8; 4]>::try_from(*octets).ok()?).to_string()),
+                        16 => Some(IpAddr::from(<[u8; 16]>::try_from(*octets).ok()?).to_string()),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let fingerprint = |der: &CertificateDer<'_>| format!("{:X}", Sha256::digest(der));
+
+    Ok(CertInfo {
+        source,
+        subject: leaf.subject().to_string(),
+        issuer: leaf.issuer().to_string(),
+        subject_alt_names,
+        not_before: leaf.validity().not_before.timestamp() * 1000,
+        not_after: leaf.validity().not_after.timestamp() * 1000,
+        fingerprint: fingerprint(&chain[0]),
+        pinned_fingerprint: chain.last().map(fingerprint).unwrap_or_default(),
+        chain_length: chain.len(),
+    })
+}
+
+/// The local root CA, created once. Installing it as trusted removes the browser warnings.
+pub async fn ensure_ca() -> Result<(String, String)> {
     let synclan_draft = Config::synclan().await;
     let synclan = synclan_draft.latest_arc();
-    if let (Some(cert_pem), Some(signing_key_pem)) = (&synclan.cert_pem, &synclan.signing_key_pem) {
-        let config =
-            RustlsConfig::from_pem(cert_pem.clone().into_bytes(), signing_key_pem.clone().into_bytes()).await?;
-        return Ok(config);
+    if let (Some(ca_cert_pem), Some(ca_key_pem)) = (&synclan.ca_cert_pem, &synclan.ca_key_pem) {
+        return Ok((ca_cert_pem.clone(), ca_key_pem.clone()));
     }
 
-    // Generate new self-signed certificate
-    let (cert_pem, signing_key_pem) = generate_cert(ip)?;
-    let config = RustlsConfig::from_pem(cert_pem.clone().into_bytes(), signing_key_pem.clone().into_bytes()).await?;
-
-    // save to config
-    let patch = ISynclan {
-        cert_pem: Some(cert_pem),
-        signing_key_pem: Some(signing_key_pem),
+    let (ca_cert_pem, ca_key_pem) = generate_ca()?;
+    save(ISynclan {
+        ca_cert_pem: Some(ca_cert_pem.clone()),
+        ca_key_pem: Some(ca_key_pem.clone()),
         ..ISynclan::default()
+    })
+    .await?;
+
+    Ok((ca_cert_pem, ca_key_pem))
+}
+
+/// The leaf as a PEM chain (leaf, CA) and its key. Reused as long as it is signed by the
+/// local CA, covers every current address and is not about to expire, re-issued otherwise.
+pub async fn ensure_leaf_cert() -> Result<(String, String)> {
+    let (ca_cert_pem, ca_key_pem) = ensure_ca().await?;
+    let names = subject_alt_names();
+
+    let synclan = Config::synclan().await.latest_arc();
+    if let (Some(cert_pem), Some(signing_key_pem)) = (&synclan.cert_pem, &synclan.signing_key_pem)
+        && is_leaf_current(cert_pem, &ca_cert_pem, &names)
+    {
+        return Ok((cert_pem.clone(), signing_key_pem.clone()));
+    }
+
+    // the first leaf of the local CA replaces the self-signed certificate peers may have pinned
+    let (legacy_cert_pem, legacy_pin_signature) = match (&synclan.cert_pem, &synclan.signing_key_pem) {
+        (Some(cert_pem), Some(signing_key_pem))
+            if synclan.legacy_cert_pem.is_none() && is_self_signed_leaf(cert_pem) =>
+        {
+            let fingerprint = cert_fingerprint(&ca_cert_pem).ok_or_else(|| anyhow!("Invalid CA certificate"))?;
+            match sign(signing_key_pem, fingerprint.as_bytes()) {
+                Ok(signature) => (Some(cert_pem.clone()), Some(signature)),
+                Err(err) => {
+                    logging!(warn, Type::Server, "Failed to sign the pin migration: {err}");
+                    (None, None)
+                },
+            }
+        },
+        _ => (None, None),
     };
+
+    let (cert_pem, signing_key_pem) = generate_leaf(&ca_cert_pem, &ca_key_pem, &names)?;
+    save(ISynclan {
+        cert_pem: Some(cert_pem.clone()),
+        signing_key_pem: Some(signing_key_pem.clone()),
+        legacy_cert_pem,
+        legacy_pin_signature,
+        ..ISynclan::default()
+    })
+    .await?;
+    logging!(
+        info,
+        Type::Server,
+        "Issued a server certificate for {}",
+        names.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    );
+
+    Ok((cert_pem, signing_key_pem))
+}
+
+async fn save(patch: ISynclan) -> Result<()> {
+    let synclan_draft = Config::synclan().await;
     synclan_draft.edit_draft(|s| s.patch_config(&patch));
     synclan_draft.apply();
-    synclan_draft.data_arc().save_config().await?;
+    synclan_draft.data_arc().save_config().await
+}
+
+/// A name the leaf is issued for
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SubjectAltName {
+    Ip(IpAddr),
+    Dns(String),
+}
+
+impl std::fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{ip}"),
+            Self::Dns(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Every interface address, loopback, `localhost` and the mDNS hostname
+pub fn subject_alt_names() -> BTreeSet<SubjectAltName> {
+    let mut names = BTreeSet::from([
+        SubjectAltName::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        SubjectAltName::Ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+        SubjectAltName::Dns("localhost".into()),
+    ]);
+    if let Ok(interfaces) = local_ip_address::list_afinet_netifas() {
+        // the local CA can't issue for a public address
+        names.extend(
+            interfaces
+                .into_iter()
+                .map(|(_, ip)| ip)
+                .filter(|ip| !ip.is_unspecified() && !is_ipv6_link_local(ip) && is_permitted_ip(*ip))
+                .map(SubjectAltName::Ip),
+        );
+    }
+    if let Ok(hostname) = whoami::hostname() {
+        let hostname = hostname.trim_end_matches(".local").to_lowercase();
+        if !hostname.is_empty() && hostname.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            names.insert(SubjectAltName::Dns(format!("{hostname}.local")));
+        }
+    }
+    names
+}
+
+fn is_permitted_ip(ip: IpAddr) -> bool {
+    PERMITTED_NETWORKS.iter().any(|&(network, prefix)| match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            (u32::from(ip) ^ u32::from(network))
+                .checked_shr(32 - prefix as u32)
+                .unwrap_or(0)
+                == 0
+        },
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            (u128::from(ip) ^ u128::from(network))
+                .checked_shr(128 - prefix as u32)
+                .unwrap_or(0)
+                == 0
+        },
+        _ => false,
+    })
+}
+
+/// A certificate from before the local CA, see [`PinMigration`]
+fn is_self_signed_leaf(cert_pem: &str) -> bool {
+    let Ok(chain) = parse_chain(cert_pem) else {
+        return false;
+    };
+    let [der] = chain.as_slice() else {
+        return false;
+    };
+    X509Certificate::from_der(der).is_ok_and(|(_, cert)| cert.issuer() == cert.subject() && !cert.is_ca())
+}
+
+/// Base64 signature of `message`, every key here is generated as ECDSA P-256
+fn sign(signing_key_pem: &str, message: &[u8]) -> Result<String> {
+    let pkcs8 = KeyPair::from_pem(signing_key_pem)?.serialize_der();
+    let rng = SystemRandom::new();
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8, &rng)
+        .map_err(|err| anyhow!("Unsupported signing key: {err}"))?;
+    let signature = key_pair.sign(&rng, message).map_err(|_| anyhow!("Failed to sign"))?;
+    Ok(STANDARD.encode(signature.as_ref()))
+}
+
+fn is_leaf_current(cert_pem: &str, ca_cert_pem: &str, names: &BTreeSet<SubjectAltName>) -> bool {
+    // a leaf from before the local CA is self-signed, the chain ends with the CA otherwise
+    if !cert_pem.trim_end().ends_with(ca_cert_pem.trim_end()) {
+        return false;
+    }
+    let Ok(params) = CertificateParams::from_ca_cert_pem(cert_pem) else {
+        return false;
+    };
+    if params.not_after - OffsetDateTime::now_utc() < LEAF_RENEW_BEFORE {
+        return false;
+    }
 
-    Ok(config)
+    let issued: BTreeSet<SubjectAltName> = params
+        .subject_alt_names
+        .iter()
+        .filter_map(|name| match name {
+            SanType::IpAddress(ip) => Some(SubjectAltName::Ip(*ip)),
+            SanType::DnsName(name) => Some(SubjectAltName::Dns(name.as_str().to_string())),
+            _ => None,
+        })
+        .collect();
+    names.is_subset(&issued)
 }
 
-/// Generate new self-signed certificate
-pub fn generate_cert(ip: &IpAddr) -> Result<(String, String)> {
+fn generate_ca() -> Result<(String, String)> {
+    let mut params = CertificateParams::new(vec![])?;
+    params.distinguished_name = DistinguishedName::new();
+    params.distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    // installing the CA as trusted must not let it vouch for anything outside the LAN
+    params.name_constraints = Some(NameConstraints {
+        permitted_subtrees: PERMITTED_DNS_NAMES
+            .iter()
+            .map(|name| GeneralSubtree::DnsName(name.to_string()))
+            .chain(
+                PERMITTED_NETWORKS
+                    .iter()
+                    .map(|&(network, prefix)| GeneralSubtree::IpAddress(CidrSubnet::from_addr_prefix(network, prefix))),
+            )
+            .collect(),
+        excluded_subtrees: Vec::new(),
+    });
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    let now = OffsetDateTime::now_utc();
+    params.not_before = now - time::Duration::days(1);
+    params.not_after = now + CA_VALIDITY;
+
+    let signing_key = KeyPair::generate()?;
+    let cert = params.self_signed(&signing_key)?;
+
+    Ok((cert.pem(), signing_key.serialize_pem()))
+}
+
+/// Leaf for `names` signed by the CA, returned as a PEM chain with the CA
+fn generate_leaf(ca_cert_pem: &str, ca_key_pem: &str, names: &BTreeSet<SubjectAltName>) -> Result<(String, String)> {
+    let issuer = Issuer::from_ca_cert_pem(ca_cert_pem, KeyPair::from_pem(ca_key_pem)?)?;
+
     let mut params = CertificateParams::new(vec![])?;
     params.distinguished_name = DistinguishedName::new();
     params
         .distinguished_name
         .push(DnType::CommonName, "Local SyncLan HTTPS Server");
-    params.subject_alt_names.push(SanType::IpAddress(*ip));
+    for name in names {
+        params.subject_alt_names.push(match name {
+            SubjectAltName::Ip(ip) => SanType::IpAddress(*ip),
+            SubjectAltName::Dns(name) => SanType::DnsName(name.clone().try_into()?),
+        });
+    }
+    params.is_ca = IsCa::ExplicitNoCa;
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    params.use_authority_key_identifier_extension = true;
+    let now = OffsetDateTime::now_utc();
+    params.not_before = now - time::Duration::days(1);
+    params.not_after = now + LEAF_VALIDITY;
+
     let signing_key = KeyPair::generate()?;
-    let cert = params.self_signed(&signing_key)?;
-    let cert_pem = cert.pem();
-    let signing_key_pem = signing_key.serialize_pem();
+    let cert = params.signed_by(&signing_key, &issuer)?;
 
-    Ok((cert_pem, signing_key_pem))
+    Ok((format!("{}{}", cert.pem(), ca_cert_pem), signing_key.serialize_pem()))
+}
+
+/// Uppercase hex SHA-256 of the first certificate of the PEM
+pub fn cert_fingerprint(cert_pem: &str) -> Option<String> {
+    let body: String = cert_pem
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN CERTIFICATE-----"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END"))
+        .collect();
+    let der = STANDARD.decode(body).ok()?;
+    Some(format!("{:X}", Sha256::digest(der)))
+}
+
+/// What peers that pinned the certificate from before the local CA need to move their pin,
+/// `None` if there was none or another certificate is served now
+pub fn pin_migration(synclan: &ISynclan) -> Option<PinMigration> {
+    let fingerprint = cert_fingerprint(synclan.ca_cert_pem.as_deref()?)?;
+    if server_fingerprint(synclan).as_deref() != Some(fingerprint.as_str()) {
+        return None;
+    }
+    Some(PinMigration {
+        legacy_cert_pem: synclan.legacy_cert_pem.clone()?,
+        fingerprint,
+        signature: synclan.legacy_pin_signature.clone()?,
+    })
+}
+
+/// How peers tell this host apart and pin it: the fingerprint of the last certificate
+/// of the served chain, the local CA by default, which outlives the leaves it signs.
+/// `None` without a certificate.
+pub fn server_fingerprint(synclan: &ISynclan) -> Option<String> {
+    ServerTls::global().pinned_fingerprint().or_else(|| {
+        synclan
+            .ca_cert_pem
+            .as_deref()
+            .or(synclan.cert_pem.as_deref())
+            .and_then(cert_fingerprint)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leaf_covers_names_and_chains_to_ca() {
+        let (ca_cert_pem, ca_key_pem) = generate_ca().unwrap();
+        let names = BTreeSet::from([
+            SubjectAltName::Ip("192.168.1.2".parse().unwrap()),
+            SubjectAltName::Dns("localhost".into()),
+        ]);
+        let (cert_pem, _) = generate_leaf(&ca_cert_pem, &ca_key_pem, &names).unwrap();
+
+        assert!(is_leaf_current(&cert_pem, &ca_cert_pem, &names));
+        assert_ne!(cert_fingerprint(&cert_pem), cert_fingerprint(&ca_cert_pem));
+
+        // a new address needs a new leaf
+        let mut moved = names.clone();
+        moved.insert(SubjectAltName::Ip("10.0.0.2".parse().unwrap()));
+        assert!(!is_leaf_current(&cert_pem, &ca_cert_pem, &moved));
+
+        // so does another CA
+        let (other_ca_pem, _) = generate_ca().unwrap();
+        assert!(!is_leaf_current(&cert_pem, &other_ca_pem, &names));
+    }
+
+    #[test]
+    fn test_validate_custom_cert() {
+        let (ca_cert_pem, ca_key_pem) = generate_ca().unwrap();
+        let names = BTreeSet::from([SubjectAltName::Dns("synclan.local".into())]);
+        let (cert_pem, signing_key_pem) = generate_leaf(&ca_cert_pem, &ca_key_pem, &names).unwrap();
+        validate_cert(&cert_pem, &signing_key_pem).unwrap();
+
+        let info = cert_info(CertSource::Custom, &cert_pem).unwrap();
+        assert_eq!(info.subject_alt_names, vec!["synclan.local"]);
+        assert_eq!(info.chain_length, 2);
+        assert_eq!(Some(info.pinned_fingerprint), cert_fingerprint(&ca_cert_pem));
+
+        // another key
+        let (_, other_key_pem) = generate_leaf(&ca_cert_pem, &ca_key_pem, &names).unwrap();
+        assert!(validate_cert(&cert_pem, &other_key_pem).is_err());
+
+        // the leaf without the CA that issued it
+        let leaf_pem = cert_pem.strip_suffix(ca_cert_pem.as_str()).unwrap();
+        assert!(validate_cert(leaf_pem, &signing_key_pem).is_err());
+
+        // issued by another CA
+        let (other_ca_pem, _) = generate_ca().unwrap();
+        assert!(validate_cert(&format!("{leaf_pem}{other_ca_pem}"), &signing_key_pem).is_err());
+    }
+
+    #[test]
+    fn test_ca_is_limited_to_the_lan() {
+        let (ca_cert_pem, _) = generate_ca().unwrap();
+        let der = parse_chain(&ca_cert_pem).unwrap().remove(0);
+        let (_, ca) = X509Certificate::from_der(&der).unwrap();
+        let constraints = ca.name_constraints().unwrap().unwrap().value;
+        let permitted = constraints.permitted_subtrees.as_ref().unwrap();
+        assert!(
+            permitted
+                .iter()
+                .any(|subtree| matches!(subtree.base, GeneralName::DNSName("local")))
+        );
+        assert!(
+            permitted
+                .iter()
+                .any(|subtree| matches!(subtree.base, GeneralName::DNSName("localhost")))
+        );
+
+        assert!(is_permitted_ip("192.168.1.2".parse().unwrap()));
+        assert!(is_permitted_ip("172.31.255.1".parse().unwrap()));
+        assert!(is_permitted_ip("::1".parse().unwrap()));
+        assert!(is_permitted_ip("fd12::1".parse().unwrap()));
+        assert!(!is_permitted_ip("172.32.0.1".parse().unwrap()));
+        assert!(!is_permitted_ip("8.8.8.8".parse().unwrap()));
+        assert!(!is_permitted_ip("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_pin_migration() {
+        // a certificate like the ones served before the local CA
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        params
+            .subject_alt_names
+            .push(SanType::IpAddress("192.168.1.2".parse().unwrap()));
+        let legacy_key = KeyPair::generate().unwrap();
+        let legacy_cert_pem = params.self_signed(&legacy_key).unwrap().pem();
+        let legacy_fingerprint = cert_fingerprint(&legacy_cert_pem).unwrap();
+        assert!(is_self_signed_leaf(&legacy_cert_pem));
+
+        let (ca_cert_pem, ca_key_pem) = generate_ca().unwrap();
+        assert!(!is_self_signed_leaf(&ca_cert_pem));
+        let (leaf_pem, _) = generate_leaf(&ca_cert_pem, &ca_key_pem, &BTreeSet::new()).unwrap();
+        assert!(!is_self_signed_leaf(&leaf_pem));
+
+        let fingerprint = cert_fingerprint(&ca_cert_pem).unwrap();
+        let migration = PinMigration {
+            legacy_cert_pem,
+            signature: sign(&legacy_key.serialize_pem(), fingerprint.as_bytes()).unwrap(),
+            fingerprint,
+        };
+        assert!(migration.verify(&legacy_fingerprint));
+        assert!(!migration.verify(&cert_fingerprint(&leaf_pem).unwrap()));
+
+        // the proof only holds for the fingerprint that was signed
+        let forged = PinMigration {
+            fingerprint: cert_fingerprint(&leaf_pem).unwrap(),
+            ..migration
+        };
+        assert!(!forged.verify(&legacy_fingerprint));
+    }
 }
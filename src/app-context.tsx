@@ -1,34 +1,243 @@
-import { createContext, useContext, type ReactNode } from 'react';
+import { createContext, useContext, useRef, type ReactNode } from 'react';
+import { toast } from 'sonner';
 
+import { formatFileSize } from '@/components/messages/util';
 import { useSocketIO, type ReadyState, type SendMessage } from '@/hooks';
-import { getWSUrl } from '@/lib/constant';
-import { useDeviceStore, useIMStore, useMessageAnimationStore } from '@/stores';
+import { getWSUrl, isWeb } from '@/lib/constant';
+import { DEVICE_TOKEN_STORAGE_KEY } from '@/lib/device';
+import { i18n } from '@/lib/i18n';
+import {
+  createTransferOffer,
+  isTransferFinished,
+  receiveTransfer,
+  sendTransfer,
+  type TransferOffer,
+  type TransferProgress,
+} from '@/services/transfer';
+import {
+  useDeviceStore,
+  useIMStore,
+  useMessageAnimationStore,
+  useTransferStore,
+} from '@/stores';
 
 type AppContext = {
   socketState: ReadyState;
   sendMessage: SendMessage;
+  /** 直接发送文件给对方，对方接受后才开始传输 */
+  sendFileDirectly: (file: File, receiver: string) => Promise<void>;
 };
 
+const TOASTER_ID = 'global';
+
 export const AppContext = createContext<AppContext | null>(null);
 
 export function AppProvider({ children }: { children: ReactNode }) {
   const current = useDeviceStore((s) => s.current);
   const addMessage = useIMStore((s) => s.addMessage);
 
-  const { state: socketState, sendMessage } = useSocketIO(getWSUrl(), {
+  // 等待对方接受的文件
+  const outgoingFilesRef = useRef(new Map<string, File>());
+  const abortControllersRef = useRef(new Map<string, AbortController>());
+
+  const {
+    state: socketState,
+    sendMessage,
+    offerTransfer,
+    answerTransfer,
+    cancelTransfer,
+  } = useSocketIO(getWSUrl(), {
     transports: ['websocket'],
     auth: {
-      deviceId: current?.id,
+      token: current?.id
+        ? localStorage.getItem(DEVICE_TOKEN_STORAGE_KEY)
+        : undefined,
     },
     onMessage(message) {
       if (!current?.id) return;
       useMessageAnimationStore.getState().add(message.uuid);
       addMessage(message.sender, message, current.id);
     },
+    onTransferOffer(offer) {
+      useTransferStore.getState().add(offer, 'incoming');
+      showOfferToast(offer);
+    },
+    onServerRestart({ urls }) {
+      // 地址不变时连接会自动恢复，变了则提示前往新地址
+      const moved =
+        isWeb && urls.length > 0 && !urls.includes(window.location.origin);
+      toast.info(i18n.t('server.restarting'), {
+        toasterId: TOASTER_ID,
+        description: moved ? urls[0] : undefined,
+        action: moved
+          ? {
+              label: i18n.t('server.open'),
+              onClick: () => window.location.assign(urls[0]),
+            }
+          : undefined,
+      });
+    },
+    onTransferProgress(progress) {
+      const item = useTransferStore.getState().get(progress.transferId);
+      if (!item) return;
+
+      useTransferStore.getState().update(progress);
+
+      if (item.direction === 'outgoing' && progress.state === 'accepted') {
+        void startSending(progress.transferId);
+      }
+
+      showProgressToast(item.offer, progress);
+
+      if (isTransferFinished(progress.state)) {
+        useTransferStore.getState().remove(progress.transferId);
+        outgoingFilesRef.current.delete(progress.transferId);
+        abortControllersRef.current.get(progress.transferId)?.abort();
+        abortControllersRef.current.delete(progress.transferId);
+      }
+    },
   });
 
+  const withAbort = (transferId: string) => {
+    const controller = new AbortController();
+    abortControllersRef.current.set(transferId, controller);
+    return controller.signal;
+  };
+
+  const startSending = async (transferId: string) => {
+    const file = outgoingFilesRef.current.get(transferId);
+    if (!file) return;
+
+    try {
+      await sendTransfer(transferId, file, withAbort(transferId));
+    } catch (error) {
+      // 失败原因由 progress 事件通知
+      console.error('Failed to send transfer:', error);
+    }
+  };
+
+  const cancel = (transferId: string) => {
+    cancelTransfer(transferId).catch(() => {
+      toast.error(i18n.t('transfer.cancelFailed'), { toasterId: TOASTER_ID });
+    });
+  };
+
+  const showOfferToast = (offer: TransferOffer) => {
+    toast(i18n.t('transfer.incoming', { name: offer.name }), {
+      id: offer.transferId,
+      toasterId: TOASTER_ID,
+      description: formatFileSize(offer.size),
+      duration: Infinity,
+      action: {
+        label: i18n.t('transfer.accept'),
+        onClick: async () => {
+          try {
+            await answerTransfer(offer.transferId, true);
+            await receiveTransfer(
+              offer.transferId,
+              offer.name,
+              withAbort(offer.transferId),
+            );
+          } catch (error) {
+            console.error('Failed to receive transfer:', error);
+          }
+        },
+      },
+      cancel: {
+        label: i18n.t('transfer.decline'),
+        onClick: () => {
+          answerTransfer(offer.transferId, false).catch(() => {});
+        },
+      },
+    });
+  };
+
+  const showProgressToast = (
+    offer: TransferOffer,
+    progress: TransferProgress,
+  ) => {
+    const options = { id: offer.transferId, toasterId: TOASTER_ID };
+
+    switch (progress.state) {
+      case 'accepted':
+      case 'transferring': {
+        const percent = progress.total
+          ? Math.floor((progress.transferred / progress.total) * 100)
+          : 0;
+        const transferred = formatFileSize(progress.transferred);
+        const total = formatFileSize(progress.total);
+        toast.loading(
+          i18n.t('transfer.progress', { name: offer.name, percent }),
+          {
+            ...options,
+            description: `${transferred} / ${total}`,
+            duration: Infinity,
+            cancel: {
+              label: i18n.t('transfer.cancel'),
+              onClick: () => cancel(offer.transferId),
+            },
+          },
+        );
+        break;
+      }
+      case 'completed':
+        toast.success(i18n.t('transfer.completed', { name: offer.name }), {
+          ...options,
+          description: progress.message ?? undefined,
+          duration: 4000,
+        });
+        break;
+      case 'declined':
+        toast.info(i18n.t('transfer.declined', { name: offer.name }), {
+          ...options,
+          duration: 4000,
+        });
+        break;
+      case 'cancelled':
+      case 'failed':
+        toast.error(i18n.t('transfer.failed', { name: offer.name }), {
+          ...options,
+          description: progress.message ?? undefined,
+          duration: 4000,
+        });
+        break;
+    }
+  };
+
+  const sendFileDirectly: AppContext['sendFileDirectly'] = async (
+    file,
+    receiver,
+  ) => {
+    try {
+      const { data: offer } = await offerTransfer(
+        await createTransferOffer(file, receiver),
+      );
+      if (!offer) return;
+
+      outgoingFilesRef.current.set(offer.transferId, file);
+      useTransferStore.getState().add(offer, 'outgoing');
+
+      toast.loading(i18n.t('transfer.waiting', { name: offer.name }), {
+        id: offer.transferId,
+        toasterId: TOASTER_ID,
+        duration: Infinity,
+        cancel: {
+          label: i18n.t('transfer.cancel'),
+          onClick: () => cancel(offer.transferId),
+        },
+      });
+    } catch (error) {
+      toast.error(i18n.t('transfer.offerFailed', { name: file.name }), {
+        toasterId: TOASTER_ID,
+        description: (error as { message?: string })?.message,
+      });
+    }
+  };
+
   return (
-    <AppContext value={{ socketState, sendMessage }}>{children}</AppContext>
+    <AppContext value={{ socketState, sendMessage, sendFileDirectly }}>
+      {children}
+    </AppContext>
   );
 }
 
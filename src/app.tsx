@@ -33,12 +33,12 @@ function App() {
 
     const initialization = async () => {
       try {
-        const [device, domain] = await Promise.all([
+        const [device, domains] = await Promise.all([
           getDevice(),
           getServerDomain(),
           hydrateConversations(),
         ]);
-        updateDomain(domain);
+        updateDomain(domains);
         if (device) {
           await updateConvsFromOffline(device.id);
           updateCurrent(device);
@@ -31,6 +31,7 @@ import { useDeviceStore, useIMStore, useMessageAnimationStore } from '@/stores';
 import { useConfirm } from '../confirm-dialog';
 import { MessageExpandable } from './message-expandable';
 import { FileMessage } from './message-file';
+import { FolderMessage } from './message-folder';
 import { ImageMessage } from './message-image';
 import { TextMessage, type MessageContextMenuRef } from './message-text';
 import { VideoMessage } from './message-video';
@@ -132,6 +133,10 @@ function MessageAnimatedRow({
       return <FileMessage message={message} />;
     }
 
+    if (message.type === 'folder') {
+      return <FolderMessage message={message} />;
+    }
+
     return null;
   };
 
@@ -188,7 +193,8 @@ function MessageAnimatedRow({
                 <BubbleContent
                   className={cn(
                     'editor-shell overflow-x-auto',
-                    message.type === 'file' && 'p-0 border-none',
+                    (message.type === 'file' || message.type === 'folder') &&
+                      'p-0 border-none',
                   )}
                 >
                   {message.type === 'text' ? (
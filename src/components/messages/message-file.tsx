@@ -10,9 +10,9 @@ import {
   AttachmentMedia,
   AttachmentTitle,
 } from '@/components/ui';
-import { downloadFile } from '@/lib/media';
+import { downloadFile, getMediaUrl } from '@/lib/media';
 
-import { parseMessageExtra } from './util';
+import { formatFileSize, parseMessageExtra } from './util';
 
 type FileMessageExtra = {
   name: string;
@@ -51,7 +51,10 @@ function FileMessage({ message }: Props) {
             }
 
             try {
-              await downloadFile(message.content, extra.name);
+              await downloadFile(
+                getMediaUrl(message.content, message.attachmentUrls),
+                extra.name,
+              );
               toast.success('File downloaded');
             } catch {
               toast.error('Unable to download the file');
@@ -75,20 +78,4 @@ function getFileDescription(extra?: FileMessageExtra) {
   return `${ext ?? 'FILE'} · ${formatFileSize(extra.size)}`;
 }
 
-function formatFileSize(size: number) {
-  if (size < 1024) {
-    return `${size} B`;
-  }
-
-  if (size < 1024 * 1024) {
-    return `${(size / 1024).toFixed(1)} KB`;
-  }
-
-  if (size < 1024 * 1024 * 1024) {
-    return `${(size / 1024 / 1024).toFixed(1)} MB`;
-  }
-
-  return `${(size / 1024 / 1024 / 1024).toFixed(1)} GB`;
-}
-
 export { FileMessage };
@@ -0,0 +1,152 @@
+import { DownloadIcon, FolderIcon, ListIcon } from 'lucide-react';
+import { useState } from 'react';
+import { toast } from 'sonner';
+
+import {
+  Attachment,
+  AttachmentAction,
+  AttachmentActions,
+  AttachmentContent,
+  AttachmentDescription,
+  AttachmentMedia,
+  AttachmentTitle,
+} from '@/components/ui';
+import { getAttachmentBaseUrl } from '@/lib/constant';
+import { downloadFile, getMediaUrl } from '@/lib/media';
+
+import { formatFileSize, parseMessageExtra } from './util';
+
+type FolderMessageExtra = {
+  name: string;
+  size: number;
+  fileCount: number;
+};
+
+type FolderListing = {
+  name: string;
+  path: string;
+  size: number;
+  files: {
+    path: string;
+    size: number;
+    /** Signed url relative to the attachment base url */
+    url: string;
+  }[];
+};
+
+type Props = {
+  message: FolderMessage;
+};
+
+function FolderMessage({ message }: Props) {
+  const [listing, setListing] = useState<FolderListing | null>(null);
+
+  const extra = parseMessageExtra<FolderMessageExtra>(message.extra);
+  const folderUrl = getMediaUrl(message.content, message.attachmentUrls);
+
+  const onToggleList = async () => {
+    if (listing) {
+      setListing(null);
+      return;
+    }
+
+    try {
+      const response = await fetch(folderUrl);
+      if (!response.ok) {
+        throw new Error(response.statusText);
+      }
+
+      const data = (await response.json()) as { payload: FolderListing };
+      setListing(data.payload);
+    } catch {
+      toast.error('Unable to load the folder');
+    }
+  };
+
+  const onDownload = async (url: string, fileName: string) => {
+    try {
+      await downloadFile(url, fileName);
+      toast.success('File downloaded');
+    } catch {
+      toast.error('Unable to download the file');
+    }
+  };
+
+  return (
+    <div className='flex flex-col'>
+      <Attachment>
+        <AttachmentMedia>
+          <FolderIcon />
+        </AttachmentMedia>
+        <AttachmentContent>
+          <AttachmentTitle>{extra?.name ?? '-'}</AttachmentTitle>
+          <AttachmentDescription>
+            {extra
+              ? `${extra.fileCount} files · ${formatFileSize(extra.size)}`
+              : 'FOLDER'}
+          </AttachmentDescription>
+        </AttachmentContent>
+        <AttachmentActions>
+          <AttachmentAction
+            type='button'
+            title='Files'
+            aria-label='Files'
+            size='icon-sm'
+            variant='secondary'
+            onClick={onToggleList}
+          >
+            <ListIcon />
+          </AttachmentAction>
+          <AttachmentAction
+            type='button'
+            title='Download'
+            aria-label='Download'
+            size='icon-sm'
+            variant='secondary'
+            onClick={async () => {
+              if (!message.content) {
+                return;
+              }
+
+              const separator = folderUrl.includes('?') ? '&' : '?';
+              await onDownload(
+                `${folderUrl}${separator}download=true`,
+                `${extra?.name ?? 'folder'}.zip`,
+              );
+            }}
+          >
+            <DownloadIcon />
+          </AttachmentAction>
+        </AttachmentActions>
+      </Attachment>
+      {listing && (
+        <ul className='max-h-60 overflow-y-auto px-3 py-2 text-sm'>
+          {listing.files.map((file) => (
+            <li
+              key={file.path}
+              className='flex items-center justify-between gap-2 py-0.5'
+            >
+              <span className='truncate' title={file.path}>
+                {file.path}
+              </span>
+              <button
+                type='button'
+                className='text-muted-foreground shrink-0 text-xs hover:underline'
+                onClick={() =>
+                  onDownload(
+                    `${getAttachmentBaseUrl()}/${file.url}`,
+                    file.path.split('/').pop() ?? file.path,
+                  )
+                }
+              >
+                {formatFileSize(file.size)}
+              </button>
+            </li>
+          ))}
+        </ul>
+      )}
+    </div>
+  );
+}
+
+export { FolderMessage };
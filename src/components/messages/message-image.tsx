@@ -1,4 +1,8 @@
-import { calculateMediaSize, getMediaUrl } from '@/lib/media';
+import {
+  calculateMediaSize,
+  getMediaUrl,
+  getThumbnailUrl,
+} from '@/lib/media';
 
 import { parseMessageExtra } from './util';
 
@@ -15,7 +19,10 @@ function ImageMessage({ message }: Props) {
     <div style={{ width, height }}>
       <img
         loading='lazy'
-        src={getMediaUrl(message.content)}
+        src={
+          getThumbnailUrl(message.content, message.thumbnailUrls) ??
+          getMediaUrl(message.content, message.attachmentUrls)
+        }
         className='h-full w-full rounded-md object-contain'
       />
     </div>
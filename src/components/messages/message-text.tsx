@@ -26,7 +26,7 @@ type Props = {
 };
 
 function TextMessage({ ref, message }: Props) {
-  const { content } = message;
+  const { content, attachmentUrls } = message;
 
   const lexicalMessageRef = useRef<MessageContextMenuRef>(null);
 
@@ -37,7 +37,10 @@ function TextMessage({ ref, message }: Props) {
   }));
 
   // oxlint-disable-next-line react-hooks/exhaustive-deps
-  const initialState = useMemo(() => parseTextMessageContent(content), []);
+  const initialState = useMemo(
+    () => parseTextMessageContent(content, attachmentUrls),
+    [],
+  );
 
   const onCopyHandler = async () => {
     if (typeof initialState === 'string') {
@@ -1,4 +1,8 @@
-import { calculateMediaSize, getMediaUrl } from '@/lib/media';
+import {
+  calculateMediaSize,
+  getMediaUrl,
+  getThumbnailUrl,
+} from '@/lib/media';
 
 import { parseMessageExtra } from './util';
 
@@ -14,8 +18,11 @@ function VideoMessage({ message }: Props) {
   return (
     <div style={{ width, height }}>
       <video
-        src={getMediaUrl(message.content)}
-        poster='/clapperboard.svg'
+        src={getMediaUrl(message.content, message.attachmentUrls)}
+        poster={
+          getThumbnailUrl(message.content, message.thumbnailUrls) ??
+          '/clapperboard.svg'
+        }
         controls
         playsInline
         preload='metadata'
@@ -6,6 +6,10 @@ interface BasicMessage {
   content?: string;
   plainContent?: string;
   extra?: string;
+  /** Signed urls of the uploaded files, keyed by path */
+  attachmentUrls?: Record<string, string>;
+  /** Signed thumbnail urls of the uploaded images and videos, keyed by path */
+  thumbnailUrls?: Record<string, string>;
   createdAt: number;
   updatedAt: number;
 }
@@ -36,7 +40,16 @@ interface FileMessage extends BasicMessage {
   type: 'file';
 }
 
-type IMessage = TextMessage | ImageMessage | VideoMessage | FileMessage;
+interface FolderMessage extends BasicMessage {
+  type: 'folder';
+}
+
+type IMessage =
+  | TextMessage
+  | ImageMessage
+  | VideoMessage
+  | FileMessage
+  | FolderMessage;
 type IUIMessage = IMessage & { showTimestamp: boolean };
 
 type MediaMessageExtra = Record<string, any> & {
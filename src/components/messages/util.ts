@@ -69,16 +69,43 @@ export function isSameDay(time: number, diffTime?: number) {
 
 export const THRESHOLD = 5 * 60 * 1000;
 
-export function parseTextMessageContent(content?: string) {
+export function parseTextMessageContent(
+  content?: string,
+  attachmentUrls?: Record<string, string>,
+) {
   if (!content) return '';
 
   try {
-    return JSON.parse(content) as EditorState;
+    const state = JSON.parse(content);
+    if (attachmentUrls) {
+      signImageNodes(state?.root, attachmentUrls);
+    }
+    return state as EditorState;
   } catch {
     return content;
   }
 }
 
+type ImageNodeJSON = {
+  src?: string;
+  isFromSynclan?: boolean;
+  children?: ImageNodeJSON[];
+};
+
+/** 将已上传图片的路径替换为签名地址 */
+function signImageNodes(
+  node: ImageNodeJSON | undefined,
+  attachmentUrls: Record<string, string>,
+) {
+  if (!node) return;
+
+  if (node.isFromSynclan && node.src && attachmentUrls[node.src]) {
+    node.src = attachmentUrls[node.src];
+  }
+
+  node.children?.forEach((child) => signImageNodes(child, attachmentUrls));
+}
+
 export function parseTextMessageContentForSend(
   content: string,
 ): EditorStateJSON | null {
@@ -94,3 +121,19 @@ export function parseMessageExtra<T>(extra?: string): T | undefined {
 
   return JSON.parse(extra) as T;
 }
+
+export function formatFileSize(size: number) {
+  if (size < 1024) {
+    return `${size} B`;
+  }
+
+  if (size < 1024 * 1024) {
+    return `${(size / 1024).toFixed(1)} KB`;
+  }
+
+  if (size < 1024 * 1024 * 1024) {
+    return `${(size / 1024 / 1024).toFixed(1)} MB`;
+  }
+
+  return `${(size / 1024 / 1024 / 1024).toFixed(1)} GB`;
+}
@@ -2,6 +2,8 @@ import { QRCodeCanvas } from 'qrcode.react';
 import { useImperativeHandle, useState, type Ref } from 'react';
 import { useTranslation } from 'react-i18next';
 
+import { isWeb } from '@/lib/constant';
+import { createPairingLink } from '@/services/cmd';
 import { useAppServerStore } from '@/stores';
 
 import {
@@ -20,12 +22,19 @@ type QRCodeDialogProps = {
   ref?: Ref<QRCodeDialogRef>;
 };
 
+// AB:CD:EF... 便于与浏览器证书信息对照
+function formatFingerprint(fingerprint: string) {
+  return fingerprint.match(/.{1,2}/g)?.join(':') ?? fingerprint;
+}
+
 function QRCodeDialog({ ref }: QRCodeDialogProps) {
   const [open, setOpen] = useState<boolean>(false);
+  const [link, setLink] = useState<PairingLink | null>(null);
 
   const { t } = useTranslation();
 
   const domain = useAppServerStore((s) => s.domain);
+  const domains = useAppServerStore((s) => s.domains);
 
   useImperativeHandle(ref, () => ({
     open: onOpen,
@@ -33,6 +42,13 @@ function QRCodeDialog({ ref }: QRCodeDialogProps) {
 
   const onOpen = () => {
     setOpen(true);
+    setLink(null);
+    if (isWeb) return;
+
+    // 每次打开都生成新的一次性授权
+    createPairingLink()
+      .then(setLink)
+      .catch(() => setLink(null));
   };
 
   return (
@@ -46,7 +62,7 @@ function QRCodeDialog({ ref }: QRCodeDialogProps) {
           <div className='rounded-sm border p-2'>
             <QRCodeCanvas
               size={224}
-              value={domain}
+              value={link?.url ?? domain}
               // bgColor='#000000'
               // fgColor='#ffffff'
             />
@@ -74,6 +90,29 @@ function QRCodeDialog({ ref }: QRCodeDialogProps) {
                 {domain}
               </a>
             </p>
+            {domains.length > 1 && (
+              <p className='mt-1 text-xs leading-5 font-light break-all text-muted-foreground'>
+                {t('qrCodeDialog.otherAddresses')}&nbsp;
+                {domains.slice(1).join(', ')}
+              </p>
+            )}
+            {link && (
+              <p className='mt-3 text-xs leading-5 font-light text-muted-foreground'>
+                {t('qrCodeDialog.oneTime')}
+              </p>
+            )}
+            {link?.fingerprint && (
+              <p className='mt-3 text-xs leading-5 font-light break-all'>
+                {t('qrCodeDialog.fingerprint')}&nbsp;
+                <span className='font-mono'>
+                  {formatFingerprint(link.fingerprint)}
+                </span>
+                <br />
+                <span className='text-muted-foreground font-light'>
+                  {t('qrCodeDialog.fingerprintHint')}
+                </span>
+              </p>
+            )}
           </div>
         </div>
       </DialogContent>
@@ -1,4 +1,4 @@
-import { CirclePlus, File, Image } from 'lucide-react';
+import { CirclePlus, File, Folder, Image, Send } from 'lucide-react';
 import { useRef, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 
@@ -12,6 +12,7 @@ import {
   TooltipContent,
   TooltipTrigger,
 } from '@/components/ui';
+import type { BundleEntry } from '@/services/upload';
 
 // const MAX_FILE_SIZE = 200 * 1024 * 1024; // 200MB
 const ACCEPT_MEDIA_TYPES = ['image/', 'video/'];
@@ -19,13 +20,23 @@ const ACCEPT_MEDIA_TYPES = ['image/', 'video/'];
 type Props = {
   onSelectMedia?: (files: File[]) => Promise<void>;
   onSelectFile?: (files: File[]) => Promise<void>;
+  onSelectFolder?: (name: string, entries: BundleEntry[]) => Promise<void>;
+  /** 直接发送，对方接受后才开始传输，不在主机上保留文件 */
+  onSelectDirect?: (files: File[]) => Promise<void>;
 };
 
-function TransmitterMoreMenu({ onSelectMedia, onSelectFile }: Props) {
+function TransmitterMoreMenu({
+  onSelectMedia,
+  onSelectFile,
+  onSelectFolder,
+  onSelectDirect,
+}: Props) {
   const [menuOpen, setMenuOpen] = useState<boolean>(false);
 
   const mediaInputRef = useRef<HTMLInputElement>(null);
   const fileInputRef = useRef<HTMLInputElement>(null);
+  const folderInputRef = useRef<HTMLInputElement>(null);
+  const directInputRef = useRef<HTMLInputElement>(null);
 
   const { t } = useTranslation();
 
@@ -92,6 +103,41 @@ function TransmitterMoreMenu({ onSelectMedia, onSelectFile }: Props) {
     await onSelectFile?.(validFiles);
   };
 
+  const handleDirectChange = async (
+    evt: React.ChangeEvent<HTMLInputElement>,
+  ) => {
+    const files = Array.from(evt.target.files ?? []);
+
+    evt.target.value = '';
+
+    if (!files.length) {
+      return;
+    }
+
+    await onSelectDirect?.(files);
+  };
+
+  const handleFolderChange = async (
+    evt: React.ChangeEvent<HTMLInputElement>,
+  ) => {
+    const files = Array.from(evt.target.files ?? []);
+
+    evt.target.value = '';
+
+    if (!files.length) {
+      return;
+    }
+
+    // webkitRelativePath 形如 `folder/sub/a.txt`，第一段为所选文件夹名
+    const name = files[0].webkitRelativePath.split('/')[0];
+    const entries = files.map((file) => ({
+      file,
+      path: file.webkitRelativePath.split('/').slice(1).join('/'),
+    }));
+
+    await onSelectFolder?.(name, entries);
+  };
+
   return (
     <>
       <input
@@ -109,6 +155,21 @@ function TransmitterMoreMenu({ onSelectMedia, onSelectFile }: Props) {
         hidden
         onChange={handleFileChange}
       />
+      <input
+        ref={folderInputRef}
+        type='file'
+        hidden
+        // 非标准属性，通过展开传入以绕过 React 类型检查
+        {...{ webkitdirectory: '' }}
+        onChange={handleFolderChange}
+      />
+      <input
+        ref={directInputRef}
+        type='file'
+        multiple
+        hidden
+        onChange={handleDirectChange}
+      />
 
       <DropdownMenu open={menuOpen} onOpenChange={setMenuOpen}>
         <Tooltip delayDuration={300}>
@@ -145,6 +206,24 @@ function TransmitterMoreMenu({ onSelectMedia, onSelectFile }: Props) {
             <File />
             {t('transmitterMoreMenu.file')}
           </DropdownMenuItem>
+          <DropdownMenuItem
+            onSelect={() => {
+              folderInputRef.current?.click();
+            }}
+          >
+            <Folder />
+            {t('transmitterMoreMenu.folder')}
+          </DropdownMenuItem>
+          {onSelectDirect && (
+            <DropdownMenuItem
+              onSelect={() => {
+                directInputRef.current?.click();
+              }}
+            >
+              <Send />
+              {t('transmitterMoreMenu.sendDirectly')}
+            </DropdownMenuItem>
+          )}
         </DropdownMenuContent>
       </DropdownMenu>
     </>
@@ -43,10 +43,14 @@ function Transmitter({
   onSend,
   onSelectFile,
   onSelectMedia,
+  onSelectFolder,
+  onSelectDirect,
 }: {
   onSend?: CompositionInputProps['onSend'];
   onSelectFile?: TransmitterMoreMenuProps['onSelectFile'];
   onSelectMedia?: TransmitterMoreMenuProps['onSelectMedia'];
+  onSelectFolder?: TransmitterMoreMenuProps['onSelectFolder'];
+  onSelectDirect?: TransmitterMoreMenuProps['onSelectDirect'];
 }) {
   const [isEmpty, setIsEmpty] = useState<boolean>(true);
   const [lineOverflow, setLineOverflow] = useState<boolean>(false);
@@ -202,6 +206,8 @@ function Transmitter({
               <TransmitterMoreMenu
                 onSelectFile={onSelectFile}
                 onSelectMedia={onSelectMedia}
+                onSelectFolder={onSelectFolder}
+                onSelectDirect={onSelectDirect}
               />
             </li>
             {/*{!isMobile && (
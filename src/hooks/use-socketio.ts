@@ -1,8 +1,9 @@
-import { useCallback, useEffect, useRef, useState } from 'react';
+import { useCallback, useEffect, useMemo, useRef, useState } from 'react';
 import type { ManagerOptions, Socket, SocketOptions } from 'socket.io-client';
 import { io as SocketIO } from 'socket.io-client';
 
 import { HttpStatus } from '@/lib/types';
+import type { TransferOffer, TransferProgress } from '@/services/transfer';
 
 export enum ReadyState {
   UNINSTANTIATED = -1,
@@ -15,8 +16,20 @@ export enum ReadyState {
 export enum EventNames {
   MESSAGE = 'synclan://message',
   MESSAGEREAD = 'synclan://message:read',
+  TRANSFER_OFFER = 'synclan://transfer:offer',
+  TRANSFER_ANSWER = 'synclan://transfer:answer',
+  TRANSFER_CANCEL = 'synclan://transfer:cancel',
+  TRANSFER_PROGRESS = 'synclan://transfer:progress',
+  SERVER_RESTART = 'synclan://server:restart',
 }
 
+/** 服务重启前通知，urls 为重启后的地址，随机端口或端口可能被占用而改用空闲端口时为空 */
+export type ServerRestart = {
+  urls: string[];
+};
+
+type MessageEvents = EventNames.MESSAGE | EventNames.MESSAGEREAD;
+
 type AckResponse<T> = {
   statusCode: HttpStatus;
   message?: string;
@@ -24,18 +37,38 @@ type AckResponse<T> = {
 };
 
 type ListenEvents = Record<
-  EventNames,
+  MessageEvents,
   (message: IMessage, cb: (resp: AckResponse<unknown>) => void) => void
->;
+> & {
+  [EventNames.TRANSFER_OFFER]: (offer: TransferOffer) => void;
+  [EventNames.TRANSFER_PROGRESS]: (progress: TransferProgress) => void;
+  [EventNames.SERVER_RESTART]: (restart: ServerRestart) => void;
+};
 type EmitEvents = Record<
-  EventNames,
+  MessageEvents,
   (message: IMessage, cb: (resp: AckResponse<IMessage>) => void) => void
->;
+> & {
+  [EventNames.TRANSFER_OFFER]: (
+    offer: Omit<TransferOffer, 'transferId' | 'sender'>,
+    cb: (resp: AckResponse<TransferOffer>) => void,
+  ) => void;
+  [EventNames.TRANSFER_ANSWER]: (
+    answer: { transferId: string; accept: boolean },
+    cb: (resp: AckResponse<null>) => void,
+  ) => void;
+  [EventNames.TRANSFER_CANCEL]: (
+    cancel: { transferId: string },
+    cb: (resp: AckResponse<null>) => void,
+  ) => void;
+};
 
 export type UseSocketOptions = Partial<
   ManagerOptions &
     SocketOptions & {
       onMessage: (message: IMessage) => void;
+      onTransferOffer: (offer: TransferOffer) => void;
+      onTransferProgress: (progress: TransferProgress) => void;
+      onServerRestart: (restart: ServerRestart) => void;
     }
 >;
 
@@ -44,6 +77,17 @@ export type SendMessage = (
   timeout?: number,
 ) => Promise<AckResponse<IMessage>>;
 
+export type TransferActions = {
+  offerTransfer: (
+    offer: Omit<TransferOffer, 'transferId' | 'sender'>,
+  ) => Promise<AckResponse<TransferOffer>>;
+  answerTransfer: (
+    transferId: string,
+    accept: boolean,
+  ) => Promise<AckResponse<null>>;
+  cancelTransfer: (transferId: string) => Promise<AckResponse<null>>;
+};
+
 export function useSocketIO(url: string, options: UseSocketOptions = {}) {
   const [state, setState] = useState<ReadyState>(
     () => ReadyState.UNINSTANTIATED,
@@ -90,6 +134,26 @@ export function useSocketIO(url: string, options: UseSocketOptions = {}) {
       socket.on(EventNames.MESSAGE, onMessage);
     }
 
+    const onTransferOffer: ListenEvents[EventNames.TRANSFER_OFFER] = (
+      offer,
+    ) => {
+      optionsRef.current.onTransferOffer?.(offer);
+    };
+    const onTransferProgress: ListenEvents[EventNames.TRANSFER_PROGRESS] = (
+      progress,
+    ) => {
+      optionsRef.current.onTransferProgress?.(progress);
+    };
+    // 连接断开后 socket.io 会自动重连
+    const onServerRestart: ListenEvents[EventNames.SERVER_RESTART] = (
+      restart,
+    ) => {
+      optionsRef.current.onServerRestart?.(restart);
+    };
+    socket.on(EventNames.TRANSFER_OFFER, onTransferOffer);
+    socket.on(EventNames.TRANSFER_PROGRESS, onTransferProgress);
+    socket.on(EventNames.SERVER_RESTART, onServerRestart);
+
     return () => {
       socket.off('connect', onConnect);
       socket.off('disconnect', onDisconnect);
@@ -97,6 +161,9 @@ export function useSocketIO(url: string, options: UseSocketOptions = {}) {
       if (optionsRef.current?.onMessage && onMessage) {
         socket.off(EventNames.MESSAGE, onMessage);
       }
+      socket.off(EventNames.TRANSFER_OFFER, onTransferOffer);
+      socket.off(EventNames.TRANSFER_PROGRESS, onTransferProgress);
+      socket.off(EventNames.SERVER_RESTART, onServerRestart);
 
       socket.close();
       socketRef.current = null;
@@ -133,8 +200,57 @@ export function useSocketIO(url: string, options: UseSocketOptions = {}) {
     [],
   );
 
+  const emitWithAck = useCallback(
+    <T>(
+      event: keyof EmitEvents,
+      payload: unknown,
+      timeout: number = 10000,
+    ): Promise<AckResponse<T>> => {
+      return new Promise((resolve, reject) => {
+        const socket = socketRef.current as Socket | null;
+        if (!socket?.connected) {
+          return reject({
+            statusCode: HttpStatus.SERVICE_UNAVAILABLE,
+            message: 'disconnected',
+          });
+        }
+
+        socket
+          .timeout(timeout)
+          .emit(event, payload, (err: Error | null, resp: AckResponse<T>) => {
+            if (err) {
+              return reject({
+                statusCode: HttpStatus.REQUEST_TIMEOUT,
+                message: 'timeout',
+              });
+            }
+
+            if (resp.statusCode !== HttpStatus.OK) {
+              return reject(resp);
+            }
+
+            resolve(resp);
+          });
+      });
+    },
+    [],
+  );
+
+  const transferActions = useMemo<TransferActions>(
+    () => ({
+      offerTransfer: (offer) =>
+        emitWithAck<TransferOffer>(EventNames.TRANSFER_OFFER, offer),
+      answerTransfer: (transferId, accept) =>
+        emitWithAck<null>(EventNames.TRANSFER_ANSWER, { transferId, accept }),
+      cancelTransfer: (transferId) =>
+        emitWithAck<null>(EventNames.TRANSFER_CANCEL, { transferId }),
+    }),
+    [emitWithAck],
+  );
+
   return {
     state,
     sendMessage,
+    ...transferActions,
   };
 }
@@ -1,5 +1,5 @@
 import { getBaseUrl } from '@/lib/constant';
-import { DEVICE_ID_STORAGE_KEY } from '@/lib/device';
+import { DEVICE_TOKEN_STORAGE_KEY } from '@/lib/device';
 
 export interface RequestOptions extends RequestInit {
   params?: Record<
@@ -53,7 +53,7 @@ async function request<T>(
   path: string,
   options: RequestOptions = {},
 ): Promise<T> {
-  const token = localStorage.getItem(DEVICE_ID_STORAGE_KEY);
+  const token = localStorage.getItem(DEVICE_TOKEN_STORAGE_KEY);
 
   const headers = new Headers(options.headers);
 
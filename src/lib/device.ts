@@ -1,9 +1,12 @@
 import { v4 as uuidv4 } from 'uuid';
 
+import { HttpError } from '@/lib/api';
 import { isWeb } from '@/lib/constant';
 import { getDeviceById, registerDevice } from '@/services/cmd';
 
 export const DEVICE_ID_STORAGE_KEY = '__SYNCLAN_DEVICE_ID__';
+// 注册时下发的凭证，设备 id 对其他设备可见，不能作为凭证
+export const DEVICE_TOKEN_STORAGE_KEY = '__SYNCLAN_DEVICE_TOKEN__';
 
 export async function getDevice(): Promise<IDevice> {
   let deviceId = localStorage.getItem(DEVICE_ID_STORAGE_KEY);
@@ -12,20 +15,56 @@ export async function getDevice(): Promise<IDevice> {
     localStorage.setItem(DEVICE_ID_STORAGE_KEY, deviceId);
   }
 
-  const device = await getDeviceById(deviceId);
-  if (device) {
-    return device;
+  // 没有凭证时需重新注册，升级前注册的设备会因 id 已存在而换用新 id
+  if (localStorage.getItem(DEVICE_TOKEN_STORAGE_KEY)) {
+    const device = await getDeviceById(deviceId);
+    if (device) {
+      return device;
+    }
   }
 
-  const newDevice: Partial<IDevice> = {
-    id: deviceId,
-    name: generateDefaultDeviceName(deviceId),
+  const grant = isWeb ? takePairingGrant() : undefined;
+  const newDevice = (id: string): Partial<IDevice> & { grant?: string } => ({
+    id,
+    name: generateDefaultDeviceName(id),
     role: isWeb ? 'client' : 'host',
     platform: getPlatform(),
     browser: getBrowser(),
-  };
-  const createdDevice = await registerDevice(newDevice);
-  return createdDevice;
+    grant,
+  });
+
+  let registered: IRegisteredDevice;
+  try {
+    registered = await registerDevice(newDevice(deviceId));
+  } catch (err) {
+    // id 已被注册，凭证丢失或升级前注册的设备只能以新 id 注册，授权码此时尚未使用
+    if (!(err instanceof HttpError) || err.status !== 409) throw err;
+    deviceId = uuidv4();
+    localStorage.setItem(DEVICE_ID_STORAGE_KEY, deviceId);
+    registered = await registerDevice(newDevice(deviceId));
+  }
+
+  const { token, ...device } = registered;
+  localStorage.setItem(DEVICE_TOKEN_STORAGE_KEY, token);
+  return device;
+}
+
+/**
+ * 扫描配对二维码打开时，链接中带有一次性授权
+ * 页面无法得知自身的证书，主机身份需用户对照二维码旁的指纹与浏览器显示的证书
+ */
+function takePairingGrant() {
+  const params = new URLSearchParams(window.location.search);
+  const grant = params.get('grant');
+  if (!grant) return undefined;
+
+  // 授权只能使用一次，不保留在地址栏中
+  params.delete('grant');
+  const search = params.toString() ? `?${params.toString()}` : '';
+  const { pathname, hash } = window.location;
+  window.history.replaceState(null, '', `${pathname}${search}${hash}`);
+
+  return grant;
 }
 
 export function generateDefaultDeviceName(deviceId: string): string {
@@ -123,7 +123,10 @@ export function calculateMediaSize(width?: number, height?: number) {
   };
 }
 
-export function getMediaUrl(content?: string) {
+export function getMediaUrl(
+  content?: string,
+  attachmentUrls?: Record<string, string>,
+) {
   if (!content) {
     return '';
   }
@@ -133,8 +136,23 @@ export function getMediaUrl(content?: string) {
     return content;
   }
 
-  // 已上传资源
-  return `${getAttachmentBaseUrl()}/${content}`;
+  // 已上传资源，优先使用签名地址
+  return `${getAttachmentBaseUrl()}/${attachmentUrls?.[content] ?? content}`;
+}
+
+/**
+ * 缩略图地址，尚未生成缩略图时返回 undefined
+ */
+export function getThumbnailUrl(
+  content?: string,
+  thumbnailUrls?: Record<string, string>,
+) {
+  const thumbnail = content ? thumbnailUrls?.[content] : undefined;
+  if (!thumbnail) {
+    return undefined;
+  }
+
+  return `${getAttachmentBaseUrl()}/${thumbnail}`;
 }
 
 export async function downloadFile(url: string, fileName?: string) {
@@ -31,7 +31,7 @@ import { createAttachmentMessage } from '@/lib/media';
 import { HttpStatus } from '@/lib/types';
 import { cn } from '@/lib/utils';
 import { getMessages } from '@/services/cmd';
-import { uploadFile } from '@/services/upload';
+import { uploadFile, uploadFolder } from '@/services/upload';
 import {
   useCurrentConversation,
   useDeviceStore,
@@ -55,7 +55,7 @@ function DevicesPage() {
 
   const params = useParams();
 
-  const { sendMessage } = useAppContext();
+  const { sendMessage, sendFileDirectly } = useAppContext();
 
   const current = useDeviceStore((s) => s.current);
   const conversation = useCurrentConversation();
@@ -379,6 +379,64 @@ function DevicesPage() {
     );
   };
 
+  const onSelectFolder: TransmitterMoreMenuProps['onSelectFolder'] = async (
+    name,
+    entries,
+  ) => {
+    if (!entries.length) return;
+
+    const deviceId = params.id;
+    if (!deviceId || !current) return;
+
+    const now = Date.now();
+    const message: IMessage = {
+      uuid: uuidv4(),
+      type: 'folder',
+      content: '',
+      plainContent: '[Folder]',
+      sender: current.id,
+      receiver: deviceId,
+      createdAt: now,
+      updatedAt: now,
+      extra: JSON.stringify({
+        name,
+        size: entries.reduce((size, entry) => size + entry.file.size, 0),
+        fileCount: entries.length,
+      }),
+    };
+
+    useMessageAnimationStore.getState().add(message.uuid);
+    addMessage(deviceId, message, current.id);
+
+    if (latestMessageInViewRef.current) {
+      setTimeout(() => virtualizer.scrollToEnd({ behavior: 'smooth' }));
+    }
+
+    try {
+      const path = await uploadFolder(name, entries);
+
+      const result = await sendMessage({
+        ...message,
+        content: path,
+        updatedAt: Date.now(),
+      });
+      if (result.statusCode === HttpStatus.OK && result.data) {
+        reconcileServerMessage(deviceId, message.uuid, result.data);
+      }
+    } catch {
+      toast.error('Unable to upload the folder');
+    }
+  };
+
+  const onSelectDirect: TransmitterMoreMenuProps['onSelectDirect'] = async (
+    files,
+  ) => {
+    const deviceId = params.id;
+    if (!deviceId) return;
+
+    await Promise.all(files.map((file) => sendFileDirectly(file, deviceId)));
+  };
+
   const onDrop: DragUploadOverlayProps['onDrop'] = async (files) => {
     await onSelectFile(files);
   };
@@ -477,6 +535,8 @@ function DevicesPage() {
               onSend={onSend}
               onSelectFile={onSelectFile}
               onSelectMedia={onSelectMedia}
+              onSelectFolder={onSelectFolder}
+              onSelectDirect={onSelectDirect}
             />
           </footer>
         </div>
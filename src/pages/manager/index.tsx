@@ -1,5 +1,6 @@
 import { useQuery, useQueryClient } from '@tanstack/react-query';
 import {
+  Ban,
   Ellipsis,
   Laptop,
   Monitor,
@@ -25,6 +26,10 @@ import {
   DropdownMenu,
   DropdownMenuContent,
   DropdownMenuItem,
+  DropdownMenuSeparator,
+  DropdownMenuSub,
+  DropdownMenuSubContent,
+  DropdownMenuSubTrigger,
   DropdownMenuTrigger,
   Table,
   TableBody,
@@ -34,11 +39,54 @@ import {
   TableRow,
 } from '@/components/ui';
 import { resolveResourceUrl } from '@/lib/utils';
-import { getDevices, removeDevice } from '@/services/cmd';
+import {
+  blockDevice,
+  getBlockedDevices,
+  getDeviceAccess,
+  getDevices,
+  removeDevice,
+  setDeviceAccess,
+  unblockDevice,
+} from '@/services/cmd';
 import { useDeviceStore } from '@/stores';
 
 function loader() {}
 
+const ASSIGNABLE_ROLES = ['admin', 'member', 'read_only'] as const;
+
+const HOUR = 60 * 60 * 1000;
+const GUEST_DURATIONS = [
+  { label: 'guestForHour', duration: HOUR },
+  { label: 'guestForDay', duration: 24 * HOUR },
+  { label: 'guestForWeek', duration: 7 * 24 * HOUR },
+] as const;
+
+function AccessBadge({ access }: { access?: DeviceAccess }) {
+  const { t } = useTranslation();
+
+  const role = access?.role ?? 'member';
+  const expiresAt = role === 'guest' ? (access?.expiresAt ?? 0) : undefined;
+  const expired = expiresAt !== undefined && expiresAt <= Date.now();
+
+  return (
+    <div className='space-y-0.5'>
+      <Badge variant={expired ? 'destructive' : 'outline'}>
+        {t(`manage.roles.${role}`)}
+      </Badge>
+
+      {expiresAt !== undefined && (
+        <div className='text-muted-foreground text-xs'>
+          {expired
+            ? t('manage.guestExpired')
+            : t('manage.guestUntil', {
+                time: new Date(expiresAt).toLocaleString(),
+              })}
+        </div>
+      )}
+    </div>
+  );
+}
+
 function PlatformIcon({ platform }: { platform?: string }) {
   switch (platform) {
     case 'Android':
@@ -69,6 +117,77 @@ function ManagePage() {
     queryFn: () => getDevices(current?.id),
   });
 
+  const { data: blockedDevices = [] } = useQuery({
+    queryKey: ['blocked_devices'],
+    queryFn: getBlockedDevices,
+  });
+
+  const { data: accessList = [] } = useQuery({
+    queryKey: ['device_access'],
+    queryFn: getDeviceAccess,
+  });
+  const accessById = new Map(
+    accessList.map((access) => [access.deviceId, access]),
+  );
+
+  const handleSetAccess = async (
+    device: IDevice,
+    role: AccessRole,
+    duration?: number,
+  ) => {
+    try {
+      await setDeviceAccess({
+        deviceId: device.id,
+        role,
+        expiresAt: duration ? Date.now() + duration : null,
+      });
+
+      toast.success(t('manage.accessUpdated'));
+
+      await queryClient.invalidateQueries({ queryKey: ['device_access'] });
+    } catch (error) {
+      console.error(error);
+      toast.error(t('manage.accessFailed'));
+    }
+  };
+
+  const handleBlockDevice = async (device: IDevice) => {
+    const ok = await confirm({
+      icon: <Ban />,
+      title: t('manage.blockTitle', { name: device.name }),
+      description: t('manage.blockDescription'),
+      confirmText: t('manage.block'),
+      actionVariant: 'destructive',
+    });
+
+    if (!ok) return;
+
+    try {
+      await blockDevice(device.id);
+
+      toast.success(t('manage.blocked'));
+
+      await Promise.all([
+        queryClient.invalidateQueries({ queryKey: ['devices'] }),
+        queryClient.invalidateQueries({ queryKey: ['blocked_devices'] }),
+      ]);
+    } catch (error) {
+      console.error(error);
+      toast.error(t('manage.blockFailed'));
+    }
+  };
+
+  const handleUnblockDevice = async (device: BlockedDevice) => {
+    try {
+      await unblockDevice(device.id);
+
+      await queryClient.invalidateQueries({ queryKey: ['blocked_devices'] });
+    } catch (error) {
+      console.error(error);
+      toast.error(t('manage.unblockFailed'));
+    }
+  };
+
   const handleRemoveDevice = async (device: IDevice) => {
     const ok = await confirm({
       icon: <TrashIcon />,
@@ -159,6 +278,7 @@ function ManagePage() {
                 <TableRow>
                   <TableHead>{t('manage.device')}</TableHead>
                   <TableHead>{t('manage.role')}</TableHead>
+                  <TableHead>{t('manage.access')}</TableHead>
                   <TableHead>{t('manage.platform')}</TableHead>
                   <TableHead>{t('manage.browser')}</TableHead>
                   <TableHead>{t('manage.updated')}</TableHead>
@@ -169,14 +289,14 @@ function ManagePage() {
               <TableBody>
                 {isPending ? (
                   <TableRow>
-                    <TableCell colSpan={6} className='h-32 text-center'>
+                    <TableCell colSpan={7} className='h-32 text-center'>
                       {t('manage.loading')}
                     </TableCell>
                   </TableRow>
                 ) : devices.length === 0 ? (
                   <TableRow>
                     <TableCell
-                      colSpan={6}
+                      colSpan={7}
                       className='text-muted-foreground h-32 text-center'
                     >
                       {t('manage.noDevicesFound')}
@@ -208,6 +328,14 @@ function ManagePage() {
                         <Badge variant='secondary'>{device.role}</Badge>
                       </TableCell>
 
+                      <TableCell>
+                        {device.homeHost ? (
+                          '-'
+                        ) : (
+                          <AccessBadge access={accessById.get(device.id)} />
+                        )}
+                      </TableCell>
+
                       <TableCell>
                         <div className='flex items-center gap-2'>
                           <PlatformIcon platform={device.platform} />
@@ -244,12 +372,55 @@ function ManagePage() {
                             >
                               {t('manage.copyDeviceId')}
                             </DropdownMenuItem>
+                            {!device.homeHost && (
+                              <DropdownMenuSub>
+                                <DropdownMenuSubTrigger>
+                                  {t('manage.accessRole')}
+                                </DropdownMenuSubTrigger>
+
+                                <DropdownMenuSubContent>
+                                  {ASSIGNABLE_ROLES.map((role) => (
+                                    <DropdownMenuItem
+                                      key={role}
+                                      onClick={() =>
+                                        handleSetAccess(device, role)
+                                      }
+                                    >
+                                      {t(`manage.roles.${role}`)}
+                                    </DropdownMenuItem>
+                                  ))}
+                                  <DropdownMenuSeparator />
+                                  {GUEST_DURATIONS.map(
+                                    ({ label, duration }) => (
+                                      <DropdownMenuItem
+                                        key={label}
+                                        onClick={() =>
+                                          handleSetAccess(
+                                            device,
+                                            'guest',
+                                            duration,
+                                          )
+                                        }
+                                      >
+                                        {t(`manage.${label}`)}
+                                      </DropdownMenuItem>
+                                    ),
+                                  )}
+                                </DropdownMenuSubContent>
+                              </DropdownMenuSub>
+                            )}
                             <DropdownMenuItem
                               variant='destructive'
                               onClick={() => handleRemoveDevice(device)}
                             >
                               {t('manage.remove')}
                             </DropdownMenuItem>
+                            <DropdownMenuItem
+                              variant='destructive'
+                              onClick={() => handleBlockDevice(device)}
+                            >
+                              {t('manage.block')}
+                            </DropdownMenuItem>
                           </DropdownMenuContent>
                         </DropdownMenu>
                       </TableCell>
@@ -260,6 +431,59 @@ function ManagePage() {
             </Table>
           </CardContent>
         </Card>
+
+        {blockedDevices.length > 0 && (
+          <Card>
+            <CardHeader>
+              <CardTitle>{t('manage.blockedDevices')}</CardTitle>
+              <CardDescription>{t('manage.blockedDevicesInfo')}</CardDescription>
+            </CardHeader>
+
+            <CardContent className='p-0'>
+              <Table>
+                <TableHeader>
+                  <TableRow>
+                    <TableHead>{t('manage.device')}</TableHead>
+                    <TableHead>{t('manage.blockedAt')}</TableHead>
+                    <TableHead className='bg-card sticky right-0 z-10 w-12' />
+                  </TableRow>
+                </TableHeader>
+
+                <TableBody>
+                  {blockedDevices.map((device) => (
+                    <TableRow key={device.id}>
+                      <TableCell>
+                        <div className='space-y-0.5'>
+                          <div className='font-medium'>
+                            {device.name || '-'}
+                          </div>
+
+                          <div className='text-muted-foreground text-xs'>
+                            {device.id}
+                          </div>
+                        </div>
+                      </TableCell>
+
+                      <TableCell>
+                        {new Date(device.blockedAt).toLocaleString()}
+                      </TableCell>
+
+                      <TableCell className='bg-card sticky right-0'>
+                        <Button
+                          variant='outline'
+                          size='sm'
+                          onClick={() => handleUnblockDevice(device)}
+                        >
+                          {t('manage.unblock')}
+                        </Button>
+                      </TableCell>
+                    </TableRow>
+                  ))}
+                </TableBody>
+              </Table>
+            </CardContent>
+          </Card>
+        )}
       </div>
     </div>
   );
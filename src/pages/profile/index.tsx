@@ -42,6 +42,7 @@ const formSchema = z.object({
 
 function ProfilePage() {
   const [isEdit, setIsEdit] = useState<boolean>(false);
+  const [preview, setPreview] = useState<string | null>(null);
 
   const current = useDeviceStore((s) => s.current);
   const updateCurrent = useDeviceStore((s) => s.updateCurrent);
@@ -88,6 +89,7 @@ function ProfilePage() {
 
       updateCurrent(device);
       setIsEdit(false);
+      setPreview(null);
     } catch (error) {
       toast.error(
         error instanceof Error ? error.message : t('profile.uploadFailed'),
@@ -121,7 +123,8 @@ function ProfilePage() {
                 disabled={disabled}
                 control={form.control}
                 render={({ field }) => {
-                  const avatarUrl = resolveResourceUrl(field.value);
+                  const avatarUrl =
+                    preview ?? resolveResourceUrl(field.value);
                   return (
                     <Avatar
                       className='relative size-32 overflow-hidden'
@@ -143,9 +146,10 @@ function ProfilePage() {
                           onClick={async (evt) => {
                             evt.stopPropagation();
                             try {
-                              const url = await onPickImage();
-                              if (url) {
-                                field.onChange(url);
+                              const picked = await onPickImage();
+                              if (picked) {
+                                field.onChange(picked.path);
+                                setPreview(picked.preview);
                               }
                             } catch (error) {
                               toast.error(
@@ -1,15 +1,14 @@
 import { zodResolver } from '@hookform/resolvers/zod';
-import { Power } from 'lucide-react';
 import { useEffect, useEffectEvent } from 'react';
 import { useForm } from 'react-hook-form';
 import { useTranslation } from 'react-i18next';
+import { toast } from 'sonner';
 
-import { useConfirm } from '@/components';
 import { FieldGroup } from '@/components/ui';
 import { isWeb } from '@/lib/constant';
 import { applyPendingTheme, cn } from '@/lib/utils';
-import { restartApp } from '@/services/cmd';
-import { useSynclanStore } from '@/stores';
+import { getServerDomain } from '@/services/cmd';
+import { useAppServerStore, useSynclanStore } from '@/stores';
 
 import { AboutSettings } from './about-settings';
 import { AppearanceSettings } from './appearance-settings';
@@ -25,7 +24,7 @@ function SettingsPage() {
   const config = useSynclanStore((s) => s.config);
   const updateConfig = useSynclanStore((s) => s.updateConfig);
 
-  const confirm = useConfirm();
+  const updateDomain = useAppServerStore((s) => s.updateDomain);
   const { i18n, t } = useTranslation();
 
   const form = useForm<SettingsForm>({
@@ -41,7 +40,10 @@ function SettingsPage() {
             enable_silent_start: config?.enable_silent_start ?? false,
             // server
             http_server_port: config?.http_server_port ?? 53317,
+            enable_random_port: config?.enable_random_port ?? false,
             enable_encryption: config?.enable_encryption ?? false,
+            enable_localsend: config?.enable_localsend ?? false,
+            enable_api_docs: config?.enable_api_docs ?? false,
             // storage
             file_upload_dir: config?.file_upload_dir,
             auto_file_clean: `${config?.auto_file_clean ?? 0}`,
@@ -78,7 +80,10 @@ function SettingsPage() {
         enable_silent_start: values.enable_silent_start,
         // server
         http_server_port: values.http_server_port,
+        enable_random_port: values.enable_random_port,
         enable_encryption: values.enable_encryption,
+        enable_localsend: values.enable_localsend,
+        enable_api_docs: values.enable_api_docs,
         // storage
         file_upload_dir: values.file_upload_dir,
         auto_file_clean: parseInt(
@@ -106,27 +111,47 @@ function SettingsPage() {
     if (
       (settings.http_server_port !== undefined &&
         settings.http_server_port !== config?.http_server_port) ||
+      (settings.enable_random_port !== undefined &&
+        settings.enable_random_port !== config?.enable_random_port) ||
       (settings.enable_encryption !== undefined &&
         settings.enable_encryption !== config?.enable_encryption) ||
+      (settings.enable_localsend !== undefined &&
+        settings.enable_localsend !== config?.enable_localsend) ||
+      (settings.enable_api_docs !== undefined &&
+        settings.enable_api_docs !== config?.enable_api_docs) ||
       (settings.file_upload_dir !== undefined &&
         settings.file_upload_dir !== config?.file_upload_dir)
     ) {
       restart = true;
     }
 
-    await updateConfig(settings);
-
-    if (!isWeb && restart) {
-      const ok = await confirm({
-        icon: <Power />,
-        title: t('settings.restartSynclan'),
-        description: t('settings.restartDescription'),
-        confirmText: t('settings.restart'),
-        actionVariant: 'destructive',
+    try {
+      await updateConfig(settings);
+    } catch (error) {
+      // 服务重启失败，后端已回滚配置
+      toast.error(t('settings.server.restartFailed'), {
+        description: String(error),
+      });
+      const previous = useSynclanStore.getState().config;
+      form.reset({
+        ...form.getValues(),
+        http_server_port: previous?.http_server_port ?? 53317,
+        enable_random_port: previous?.enable_random_port ?? false,
+        enable_encryption: previous?.enable_encryption ?? false,
+        enable_localsend: previous?.enable_localsend ?? false,
+        enable_api_docs: previous?.enable_api_docs ?? false,
+        file_upload_dir: previous?.file_upload_dir,
       });
+      return;
+    }
 
-      if (ok) {
-        await restartApp();
+    if (!isWeb && restart) {
+      // 服务已在应用内重启，地址变了就重新加载以连接新地址
+      const domains = await getServerDomain();
+      if (domains[0] !== window.__SYNCLAN_SERVER_DOMAIN__) {
+        window.location.reload();
+      } else {
+        updateDomain(domains);
       }
     }
   });
@@ -115,6 +115,53 @@ function ServerSettings({ form }: { form: UseFormReturn<SettingsForm> }) {
             )}
           />
           <Separator />
+          <Controller
+            name='enable_random_port'
+            control={form.control}
+            render={({ field, fieldState }) => (
+              <Field data-invalid={fieldState.invalid}>
+                <FieldLabel
+                  htmlFor='synclan-enable-random-port'
+                  className='has-data-checked:bg-transparent dark:has-data-checked:bg-transparent'
+                >
+                  <Item
+                    variant='muted'
+                    className='hover:bg-muted rounded-none py-3'
+                  >
+                    <ItemContent>
+                      <ItemTitle>
+                        {t('settings.server.enableRandomPort')}
+                        <Tooltip>
+                          <TooltipTrigger asChild>
+                            <CircleHelp className='text-muted-foreground h-4 w-4 cursor-help' />
+                          </TooltipTrigger>
+                          <TooltipContent className='max-w-md'>
+                            <p className='text-sm'>
+                              {t('settings.server.enableRandomPortHelp')}
+                            </p>
+                          </TooltipContent>
+                        </Tooltip>
+                      </ItemTitle>
+
+                      {fieldState.invalid && (
+                        <FieldError errors={[fieldState.error]} />
+                      )}
+                    </ItemContent>
+                    <ItemActions>
+                      <Switch
+                        id='synclan-enable-random-port'
+                        name={field.name}
+                        checked={field.value}
+                        onCheckedChange={field.onChange}
+                        aria-invalid={fieldState.invalid}
+                      />
+                    </ItemActions>
+                  </Item>
+                </FieldLabel>
+              </Field>
+            )}
+          />
+          <Separator />
           <Controller
             name='enable_encryption'
             control={form.control}
@@ -160,6 +207,100 @@ function ServerSettings({ form }: { form: UseFormReturn<SettingsForm> }) {
             )}
           />
           <Separator />
+          <Controller
+            name='enable_localsend'
+            control={form.control}
+            render={({ field, fieldState }) => (
+              <Field data-invalid={fieldState.invalid}>
+                <FieldLabel
+                  htmlFor='synclan-enable-localsend'
+                  className='has-data-checked:bg-transparent dark:has-data-checked:bg-transparent'
+                >
+                  <Item
+                    variant='muted'
+                    className='hover:bg-muted rounded-none py-3'
+                  >
+                    <ItemContent>
+                      <ItemTitle>
+                        {t('settings.server.enableLocalSend')}
+                        <Tooltip>
+                          <TooltipTrigger asChild>
+                            <CircleHelp className='text-muted-foreground h-4 w-4 cursor-help' />
+                          </TooltipTrigger>
+                          <TooltipContent className='max-w-md'>
+                            <p className='text-sm'>
+                              {t('settings.server.enableLocalSendHelp')}
+                            </p>
+                          </TooltipContent>
+                        </Tooltip>
+                      </ItemTitle>
+
+                      {fieldState.invalid && (
+                        <FieldError errors={[fieldState.error]} />
+                      )}
+                    </ItemContent>
+                    <ItemActions>
+                      <Switch
+                        id='synclan-enable-localsend'
+                        name={field.name}
+                        checked={field.value}
+                        onCheckedChange={field.onChange}
+                        aria-invalid={fieldState.invalid}
+                      />
+                    </ItemActions>
+                  </Item>
+                </FieldLabel>
+              </Field>
+            )}
+          />
+          <Separator />
+          <Controller
+            name='enable_api_docs'
+            control={form.control}
+            render={({ field, fieldState }) => (
+              <Field data-invalid={fieldState.invalid}>
+                <FieldLabel
+                  htmlFor='synclan-enable-api-docs'
+                  className='has-data-checked:bg-transparent dark:has-data-checked:bg-transparent'
+                >
+                  <Item
+                    variant='muted'
+                    className='hover:bg-muted rounded-none py-3'
+                  >
+                    <ItemContent>
+                      <ItemTitle>
+                        {t('settings.server.enableApiDocs')}
+                        <Tooltip>
+                          <TooltipTrigger asChild>
+                            <CircleHelp className='text-muted-foreground h-4 w-4 cursor-help' />
+                          </TooltipTrigger>
+                          <TooltipContent className='max-w-md'>
+                            <p className='text-sm'>
+                              {t('settings.server.enableApiDocsHelp')}
+                            </p>
+                          </TooltipContent>
+                        </Tooltip>
+                      </ItemTitle>
+
+                      {fieldState.invalid && (
+                        <FieldError errors={[fieldState.error]} />
+                      )}
+                    </ItemContent>
+                    <ItemActions>
+                      <Switch
+                        id='synclan-enable-api-docs'
+                        name={field.name}
+                        checked={field.value}
+                        onCheckedChange={field.onChange}
+                        aria-invalid={fieldState.invalid}
+                      />
+                    </ItemActions>
+                  </Item>
+                </FieldLabel>
+              </Field>
+            )}
+          />
+          <Separator />
           <Item
             variant='muted'
             className='hover:bg-muted rounded-none py-3'
@@ -13,7 +13,10 @@ const tauriSchema = {
   enable_silent_start: z.boolean(),
   // server
   http_server_port: z.number().int().min(3000).max(65535),
+  enable_random_port: z.boolean(),
   enable_encryption: z.boolean(),
+  enable_localsend: z.boolean(),
+  enable_api_docs: z.boolean(),
   // storage
   file_upload_dir: z.string().min(1),
   auto_file_clean: z.enum(['0', '1', '2', '3', '4']),
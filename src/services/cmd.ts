@@ -19,6 +19,44 @@ export async function exportServerCert() {
   return invoke<void>('export_server_cert');
 }
 
+/**
+ * @description Storage usage per device, the largest first.
+ */
+export async function getUploadUsage() {
+  return invoke<DeviceUsage[]>('get_upload_usage');
+}
+
+/**
+ * @description Pair with another Synclan host, pinned to its certificate fingerprint.
+ * Stays pending until the other host pairs with this one as well.
+ */
+export async function pairHost(url: string, fingerprint: string) {
+  return invoke<PeerHost>('pair_host', { url, fingerprint });
+}
+
+export async function getPeerHosts() {
+  return invoke<PeerHost[]>('get_peer_hosts');
+}
+
+export async function removePeerHost(fingerprint: string) {
+  return invoke<void>('remove_peer_host', { fingerprint });
+}
+
+/**
+ * @description Audit log entries matching the filter, newest first.
+ */
+export async function getAuditLogs(filter?: AuditLogFilter) {
+  return invoke<AuditLog[]>('get_audit_logs', { filter });
+}
+
+/**
+ * @description Save the matching audit log entries as a CSV file.
+ * @returns {Promise<boolean>} `false` when the save dialog was cancelled.
+ */
+export async function exportAuditLogs(filter?: AuditLogFilter) {
+  return invoke<boolean>('export_audit_logs', { filter });
+}
+
 /**
  * @description Get the local IP address of the device.
  * @returns {Promise<string>} IP address.
@@ -27,11 +65,39 @@ export async function getLocalIp() {
   return invoke<string>('get_local_ip');
 }
 
+/**
+ * @description Every URL the server is reachable at, the primary one first.
+ */
 export async function getServerDomain() {
   if (isWeb) {
-    return window.location.origin;
+    return [window.location.origin];
+  }
+  return invoke<string[]>('get_server_domain');
+}
+
+/**
+ * @description SHA-256 fingerprint of the server certificate, null over plain HTTP.
+ */
+export async function getServerFingerprint() {
+  if (isWeb) {
+    const data = await api.get<string | null>('/synclan/fingerprint');
+    return data.payload ?? null;
   }
-  return invoke<string>('get_server_domain');
+  return invoke<string | null>('get_server_fingerprint');
+}
+
+/**
+ * @description The certificate the HTTPS server presents, null when it is not serving HTTPS.
+ */
+export async function getServerCertInfo() {
+  return invoke<CertInfo | null>('get_server_cert_info');
+}
+
+/**
+ * @description Link for the pairing QR code, with a one-time grant to register.
+ */
+export async function createPairingLink() {
+  return invoke<PairingLink>('create_pairing_link');
 }
 
 /**
@@ -122,6 +188,18 @@ export async function devicesDiscover(excludeIds: string[] = []) {
   return invoke<IDevice[]>('devices_discover', { excludeIds });
 }
 
+export async function getDiscoveredHosts() {
+  if (isWeb) {
+    try {
+      const hosts = await api.get<DiscoveredHost[]>('/devices/discover/hosts');
+      return hosts.payload ?? [];
+    } catch {
+      return [];
+    }
+  }
+  return invoke<DiscoveredHost[]>('discovered_hosts');
+}
+
 export async function getDevicesFromLocal() {
   try {
     const conversations = await db.conversations
@@ -134,14 +212,19 @@ export async function getDevicesFromLocal() {
   }
 }
 
+/**
+ * @description Register this device. The returned token authenticates it from
+ * now on and is not returned again. The app gets a new token for its device
+ * whenever it registers.
+ */
 export async function registerDevice(
-  device: Partial<IDevice>,
-): Promise<IDevice> {
+  device: Partial<IDevice> & { grant?: string },
+): Promise<IRegisteredDevice> {
   if (isWeb) {
-    const data = await api.post<IDevice>('/devices', device);
+    const data = await api.post<IRegisteredDevice>('/devices', device);
     return data.payload;
   }
-  return invoke<IDevice>('register_device', { payload: device });
+  return invoke<IRegisteredDevice>('register_device', { payload: device });
 }
 
 export async function removeDevice(id: string) {
@@ -150,6 +233,44 @@ export async function removeDevice(id: string) {
   return invoke<void>('remove_device', { id });
 }
 
+/**
+ * @description Remove the device and keep it from registering again with the
+ * same id or fingerprint. Its live sessions are disconnected.
+ */
+export async function blockDevice(id: string) {
+  if (isWeb) return;
+
+  return invoke<void>('block_device', { id });
+}
+
+export async function unblockDevice(id: string) {
+  if (isWeb) return;
+
+  return invoke<void>('unblock_device', { id });
+}
+
+export async function getBlockedDevices() {
+  if (isWeb) return [];
+
+  return invoke<BlockedDevice[]>('get_blocked_devices');
+}
+
+export async function getDeviceAccess() {
+  if (isWeb) return [];
+
+  return invoke<DeviceAccess[]>('get_device_access');
+}
+
+/**
+ * @description Assign an access role, a guest needs `expiresAt`. It applies
+ * from the next request of the device, whose sockets are reconnected.
+ */
+export async function setDeviceAccess(payload: DeviceAccess) {
+  if (isWeb) return;
+
+  return invoke<void>('set_device_access', { payload });
+}
+
 export async function getSystemTheme() {
   if (isWeb) {
     const media = window.matchMedia('(prefers-color-scheme: dark)');
@@ -177,8 +298,8 @@ export async function getMessages({
 }: FetchMessagesParams): Promise<FetchMessagesResp> {
   if (isWeb) {
     try {
+      // 服务端以当前设备的身份查询，不再携带 selfId
       const response = await api.get<CursorPaginatedMessages>('/messages', {
-        selfId,
         targetId,
         lastId,
         pageSize,
@@ -243,7 +364,8 @@ export async function getOfflineMsgsSummary(
 
 export async function updateMsgAck(payload: MessageAck) {
   if (isWeb) {
-    await api.post<void>('/messages/ack', payload);
+    // 接收方即当前设备，由服务端根据令牌确定
+    await api.post<void>('/messages/ack', { lastAck: payload.lastAck });
     return;
   }
   return invoke<void>('update_ack', { payload });
@@ -298,7 +420,16 @@ export async function uploadAttachment(attachment: Attachment) {
   return payload;
 }
 
-export async function onPickImage(): Promise<string | null> {
+export interface PickedImage {
+  path: string;
+  preview: string;
+}
+
+/**
+ * @description Pick and upload an avatar.
+ * The upload is only public once saved as the device's avatar, so `preview` is a local object URL.
+ */
+export async function onPickImage(): Promise<PickedImage | null> {
   if (isWeb) {
     return new Promise((resolve, reject) => {
       const input = document.createElement('input');
@@ -314,8 +445,8 @@ export async function onPickImage(): Promise<string | null> {
         }
 
         try {
-          const url = await uploadFile(file, { permanent: true });
-          resolve(url);
+          const path = await uploadFile(file, { permanent: true });
+          resolve({ path, preview: URL.createObjectURL(file) });
         } catch (error) {
           reject(error);
         } finally {
@@ -356,6 +487,6 @@ export async function onPickImage(): Promise<string | null> {
 
   const file = new File([blob], fileName, { type: `image/${fileExtension}` });
 
-  const url = await uploadFile(file, { permanent: true });
-  return url;
+  const uploaded = await uploadFile(file, { permanent: true });
+  return { path: uploaded, preview: URL.createObjectURL(file) };
 }
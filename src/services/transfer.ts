@@ -0,0 +1,135 @@
+import { HttpError } from '@/lib/api';
+import { getBaseUrl } from '@/lib/constant';
+import { DEVICE_TOKEN_STORAGE_KEY } from '@/lib/device';
+import { downloadFile } from '@/lib/media';
+
+// 超过该大小不计算哈希，避免一次性读入内存
+const HASH_MAX_SIZE = 256 * 1024 * 1024; // 256MB
+
+export type TransferState =
+  | 'pending'
+  | 'accepted'
+  | 'transferring'
+  | 'completed'
+  | 'declined'
+  | 'cancelled'
+  | 'failed';
+
+export interface TransferOffer {
+  transferId: string;
+  sender: string;
+  receiver: string;
+  name: string;
+  size: number;
+  hash?: string | null;
+  mimeType?: string | null;
+  persist: boolean;
+}
+
+export interface TransferProgress {
+  transferId: string;
+  state: TransferState;
+  transferred: number;
+  total: number;
+  path?: string | null;
+  message?: string | null;
+}
+
+export function isTransferFinished(state: TransferState) {
+  return (
+    state === 'completed' ||
+    state === 'declined' ||
+    state === 'cancelled' ||
+    state === 'failed'
+  );
+}
+
+/**
+ * 构造文件的传输请求，传输 id 与发送方由服务端分配
+ */
+export async function createTransferOffer(
+  file: File,
+  receiver: string,
+  persist = false,
+): Promise<Omit<TransferOffer, 'transferId' | 'sender'>> {
+  return {
+    receiver,
+    name: file.name,
+    size: file.size,
+    hash: await hashFile(file),
+    mimeType: file.type || null,
+    persist,
+  };
+}
+
+/**
+ * 对方接受后，以请求体的形式将文件发送给服务端中转
+ */
+export async function sendTransfer(
+  transferId: string,
+  file: File,
+  signal?: AbortSignal,
+): Promise<string | null> {
+  const response = await fetch(`${getBaseUrl()}/transfer/${transferId}`, {
+    method: 'PUT',
+    headers: authHeaders({ 'Content-Type': 'application/octet-stream' }),
+    body: file,
+    signal,
+  });
+  const data = await response.json().catch(() => null);
+  if (!response.ok) {
+    throw new HttpError(response.status, data);
+  }
+
+  return (data as { payload?: string | null } | null)?.payload ?? null;
+}
+
+/**
+ * 接收中转的文件并保存
+ */
+export async function receiveTransfer(
+  transferId: string,
+  fileName: string,
+  signal?: AbortSignal,
+) {
+  const response = await fetch(`${getBaseUrl()}/transfer/${transferId}`, {
+    headers: authHeaders(),
+    signal,
+  });
+  if (!response.ok) {
+    const data = await response.json().catch(() => null);
+    throw new HttpError(response.status, data);
+  }
+
+  const url = URL.createObjectURL(await response.blob());
+  try {
+    await downloadFile(url, fileName);
+  } finally {
+    // 给浏览器留出开始下载的时间
+    setTimeout(() => URL.revokeObjectURL(url), 10_000);
+  }
+}
+
+function authHeaders(init?: HeadersInit) {
+  const headers = new Headers(init);
+  const token = localStorage.getItem(DEVICE_TOKEN_STORAGE_KEY);
+  if (token) {
+    headers.set('Authorization', `Bearer ${token}`);
+  }
+  return headers;
+}
+
+/**
+ * SHA-256，非安全上下文（如局域网 http）下 crypto.subtle 不可用，此时不校验
+ */
+async function hashFile(file: File) {
+  if (!globalThis.crypto?.subtle || file.size > HASH_MAX_SIZE) {
+    return null;
+  }
+
+  const buffer = await file.arrayBuffer();
+  const digest = await crypto.subtle.digest('SHA-256', buffer);
+  return Array.from(new Uint8Array(digest))
+    .map((b) => b.toString(16).padStart(2, '0'))
+    .join('');
+}
@@ -13,10 +13,19 @@ interface UploadInitResponse {
   totalChunks: number;
 }
 
-interface UploadCompleteResponse {
-  path: string;
+type UploadState = 'uploading' | 'merging' | 'completed' | 'failed';
+
+interface UploadProgress {
+  uploadId: string;
+  state: UploadState;
+  mergedChunks: number;
+  totalChunks: number;
+  path?: string | null;
+  message?: string | null;
 }
 
+const MERGE_POLL_INTERVAL = 1000;
+
 export interface UploadFileOptions {
   permanent?: boolean;
   signal?: AbortSignal;
@@ -103,11 +112,56 @@ async function uploadChunk(
 
   let uploadedBytes = 0;
 
+  await sendChunks(file, uploadId, serverChunkSize, totalChunks, {
+    signal,
+    concurrency,
+    retry,
+    onChunk: (size) => {
+      uploadedBytes += size;
+      onProgress?.(Math.min(uploadedBytes / file.size, 1));
+    },
+  });
+
+  const complete = await api.post<UploadProgress>(
+    '/upload/chunk/complete',
+    {
+      uploadId,
+    },
+    {
+      signal,
+    },
+  );
+
+  const path = await waitForMerge(complete.payload, signal);
+
+  if (!path) {
+    throw new Error('Complete upload failed');
+  }
+
+  onProgress?.(1);
+
+  return path;
+}
+
+async function sendChunks(
+  file: File,
+  uploadId: string,
+  chunkSize: number,
+  totalChunks: number,
+  options: {
+    signal?: AbortSignal;
+    concurrency: number;
+    retry: number;
+    onChunk?: (size: number) => void;
+  },
+) {
+  const { signal, concurrency, retry, onChunk } = options;
+
   await parallel(totalChunks, concurrency, async (index) => {
     signal?.throwIfAborted?.();
 
-    const start = index * serverChunkSize;
-    const end = Math.min(start + serverChunkSize, file.size);
+    const start = index * chunkSize;
+    const end = Math.min(start + chunkSize, file.size);
 
     const blob = file.slice(start, end);
 
@@ -125,32 +179,130 @@ async function uploadChunk(
       retry,
     );
 
-    uploadedBytes += blob.size;
-
-    onProgress?.(Math.min(uploadedBytes / file.size, 1));
+    onChunk?.(blob.size);
   });
+}
 
-  const complete = await api.post<UploadCompleteResponse>(
-    '/upload/chunk/complete',
-    {
-      uploadId,
-    },
+interface BundleInitResponse {
+  bundleId: string;
+  path: string;
+  files: {
+    path: string;
+    uploadId: string;
+    chunkSize: number;
+    totalChunks: number;
+  }[];
+}
+
+export interface BundleEntry {
+  file: File;
+  /** Path relative to the folder, `/` separated */
+  path: string;
+}
+
+/**
+ * Upload a folder as one bundle, every file reuses the chunk upload.
+ * Resolves with the folder path, the content of the `folder` message.
+ */
+export async function uploadFolder(
+  name: string,
+  entries: BundleEntry[],
+  options: Omit<UploadFileOptions, 'permanent'> = {},
+): Promise<string> {
+  const {
+    signal,
+    concurrency = DEFAULT_CONCURRENCY,
+    chunkSize = DEFAULT_CHUNK_SIZE,
+    retry = DEFAULT_RETRY,
+    onProgress,
+  } = options;
+
+  const init = await api.post<BundleInitResponse>(
+    '/upload/bundle/init',
     {
-      signal,
+      name,
+      files: entries.map(({ file, path }) => ({ path, size: file.size })),
+      chunkSize,
     },
+    { signal },
   );
 
-  const path = complete.payload?.path;
+  if (!init.payload) {
+    throw new Error('Init bundle upload failed');
+  }
 
-  if (!path) {
-    throw new Error('Complete upload failed');
+  const { bundleId, path, files } = init.payload;
+  const fileMap = new Map(entries.map((entry) => [entry.path, entry.file]));
+  const totalSize = entries.reduce((sum, { file }) => sum + file.size, 0);
+
+  let uploadedBytes = 0;
+
+  // files one after another, the chunks of each file in parallel
+  for (const session of files) {
+    const file = fileMap.get(session.path);
+    if (!file) {
+      throw new Error(`Missing file ${session.path}`);
+    }
+
+    await sendChunks(
+      file,
+      session.uploadId,
+      session.chunkSize,
+      session.totalChunks,
+      {
+        signal,
+        concurrency,
+        retry,
+        onChunk: (size) => {
+          uploadedBytes += size;
+          onProgress?.(totalSize ? Math.min(uploadedBytes / totalSize, 1) : 1);
+        },
+      },
+    );
   }
 
+  const complete = await api.post<UploadProgress[]>(
+    '/upload/bundle/complete',
+    { bundleId },
+    { signal },
+  );
+
+  await Promise.all(
+    (complete.payload ?? []).map((progress) => waitForMerge(progress, signal)),
+  );
+
   onProgress?.(1);
 
   return path;
 }
 
+/**
+ * The server merges the chunks in the background,
+ * poll the upload progress until the final path is available.
+ */
+async function waitForMerge(
+  progress: UploadProgress | undefined,
+  signal?: AbortSignal,
+): Promise<string | null | undefined> {
+  let current = progress;
+
+  while (current && current.state !== 'completed') {
+    if (current.state === 'failed') {
+      throw new Error(current.message ?? 'Merge upload failed');
+    }
+
+    signal?.throwIfAborted?.();
+    await sleep(MERGE_POLL_INTERVAL);
+
+    const resp = await api.get<UploadProgress>(
+      `/upload/chunk/${current.uploadId}`,
+    );
+    current = resp.payload;
+  }
+
+  return current?.path;
+}
+
 async function parallel(
   total: number,
   concurrency: number,
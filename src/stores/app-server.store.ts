@@ -2,18 +2,23 @@ import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
 
 type AppServerState = {
+  // 主地址，即 domains[0]
   domain: string;
-  updateDomain: (domain: string) => void;
+  domains: string[];
+  updateDomain: (domains: string[]) => void;
 };
 
 export const useAppServerStore = create<AppServerState>()(
   immer((set) => ({
     domain: '',
+    domains: [],
 
-    updateDomain: (domain) => {
+    updateDomain: (domains) => {
       set((state) => {
+        const domain = domains[0] ?? '';
         window.__SYNCLAN_SERVER_DOMAIN__ = domain;
         state.domain = domain;
+        state.domains = domains;
       });
     },
   })),
@@ -8,4 +8,5 @@ export * from './device.store';
 export * from './im.store';
 export * from './message-animation.store';
 export * from './synclan.store';
+export * from './transfer.store';
 export * from './updater.store';
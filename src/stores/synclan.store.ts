@@ -19,6 +19,7 @@ let previousConfig: ISynclanConfig | null = null;
 let updateConfigPromiseResolver:
   | ((value: void | PromiseLike<void>) => void)
   | null = null;
+let updateConfigPromiseRejecter: ((reason: unknown) => void) | null = null;
 
 const storage: PersistStorage<Pick<SynclanState, 'config'>> = {
   getItem: async (_name) => {
@@ -52,10 +53,19 @@ const storage: PersistStorage<Pick<SynclanState, 'config'>> = {
       return;
     }
 
-    if (isWeb) {
-      await patchSynclanConfig(config);
-    } else {
-      await patchSynclanConfig(patch);
+    try {
+      if (isWeb) {
+        await patchSynclanConfig(config);
+      } else {
+        await patchSynclanConfig(patch);
+      }
+    } catch (error) {
+      // 后端拒绝了修改（如新端口无法监听），恢复为之前的配置
+      useSynclanStore.setState({ config: structuredClone(previousConfig) });
+      updateConfigPromiseRejecter?.(error);
+      updateConfigPromiseResolver = null;
+      updateConfigPromiseRejecter = null;
+      return;
     }
 
     previousConfig = structuredClone(config);
@@ -63,6 +73,7 @@ const storage: PersistStorage<Pick<SynclanState, 'config'>> = {
     updateConfigPromiseResolver?.();
 
     updateConfigPromiseResolver = null;
+    updateConfigPromiseRejecter = null;
   },
   removeItem: noop,
 };
@@ -81,8 +92,9 @@ export const useSynclanStore = create<SynclanState>()(
           Object.assign(state.config, patch);
         });
 
-        await new Promise<void>((resolve) => {
+        await new Promise<void>((resolve, reject) => {
           updateConfigPromiseResolver = resolve;
+          updateConfigPromiseRejecter = reject;
         });
       },
       updateTheme: async (theme) => {
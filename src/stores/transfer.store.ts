@@ -0,0 +1,47 @@
+import { create } from 'zustand';
+import { immer } from 'zustand/middleware/immer';
+
+import type { TransferOffer, TransferProgress } from '@/services/transfer';
+
+export type TransferDirection = 'incoming' | 'outgoing';
+
+export type TransferItem = {
+  offer: TransferOffer;
+  direction: TransferDirection;
+  progress?: TransferProgress;
+};
+
+type TransferStore = {
+  transfers: Map<string, TransferItem>;
+
+  add: (offer: TransferOffer, direction: TransferDirection) => void;
+  update: (progress: TransferProgress) => void;
+  remove: (transferId: string) => void;
+  get: (transferId: string) => TransferItem | undefined;
+};
+
+export const useTransferStore = create<TransferStore>()(
+  immer((set, get) => ({
+    transfers: new Map(),
+
+    add: (offer, direction) =>
+      set((state) => {
+        state.transfers.set(offer.transferId, { offer, direction });
+      }),
+
+    update: (progress) =>
+      set((state) => {
+        const item = state.transfers.get(progress.transferId);
+        if (item) {
+          item.progress = progress;
+        }
+      }),
+
+    remove: (transferId) =>
+      set((state) => {
+        state.transfers.delete(transferId);
+      }),
+
+    get: (transferId) => get().transfers.get(transferId),
+  })),
+);
@@ -11,11 +11,34 @@ interface ISynclanConfig {
   auto_check_update?: boolean;
   // server
   http_server_port?: number;
+  // 监听的 IP 地址或网卡名称，如 0.0.0.0、::、eth0，为空时监听所有 IPv4 网卡
+  listen_addresses?: string[];
   enable_encryption?: boolean;
+  // HTTPS 之外的 HTTP 端口，只提供证书下载并重定向到 HTTPS，0 表示关闭
+  http_redirect_port?: number;
+  // 允许跨域访问的来源，需与协议、主机和端口完全一致，服务器自身的地址始终允许
+  cors_allowed_origins?: string[];
+  enable_localsend?: boolean;
+  // 是否提供 /api/docs 接口文档，未设置时仅在开发版本中提供
+  enable_api_docs?: boolean;
   enable_random_port?: boolean;
+  // 自定义证书，文件优先于粘贴的 PEM，空字符串表示不使用
+  tls_cert_file?: string;
+  tls_key_file?: string;
+  tls_cert_pem?: string;
+  tls_key_pem?: string;
+  // 每台设备每分钟允许的消息、上传和发现请求次数，0 表示不限制
+  rate_limit_messages?: number;
+  rate_limit_uploads?: number;
+  rate_limit_discovery?: number;
   // storage
   file_upload_dir?: string;
   auto_file_clean?: 0 | 1 | 2 | 3 | 4;
+  upload_device_quota?: number;
+  upload_total_quota?: number;
+  upload_min_free_space?: number;
+  upload_mime_allowlist?: string[];
+  upload_mime_denylist?: string[];
   // log
   app_log_level?: 'trace' | 'debug' | 'info' | 'warn' | 'error' | 'silent';
   app_log_max_size?: number;
@@ -23,7 +46,8 @@ interface ISynclanConfig {
   auto_log_clean?: 0 | 1 | 2 | 3 | 4;
 }
 
-type DeviceRole = 'host' | 'client';
+// localsend: LocalSend 应用，只能通过 LocalSend 协议发送文件
+type DeviceRole = 'host' | 'client' | 'localsend';
 
 interface IDevice {
   id: string;
@@ -33,10 +57,133 @@ interface IDevice {
   role: DeviceRole;
   platform?: string;
   browser?: string;
+  // 设备注册在其他已配对的主机上时为该主机 id
+  homeHost?: string | null;
   createdAt: number;
   updatedAt: number;
 }
 
+/**
+ * 注册成功后返回，token 为之后请求与连接的凭证，仅返回这一次
+ */
+interface IRegisteredDevice extends IDevice {
+  token: string;
+}
+
+/**
+ * 局域网内通过组播发现的其他 Synclan 主机
+ */
+interface DiscoveredHost {
+  id: string;
+  alias: string;
+  address: string;
+  port: number;
+  protocol: 'http' | 'https';
+  fingerprint?: string | null;
+  version: string;
+  url: string;
+}
+
+/**
+ * HTTPS 服务当前使用的证书
+ */
+interface CertInfo {
+  source: 'custom' | 'local-ca';
+  subject: string;
+  issuer: string;
+  subjectAltNames: string[];
+  notBefore: number;
+  notAfter: number;
+  fingerprint: string;
+  // 对端固定的指纹，证书链最后一张证书
+  pinnedFingerprint: string;
+  chainLength: number;
+}
+
+/**
+ * 配对二维码的内容，grant 只能使用一次
+ */
+interface PairingLink {
+  url: string;
+  fingerprint?: string | null;
+  grant: string;
+  expiresAt: number;
+}
+
+/**
+ * 被屏蔽的设备，其 id 与指纹都不能再次注册
+ */
+interface BlockedDevice {
+  id: string;
+  name: string;
+  fingerprintId?: string | null;
+  blockedAt: number;
+}
+
+type AccessRole = 'admin' | 'member' | 'guest' | 'read_only';
+
+/**
+ * 主机为设备分配的访问角色，未分配的设备为 member，主机本身始终为 admin
+ */
+interface DeviceAccess {
+  deviceId: string;
+  role: AccessRole;
+  // 仅 guest 使用，到期后失去所有访问权限
+  expiresAt?: number | null;
+  updatedAt?: number;
+}
+
+/**
+ * 通过证书指纹互相信任的其他 Synclan 主机
+ */
+interface PeerHost {
+  fingerprint: string;
+  id?: string | null;
+  alias: string;
+  url: string;
+  pairedAt?: number | null;
+  createdAt: number;
+}
+
+interface DeviceUsage {
+  deviceId: string;
+  deviceName?: string | null;
+  totalSize: number;
+  fileCount: number;
+}
+
+type AuditAction =
+  | 'register'
+  | 'connect'
+  | 'upload'
+  | 'delete_message'
+  | 'delete_conversation'
+  | 'verify_access_code';
+
+type AuditOutcome = 'success' | 'failure';
+
+interface AuditLog {
+  id: number;
+  action: AuditAction;
+  deviceId?: string | null;
+  ip?: string | null;
+  userAgent?: string | null;
+  outcome: AuditOutcome;
+  detail?: string | null;
+  createdAt: number;
+}
+
+// 时间为毫秒时间戳，limit 为空时返回全部
+interface AuditLogFilter {
+  deviceId?: string;
+  action?: AuditAction;
+  outcome?: AuditOutcome;
+  since?: number;
+  until?: number;
+  limit?: number;
+  offset?: number;
+}
+
 type LastMessage = Omit<IMessage, 'content'>;
 
 interface IConversations {